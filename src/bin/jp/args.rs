@@ -0,0 +1,175 @@
+//! コマンドラインの引数を解析する
+//!
+//! 短いオプションはまとめて書ける (`-cm`)。値を取るオプションは `--indent 2`、`--indent=2`、`-p/a`、`-p /a` のように書く。
+//! `--` より後の引数と、`-` だけの引数はオプションとして扱わない。
+
+/// 1つのオプションの定義
+#[derive(Debug, PartialEq)]
+pub struct Opt {
+    /// `-c` のような短い名前
+    pub short: Option<char>,
+    /// `--color` のような長い名前 (`--` を除く)
+    pub long: &'static str,
+    /// 長い名前の別名
+    pub alias: Option<&'static str>,
+    /// 値を取る場合は、ヘルプに表示する値の名前
+    pub value: Option<&'static str>,
+    /// ヘルプに表示する説明。改行で複数行に分けられる。
+    pub help: &'static str,
+}
+
+/// 解析した1つの引数
+#[derive(Debug, PartialEq)]
+pub enum Arg<'a> {
+    /// オプションと、その値
+    Opt(&'a Opt, Option<String>),
+    /// オプションではない引数
+    Value(String),
+}
+
+/// `args` を `options` の定義に従って解析する
+pub fn parse<'a>(args: impl IntoIterator<Item = String>, options: &'a [Opt]) -> Result<Vec<Arg<'a>>, String> {
+    let mut parsed: Vec<Arg> = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.extend(args.by_ref().map(Arg::Value));
+            break;
+        }
+        if let Some(name) = arg.strip_prefix("--") {
+            let (name, value): (&str, Option<String>) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            let Some(opt) = options.iter().find(|o| o.long == name || o.alias == Some(name)) else {
+                return Err(format!("an unrecognized option --{}", name));
+            };
+            let value: Option<String> = match (opt.value, value) {
+                (None, Some(_)) => return Err(format!("--{} doesn't take a value", name)),
+                (None, None) => None,
+                (Some(_), Some(value)) => Some(value),
+                (Some(v), None) => Some(args.next().ok_or_else(|| format!("--{} requires <{}>", name, v))?),
+            };
+            parsed.push(Arg::Opt(opt, value));
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+            for (i, c) in shorts.char_indices() {
+                let Some(opt) = options.iter().find(|o| o.short == Some(c)) else {
+                    return Err(format!("an unrecognized option -{}", c));
+                };
+                let Some(v) = opt.value else {
+                    parsed.push(Arg::Opt(opt, None));
+                    continue;
+                };
+                // 値を取るオプションより後の文字は、そのオプションの値として扱う
+                let rest: &str = &shorts[i + c.len_utf8()..];
+                let value: String = match rest.is_empty() {
+                    true => args.next().ok_or_else(|| format!("-{} requires <{}>", c, v))?,
+                    false => rest.to_string(),
+                };
+                parsed.push(Arg::Opt(opt, Some(value)));
+                break;
+            }
+        } else {
+            parsed.push(Arg::Value(arg));
+        }
+    }
+    Ok(parsed)
+}
+
+/// ヘルプに表示するオプションの一覧を作る
+pub fn help(options: &[Opt]) -> String {
+    /// オプションの名前の列の幅
+    const WIDTH: usize = 22;
+
+    let mut help: String = String::new();
+    for opt in options {
+        let value: String = opt.value.map(|v| format!(" <{}>", v)).unwrap_or_default();
+        let mut names: Vec<String> = vec![];
+        names.extend(opt.short.map(|c| format!("-{}", c)));
+        names.push(format!("--{}{}", opt.long, value));
+        names.extend(opt.alias.map(|a| format!("--{}{}", a, value)));
+        let names: String = names.join(", ");
+
+        // 名前が列に収まらない場合は、説明を次の行から書く
+        let lines: Vec<&str> = opt.help.lines().collect();
+        let rest: &[&str] = match lines.split_first() {
+            Some((first, rest)) if names.len() <= WIDTH => {
+                help.push_str(&format!("    {:<WIDTH$} {}\n", names, first));
+                rest
+            }
+            _ => {
+                help.push_str(&format!("    {}\n", names));
+                &lines
+            }
+        };
+        for line in rest {
+            help.push_str(&format!("    {:<WIDTH$} {}\n", "", line));
+        }
+    }
+    help
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use super::{help, parse, Arg, Opt};
+
+    const OPTIONS: &[Opt] = &[
+        Opt { short: Some('c'), long: "color", alias: None, value: None, help: "Color" },
+        Opt { short: Some('m'), long: "minimize", alias: None, value: None, help: "Minimize" },
+        Opt { short: None, long: "max-width", alias: Some("compact-width"), value: Some("N"), help: "Width\nSecond line" },
+        Opt { short: Some('p'), long: "pointer", alias: None, value: Some("POINTER"), help: "Pointer" },
+    ];
+
+    fn args(args: &[&str]) -> Result<Vec<Arg<'static>>, String> {
+        parse(args.iter().map(|a| a.to_string()), OPTIONS)
+    }
+
+    #[test]
+    fn test_parse() {
+        let [color, minimize, width, pointer] = [&OPTIONS[0], &OPTIONS[1], &OPTIONS[2], &OPTIONS[3]];
+        let value = |v: &str| Some(v.to_string());
+        assert_eq!(
+            args(&["-cm", "a.json", "--max-width=80", "--compact-width", "40", "-p/a", "-cp", "/b"]),
+            Ok(vec![
+                Arg::Opt(color, None),
+                Arg::Opt(minimize, None),
+                Arg::Value("a.json".to_string()),
+                Arg::Opt(width, value("80")),
+                Arg::Opt(width, value("40")),
+                Arg::Opt(pointer, value("/a")),
+                Arg::Opt(color, None),
+                Arg::Opt(pointer, value("/b")),
+            ])
+        );
+        // 値は - で始まっていてもよい
+        assert_eq!(args(&["--pointer", "-x"]), Ok(vec![Arg::Opt(pointer, value("-x"))]));
+        assert_eq!(
+            args(&["-", "--", "-c", "--"]),
+            Ok(vec![Arg::Value("-".to_string()), Arg::Value("-c".to_string()), Arg::Value("--".to_string())])
+        );
+
+        assert_eq!(args(&["-cx"]), Err("an unrecognized option -x".to_string()));
+        assert_eq!(args(&["--colour"]), Err("an unrecognized option --colour".to_string()));
+        assert_eq!(args(&["--color=yes"]), Err("--color doesn't take a value".to_string()));
+        assert_eq!(args(&["--max-width"]), Err("--max-width requires <N>".to_string()));
+        assert_eq!(args(&["-mp"]), Err("-p requires <POINTER>".to_string()));
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(
+            help(OPTIONS),
+            concat!(
+                "    -c, --color            Color\n",
+                "    -m, --minimize         Minimize\n",
+                "    --max-width <N>, --compact-width <N>\n",
+                "                           Width\n",
+                "                           Second line\n",
+                "    -p, --pointer <POINTER>\n",
+                "                           Pointer\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! `.jprc.json` から jp の既定の設定を読み込む
+//!
+//! ```json
+//! {
+//!     "indent": 2,
+//!     "sortKeys": true,
+//!     "maxWidth": 80,
+//!     "color": false,
+//!     "overrides": [
+//!         { "files": ["*.jsonc", "config/**"], "indent": "tab" }
+//!     ]
+//! }
+//! ```
+//!
+//! `overrides` は `files` の glob パターンに一致するファイルにだけ適用し、後に書いたものが優先される。
+//! `/` を含まないパターンはどの階層のファイル名にも一致し、含むパターンは `.jprc.json` があるディレクトリからのパスに一致する。
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use json_prettier::{FormatOptions, Indent, JsonObject, ParseOptions};
+
+use crate::glob::{to_slash, Glob};
+
+/// 設定ファイルの名前
+pub const CONFIG_FILE: &str = ".jprc.json";
+
+/// 整形の設定のうち、指定されたものだけを持つ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub indent: Option<Indent>,
+    pub sort_keys: Option<bool>,
+    pub max_width: Option<usize>,
+    pub color: Option<bool>,
+}
+
+impl Settings {
+    /// 指定された設定だけを `format_options` と `parse_options` に反映する
+    pub fn apply(&self, format_options: &mut FormatOptions, parse_options: &mut ParseOptions) {
+        if let Some(indent) = self.indent {
+            format_options.indent = indent;
+        }
+        if let Some(sort_keys) = self.sort_keys {
+            parse_options.sort_keys = sort_keys;
+        }
+        if let Some(max_width) = self.max_width {
+            format_options.max_width = Some(max_width);
+        }
+        if let Some(color) = self.color {
+            format_options.color = color;
+        }
+    }
+
+    /// `other` で指定された設定で上書きする
+    fn merge(&mut self, other: &Settings) {
+        self.indent = other.indent.or(self.indent);
+        self.sort_keys = other.sort_keys.or(self.sort_keys);
+        self.max_width = other.max_width.or(self.max_width);
+        self.color = other.color.or(self.color);
+    }
+
+    /// `object` から設定を読み込む。設定以外のキーは `allowed` に含まれるものだけを受け付ける。
+    fn parse(object: &JsonObject, allowed: &[&str]) -> Result<Settings, String> {
+        let Some(map) = object.as_object() else {
+            return Err("settings must be an object".to_string());
+        };
+        let mut settings: Settings = Settings::default();
+        for (key, value) in map.iter() {
+            let invalid = |expected: &str| format!("'{}' must be {}, but {}", key, expected, value);
+            match key.as_str() {
+                "indent" => {
+                    settings.indent = Some(match (value.as_u64(), value.as_str()) {
+                        (Some(n), _) => Indent::Spaces(n as usize),
+                        (_, Some("tab")) => Indent::Tab,
+                        _ => return Err(invalid("a number of spaces or \"tab\"")),
+                    })
+                }
+                "sortKeys" => settings.sort_keys = Some(value.as_bool().ok_or_else(|| invalid("a boolean"))?),
+                "maxWidth" => {
+                    settings.max_width = Some(value.as_u64().ok_or_else(|| invalid("a number of columns"))? as usize)
+                }
+                "color" => settings.color = Some(value.as_bool().ok_or_else(|| invalid("a boolean"))?),
+                key if allowed.contains(&key) => {}
+                key => return Err(format!("an unknown key '{}'", key)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// 1つの `.jprc.json` の内容
+#[derive(Debug)]
+pub struct Config {
+    /// `.jprc.json` があるディレクトリ
+    dir: PathBuf,
+    /// 全てのファイルに適用する設定
+    settings: Settings,
+    /// glob パターンと、それに一致するファイルに適用する設定
+    overrides: Vec<(Vec<Glob>, Settings)>,
+}
+
+impl Config {
+    /// `dir` から親へ向かって `.jprc.json` を探し、最初に見つかったものを読み込む
+    pub fn find(dir: &Path) -> Result<Option<Config>, String> {
+        let dir: PathBuf = std::path::absolute(dir).map_err(|e| e.to_string())?;
+        for dir in dir.ancestors() {
+            let path: PathBuf = dir.join(CONFIG_FILE);
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    return Config::parse(dir, &content)
+                        .map(Some)
+                        .map_err(|e| format!("invalid {}: {}", path.display(), e));
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse(dir: &Path, content: &str) -> Result<Config, String> {
+        let value: JsonObject = json_prettier::parse(content).map_err(|e| e.to_string())?;
+        let settings: Settings = Settings::parse(&value, &["overrides"])?;
+
+        let mut overrides: Vec<(Vec<Glob>, Settings)> = vec![];
+        if let Some(list) = value.get("overrides") {
+            let Some(list) = list.as_array() else {
+                return Err("'overrides' must be an array".to_string());
+            };
+            for (i, item) in list.iter().enumerate() {
+                let patterns: Vec<&str> = match item.get("files") {
+                    Some(JsonObject::String(s)) => vec![s.as_str()],
+                    Some(JsonObject::Array(items)) => items
+                        .iter()
+                        .map(|p| {
+                            p.as_str().ok_or_else(|| {
+                                format!("'files' in 'overrides'[{}] must contain only glob patterns, but {}", i, p)
+                            })
+                        })
+                        .collect::<Result<Vec<&str>, String>>()?,
+                    _ => return Err("'files' in 'overrides' must be a glob pattern or an array of them".to_string()),
+                };
+                let globs: Vec<Glob> = patterns
+                    .iter()
+                    .map(|p| match p.strip_prefix('/') {
+                        Some(p) => Glob::new(p),
+                        None if p.contains('/') => Glob::new(p),
+                        None => Glob::new(&format!("**/{}", p)),
+                    })
+                    .collect();
+                overrides.push((globs, Settings::parse(item, &["files"])?));
+            }
+        }
+        Ok(Config { dir: dir.to_path_buf(), settings, overrides })
+    }
+
+    /// `file` に適用する設定を返す。`file` が `None` の場合は `overrides` を適用しない。
+    pub fn settings_for(&self, file: Option<&Path>) -> Settings {
+        let mut settings: Settings = self.settings.clone();
+        let relative: Option<String> = file
+            .and_then(|f| std::path::absolute(f).ok())
+            .and_then(|f| f.strip_prefix(&self.dir).ok().map(to_slash));
+        if let Some(relative) = relative {
+            for (globs, overrides) in &self.overrides {
+                if globs.iter().any(|g| g.is_match(&relative)) {
+                    settings.merge(overrides);
+                }
+            }
+        }
+        settings
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use json_prettier::Indent;
+
+    use super::{Config, Settings, CONFIG_FILE};
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_find() {
+        let root: TempDir = TempDir::new("config");
+        let sub: PathBuf = root.join("config").join("nested");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(
+            root.join(CONFIG_FILE),
+            r#"{
+                "indent": 2, "sortKeys": true, "maxWidth": 80,
+                "overrides": [
+                    {"files": "*.jsonc", "indent": "tab"},
+                    {"files": ["config/**"], "sortKeys": false, "color": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::find(&sub).unwrap().unwrap();
+        let base = Settings { indent: Some(Indent::Spaces(2)), sort_keys: Some(true), max_width: Some(80), color: None };
+        assert_eq!(config.settings_for(None), base);
+        assert_eq!(config.settings_for(Some(&root.join("a.json"))), base);
+        assert_eq!(
+            config.settings_for(Some(&sub.join("b.jsonc"))),
+            Settings { indent: Some(Indent::Tab), sort_keys: Some(false), color: Some(true), ..base.clone() }
+        );
+
+        // 近い .jprc.json だけを読み込む
+        fs::write(sub.join(CONFIG_FILE), r#"{"color": false}"#).unwrap();
+        let config = Config::find(&sub).unwrap().unwrap();
+        assert_eq!(config.settings_for(None), Settings { color: Some(false), ..Settings::default() });
+
+        for (content, message) in [
+            (r#"{"indent": "two"}"#, "'indent' must be a number of spaces or \"tab\", but \"two\""),
+            (r#"{"sort_keys": true}"#, "an unknown key 'sort_keys'"),
+            (r#"{"overrides": [{"indent": 2}]}"#, "'files' in 'overrides' must be a glob pattern or an array of them"),
+            (
+                r#"{"overrides": [{"files": "*.json"}, {"files": ["*.json", 1]}]}"#,
+                "'files' in 'overrides'[1] must contain only glob patterns, but 1",
+            ),
+        ] {
+            fs::write(sub.join(CONFIG_FILE), content).unwrap();
+            let err = Config::find(&sub).unwrap_err();
+            assert!(err.ends_with(message), "{}", err);
+        }
+        fs::write(sub.join(CONFIG_FILE), "{").unwrap();
+        assert!(Config::find(&sub).unwrap_err().contains("EofError"));
+    }
+}
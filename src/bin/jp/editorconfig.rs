@@ -0,0 +1,167 @@
+//! `.editorconfig` から整形の設定を読み込む
+//!
+//! `indent_style`、`indent_size`、`tab_width`、`end_of_line`、`insert_final_newline` に対応している。
+
+use std::{
+    fs, io,
+    path::{self, Path, PathBuf},
+};
+
+use json_prettier::{FormatOptions, Indent, Newline};
+
+use crate::glob::{to_slash, Glob};
+
+/// 1つの `.editorconfig` ファイルの内容
+struct EditorConfig {
+    /// `root = true` が指定されているかどうか
+    root: bool,
+    /// セクションの glob パターンと、そのプロパティ
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl EditorConfig {
+    fn parse(content: &str) -> EditorConfig {
+        let mut config: EditorConfig = EditorConfig { root: false, sections: vec![] };
+        for line in content.lines() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                config.sections.push((section.to_string(), vec![]));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key: String = key.trim().to_ascii_lowercase();
+            let value: String = value.trim().to_ascii_lowercase();
+            match config.sections.last_mut() {
+                Some((_, properties)) => properties.push((key, value)),
+                // 最初のセクションより前には root だけを書ける
+                None => config.root |= key == "root" && value == "true",
+            }
+        }
+        config
+    }
+
+    /// `dir` に置かれた `.editorconfig` のうち、`file` に一致するセクションのプロパティを返す
+    fn properties_for(&self, dir: &Path, file: &Path) -> Vec<(String, String)> {
+        let Ok(relative) = file.strip_prefix(dir) else {
+            return vec![];
+        };
+        let relative: String = to_slash(relative);
+
+        let mut properties: Vec<(String, String)> = vec![];
+        for (section, props) in &self.sections {
+            // `/` を含まないパターンは、どの階層のファイル名にも一致する
+            let pattern: String = match section.strip_prefix('/') {
+                Some(p) => p.to_string(),
+                None if section.contains('/') => section.clone(),
+                None => format!("**/{}", section),
+            };
+            if Glob::new(&pattern).is_match(&relative) {
+                properties.extend(props.iter().cloned());
+            }
+        }
+        properties
+    }
+}
+
+/// `file` に適用される `.editorconfig` の設定を `options` に反映する。
+///
+/// `file` のディレクトリから親へ向かって `.editorconfig` を探し、`root = true` のファイルで止める。
+/// 近いファイルの設定と、ファイル内で後に書かれたセクションの設定が優先される。
+pub fn apply(file: &Path, options: &mut FormatOptions) -> io::Result<()> {
+    let file: PathBuf = path::absolute(file)?;
+    let mut configs: Vec<(PathBuf, EditorConfig)> = vec![];
+    for dir in file.ancestors().skip(1) {
+        let content: String = match fs::read_to_string(dir.join(".editorconfig")) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let config: EditorConfig = EditorConfig::parse(&content);
+        let root: bool = config.root;
+        configs.push((dir.to_path_buf(), config));
+        if root {
+            break;
+        }
+    }
+
+    let mut properties: Vec<(String, String)> = vec![];
+    for (dir, config) in configs.iter().rev() {
+        properties.extend(config.properties_for(dir, &file));
+    }
+    let get = |key: &str| -> Option<&str> {
+        properties.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    };
+
+    let tab_width: Option<usize> = get("tab_width").and_then(|v| v.parse().ok());
+    let indent_size: Option<usize> = match get("indent_size") {
+        Some("tab") => tab_width,
+        Some(v) => v.parse().ok(),
+        None => None,
+    };
+    match (get("indent_style"), indent_size.or(tab_width)) {
+        (Some("tab"), _) => options.indent = Indent::Tab,
+        (Some("space"), Some(size)) => options.indent = Indent::Spaces(size),
+        (None, Some(size)) if options.indent != Indent::Tab => options.indent = Indent::Spaces(size),
+        _ => {}
+    }
+    match get("end_of_line") {
+        Some("lf") => options.newline = Newline::Lf,
+        Some("crlf") => options.newline = Newline::CrLf,
+        _ => {}
+    }
+    match get("insert_final_newline") {
+        Some("true") => options.trailing_newline = true,
+        Some("false") => options.trailing_newline = false,
+        _ => {}
+    }
+    Ok(())
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use json_prettier::{FormatOptions, Indent, Newline};
+
+    use super::apply;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_apply() {
+        let root: TempDir = TempDir::new("editorconfig");
+        let sub: PathBuf = root.join("project").join("config");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(
+            root.join(".editorconfig"),
+            "root = true\n\n[*]\nindent_style = space\nindent_size = 4\nend_of_line = crlf\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("project").join(".editorconfig"),
+            "# project\n[*.json]\nindent_size = 2\ninsert_final_newline = true\n\n[config/tab.json]\nindent_style = tab\n",
+        )
+        .unwrap();
+
+        let mut options = FormatOptions::default();
+        apply(&sub.join("a.json"), &mut options).unwrap();
+        assert_eq!(options.indent, Indent::Spaces(2));
+        assert_eq!(options.newline, Newline::CrLf);
+        assert!(options.trailing_newline);
+
+        let mut options = FormatOptions::default();
+        apply(&sub.join("tab.json"), &mut options).unwrap();
+        assert_eq!(options.indent, Indent::Tab);
+
+        let mut options = FormatOptions::default();
+        apply(&root.join("a.txt"), &mut options).unwrap();
+        assert_eq!(options.indent, Indent::Spaces(4));
+        assert!(!options.trailing_newline);
+    }
+}
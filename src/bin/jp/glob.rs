@@ -0,0 +1,213 @@
+//! `.editorconfig` のセクションなどで使う glob パターン
+//!
+//! `*`(`/` 以外の任意の文字列)、`**`(`/` を含む任意の文字列)、`?`、
+//! `[a-z]`、`[!a-z]`、`{a,b}` に対応している。
+
+use std::path::Path;
+
+/// パース済みの glob パターン
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    /// `{a,b}` を展開した候補
+    alternatives: Vec<Vec<Token>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `**`
+    DoubleStar,
+    /// `**/`。空文字列または `/` で終わる任意の文字列。
+    Dirs,
+    /// `[...]`
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl Glob {
+    /// 閉じていない `[` や `{` は文字として扱う
+    pub fn new(pattern: &str) -> Glob {
+        let alternatives: Vec<Vec<Token>> = expand_braces(pattern)
+            .iter()
+            .map(|p| tokenize(&p.chars().collect::<Vec<char>>()))
+            .collect();
+        Glob { alternatives }
+    }
+
+    /// `/` 区切りの `path` 全体が一致するかどうか
+    pub fn is_match(&self, path: &str) -> bool {
+        let chars: Vec<char> = path.chars().collect();
+        self.alternatives.iter().any(|tokens| match_tokens(tokens, &chars))
+    }
+}
+
+/// glob パターンと比べるために、`/` 区切りのパスにする
+pub fn to_slash(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `{a,b}` をそれぞれの候補に展開する
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut depth: usize = 0;
+    let mut open: Option<usize> = None;
+    let mut commas: Vec<usize> = vec![];
+    let mut i: usize = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                    commas.clear();
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => commas.push(i),
+            '}' if depth > 0 => {
+                depth -= 1;
+                if let (0, Some(start)) = (depth, open) {
+                    let prefix: String = chars[..start].iter().collect();
+                    let suffix: String = chars[i + 1..].iter().collect();
+                    let bounds: Vec<usize> = std::iter::once(start).chain(commas.iter().copied()).chain([i]).collect();
+                    let mut expanded: Vec<String> = vec![];
+                    for pair in bounds.windows(2) {
+                        let item: String = chars[pair[0] + 1..pair[1]].iter().collect();
+                        for rest in expand_braces(&format!("{}{}", item, suffix)) {
+                            expanded.push(format!("{}{}", prefix, rest));
+                        }
+                    }
+                    return expanded;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    vec![pattern.to_string()]
+}
+
+fn tokenize(chars: &[char]) -> Vec<Token> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut i: usize = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    tokens.push(Token::Dirs);
+                    i += 3;
+                } else {
+                    tokens.push(Token::DoubleStar);
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => tokens.push(Token::Star),
+            '?' => tokens.push(Token::Any),
+            '[' => match parse_class(chars, i + 1) {
+                Some((token, end)) => {
+                    tokens.push(token);
+                    i = end;
+                    continue;
+                }
+                None => tokens.push(Token::Char('[')),
+            },
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 1;
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// `[` の後の文字クラスを読み、`Token` と `]` の次の位置を返す
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i: usize = start;
+    let negated: bool = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges: Vec<(char, char)> = vec![];
+    loop {
+        let c: char = *chars.get(i)?;
+        if c == ']' && !ranges.is_empty() {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        if c == '/' {
+            return None;
+        }
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|c| *c != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return path.is_empty();
+    };
+    match token {
+        Token::Char(c) => path.first() == Some(c) && match_tokens(rest, &path[1..]),
+        Token::Any => path.first().is_some_and(|c| *c != '/') && match_tokens(rest, &path[1..]),
+        Token::Class { negated, ranges } => match path.first() {
+            Some(c) if *c != '/' => {
+                ranges.iter().any(|(from, to)| from <= c && c <= to) != *negated && match_tokens(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Star => {
+            let end: usize = path.iter().position(|c| *c == '/').unwrap_or(path.len());
+            (0..=end).any(|i| match_tokens(rest, &path[i..]))
+        }
+        Token::DoubleStar => (0..=path.len()).any(|i| match_tokens(rest, &path[i..])),
+        Token::Dirs => {
+            match_tokens(rest, path)
+                || (0..path.len()).any(|i| path[i] == '/' && match_tokens(rest, &path[i + 1..]))
+        }
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use super::Glob;
+
+    #[test]
+    fn test_is_match() {
+        let cases = [
+            ("*.json", "a.json", true),
+            ("*.json", "dir/a.json", false),
+            ("**.json", "dir/a.json", true),
+            ("**/*.json", "a.json", true),
+            ("src/**/*.json", "src/a/b/c.json", true),
+            ("src/**/*.json", "srcx/c.json", false),
+            ("a?c", "abc", true),
+            ("a?c", "a/c", false),
+            ("[a-c]x", "bx", true),
+            ("[!a-c]x", "bx", false),
+            ("*.{json,jsonc}", "a.jsonc", true),
+            ("*.{json,jsonc}", "a.json5", false),
+            ("{a,b/{c,d}}.json", "b/d.json", true),
+            ("\\*.json", "*.json", true),
+            ("[.json", "[.json", true),
+            ("dir/**", "dir/a/b", true),
+        ];
+        for (pattern, path, expect) in cases {
+            assert_eq!(Glob::new(pattern).is_match(path), expect, "{} {}", pattern, path);
+        }
+    }
+}
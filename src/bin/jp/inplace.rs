@@ -0,0 +1,169 @@
+//! `--write` と `--check` で、ファイルを整形して書き換える、または整形済みかを調べる
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// ファイルをどう扱うか
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// 整形した内容で書き換える
+    Write,
+    /// 整形した内容と異なるかを調べるだけで、書き換えない
+    Check,
+}
+
+/// 1つのファイルの処理結果
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// 既に整形済み
+    Unchanged,
+    /// 整形した内容で書き換えた、または `Check` で整形した内容と異なった
+    Changed,
+}
+
+/// `path` を読み込み、`format` で整形した内容と比べる。
+/// `Mode::Write` で内容が異なる場合は、ファイルを書き換える。
+pub fn process_file(
+    path: &Path,
+    mode: Mode,
+    format: impl FnOnce(&[u8], &mut Vec<u8>) -> Result<(), String>,
+) -> Result<Outcome, String> {
+    let input: Vec<u8> = fs::read(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let mut output: Vec<u8> = vec![];
+    format(&input, &mut output)?;
+    if input == output {
+        return Ok(Outcome::Unchanged);
+    }
+    if mode == Mode::Write {
+        write_atomically(path, &output).map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+    }
+    Ok(Outcome::Changed)
+}
+
+/// `files` のそれぞれを `process_file` で処理し、`files` と同じ順に結果を返す。
+/// CPU の数だけスレッドを作り、各スレッドはまだ処理していないファイルを1つずつ取り出して処理する。
+pub fn process_files(
+    files: &[PathBuf],
+    mode: Mode,
+    format: impl Fn(&Path, &[u8], &mut Vec<u8>) -> Result<(), String> + Sync,
+) -> Vec<Result<Outcome, String>> {
+    let workers: usize = thread::available_parallelism().map_or(1, |n| n.get()).min(files.len());
+    let next: AtomicUsize = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<Outcome, String>>>> = Mutex::new(files.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i: usize = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(i) else {
+                    break;
+                };
+                let result = process_file(path, mode, |input, output| format(path, input, output));
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// 同じディレクトリの一時ファイルに書き込んでから名前を変更し、`path` の内容を置き換える。
+/// `path` が無い場合は作る。途中で失敗しても、`path` が書きかけの状態になることはない。
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir: &Path = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name: String = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp: PathBuf = dir.join(format!(".{}.jp-{}.tmp", name, process::id()));
+
+    let result: io::Result<()> = (|| {
+        let mut file: File = File::create(&temp)?;
+        file.write_all(contents)?;
+        // 元のファイルがある場合は、その権限を引き継ぐ
+        match fs::metadata(path) {
+            Ok(metadata) => file.set_permissions(metadata.permissions())?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{process_file, process_files, write_atomically, Mode, Outcome};
+    use crate::testutil::TempDir;
+
+    fn upper(input: &[u8], output: &mut Vec<u8>) -> Result<(), String> {
+        output.extend(input.to_ascii_uppercase());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir: TempDir = TempDir::new("inplace");
+        let path: PathBuf = dir.join("a.json");
+        fs::write(&path, "abc").unwrap();
+
+        assert_eq!(process_file(&path, Mode::Check, upper), Ok(Outcome::Changed));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+
+        assert_eq!(process_file(&path, Mode::Write, upper), Ok(Outcome::Changed));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ABC");
+        assert_eq!(process_file(&path, Mode::Write, upper), Ok(Outcome::Unchanged));
+        // 一時ファイルは残らない
+        assert_eq!(fs::read_dir(&*dir).unwrap().count(), 1);
+
+        let err = process_file(&path, Mode::Write, |_, _| Err("invalid".to_string()));
+        assert_eq!(err, Err("invalid".to_string()));
+        assert!(process_file(&dir.join("missing.json"), Mode::Check, upper).is_err());
+
+        // 無いファイルは作る
+        write_atomically(&dir.join("new.json"), b"{}").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("new.json")).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_process_files() {
+        let dir: TempDir = TempDir::new("inplace-files");
+        let files: Vec<PathBuf> = (0..20).map(|i| dir.join(format!("{}.json", i))).collect();
+        for (i, file) in files.iter().enumerate() {
+            fs::write(file, if i % 2 == 0 { "abc" } else { "ABC" }).unwrap();
+        }
+
+        let results = process_files(&files, Mode::Write, |path, input, output| {
+            if path.ends_with("7.json") {
+                return Err("failed".to_string());
+            }
+            upper(input, output)
+        });
+        assert_eq!(results.len(), 20);
+        for (i, result) in results.iter().enumerate() {
+            let expect = match i {
+                7 => Err("failed".to_string()),
+                i if i % 2 == 0 => Ok(Outcome::Changed),
+                _ => Ok(Outcome::Unchanged),
+            };
+            assert_eq!(result, &expect, "{}", i);
+        }
+        assert_eq!(fs::read_to_string(&files[4]).unwrap(), "ABC");
+    }
+}
@@ -0,0 +1,653 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, stdin, stdout, BufRead, BufReader, BufWriter, StdoutLock, Write},
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use json_prettier::{
+    FormatOptions, Indent, IoError, JsonLines, JsonObject, JsonPath, JsonPointer, JsonPretError, JsonStream, ParseOptions,
+    Query,
+};
+
+mod args;
+mod config;
+mod editorconfig;
+mod glob;
+mod inplace;
+#[cfg(test)]
+mod testutil;
+mod walk;
+
+use args::{Arg, Opt};
+use config::{Config, Settings};
+use inplace::{Mode, Outcome};
+
+/// jp のオプションの一覧。ヘルプもこの順に表示する。
+const OPTIONS: &[Opt] = &[
+    Opt { short: Some('h'), long: "help", alias: None, value: None, help: "Print help information" },
+    Opt { short: Some('V'), long: "version", alias: None, value: None, help: "Print version information" },
+    Opt { short: Some('c'), long: "color", alias: None, value: None, help: "Color JSON output" },
+    Opt { short: Some('m'), long: "minimize", alias: None, value: None, help: "Minimize JSON output" },
+    Opt {
+        short: Some('s'),
+        long: "sort-keys",
+        alias: None,
+        value: None,
+        help: "Sort object keys instead of keeping input order",
+    },
+    Opt {
+        short: None,
+        long: "no-sort-keys",
+        alias: None,
+        value: None,
+        help: "Keep input order even if .jprc.json sets sortKeys",
+    },
+    Opt { short: Some('a'), long: "ascii", alias: None, value: None, help: "Escape non-ASCII characters as \\uXXXX" },
+    Opt {
+        short: None,
+        long: "indent",
+        alias: None,
+        value: Some("N"),
+        help: "Indent with N spaces (default: 3, or .editorconfig for a FILE)",
+    },
+    Opt { short: None, long: "tab", alias: None, value: None, help: "Indent with tabs" },
+    Opt {
+        short: None,
+        long: "max-width",
+        alias: Some("compact-width"),
+        value: Some("N"),
+        help: "Keep arrays and objects on one line when they fit in N columns",
+    },
+    Opt {
+        short: None,
+        long: "json5",
+        alias: None,
+        value: None,
+        help: "Accept JSON5 / JSONC input (comments, trailing commas, etc.)",
+    },
+    Opt {
+        short: None,
+        long: "jsonc",
+        alias: None,
+        value: None,
+        help: "Format JSONC input keeping its comments (e.g. tsconfig.json)",
+    },
+    Opt {
+        short: None,
+        long: "lines",
+        alias: None,
+        value: None,
+        help: "Format each line as a separate JSON value (NDJSON / JSON Lines)",
+    },
+    Opt {
+        short: None,
+        long: "seq",
+        alias: None,
+        value: None,
+        help: "Format each record of a JSON text sequence (RFC 7464)",
+    },
+    Opt {
+        short: None,
+        long: "multi",
+        alias: None,
+        value: None,
+        help: "Format each of concatenated JSON values (e.g. {\"a\":1}{\"b\":2})",
+    },
+    Opt {
+        short: None,
+        long: "no-config",
+        alias: None,
+        value: None,
+        help: "Don't read .jprc.json (searched from the FILE's directory upwards)",
+    },
+    Opt {
+        short: Some('o'),
+        long: "output",
+        alias: None,
+        value: Some("FILE"),
+        help: "Write the output to FILE instead of stdout\nFILE is replaced only when jp succeeds",
+    },
+    Opt {
+        short: Some('w'),
+        long: "write",
+        alias: None,
+        value: None,
+        help: "Rewrite the PATHs in place with the formatted JSON",
+    },
+    Opt {
+        short: None,
+        long: "check",
+        alias: None,
+        value: None,
+        help: "List the files that are not formatted and exit with 1",
+    },
+    Opt {
+        short: Some('p'),
+        long: "pointer",
+        alias: None,
+        value: Some("POINTER"),
+        help: "Print only the value at a JSON pointer (e.g. /a/0/b)",
+    },
+    Opt {
+        short: None,
+        long: "jsonpath",
+        alias: None,
+        value: Some("PATH"),
+        help: "Print the values selected by a JSONPath (e.g. '$..book[?@.price < 10]')",
+    },
+    Opt {
+        short: Some('q'),
+        long: "query",
+        alias: None,
+        value: Some("FILTER"),
+        help: "Print the outputs of a jq-like filter (e.g. '.items[] | .id')",
+    },
+];
+
+fn usage() -> String {
+    let mut usage: String = String::new();
+    usage.push_str("jp - command line JSON minimum prettier\n");
+    usage.push_str("USAGE:\n");
+    usage.push_str("    jp [OPTIONS...] [FILE] [OPTIONS...]\n");
+    usage.push_str("    jp --write|--check [OPTIONS...] PATH...\n");
+    usage.push_str("ARGS:\n");
+    usage.push_str("    <FILE> A JSON file, or - to read stdin (default)\n");
+    usage.push_str("    <PATH> A JSON file, a directory to search for *.json recursively, or a glob (e.g. 'src/**/*.json')\n");
+    usage.push_str("           Files listed in .gitignore or .jpignore are skipped\n");
+    usage.push_str("    Use -- before a FILE or PATH that starts with -\n");
+    usage.push_str("OPTIONS:\n");
+    usage.push_str(&args::help(OPTIONS));
+    usage
+}
+
+fn main() {
+    let mut args: Vec<String> = vec![];
+    let mut format_options = FormatOptions::default();
+    let mut parse_options = ParseOptions::default();
+    // .jprc.json より優先する設定
+    let mut cli: Settings = Settings::default();
+    let mut no_config: bool = false;
+    let mut output: Option<PathBuf> = None;
+    let mut pointer: Option<JsonPointer> = None;
+    let mut jsonpath: Option<JsonPath> = None;
+    let mut query: Option<Query> = None;
+    let mut jsonc: bool = false;
+    let mut lines: bool = false;
+    let mut seq: bool = false;
+    let mut multi: bool = false;
+    let mut mode: Option<Mode> = None;
+
+    let parsed: Vec<Arg> = match args::parse(env::args().skip(1), OPTIONS) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprint!("{}", usage());
+            exit(1);
+        }
+    };
+    for arg in parsed {
+        let (opt, value): (&Opt, String) = match arg {
+            Arg::Opt(opt, value) => (opt, value.unwrap_or_default()),
+            Arg::Value(value) => {
+                args.push(value);
+                continue;
+            }
+        };
+        match opt.long {
+            "help" => {
+                print!("{}", usage());
+                exit(0);
+            }
+            "version" => {
+                println!("jp {}", env!("CARGO_PKG_VERSION"));
+                exit(0);
+            }
+            "color" => cli.color = Some(true),
+            "minimize" => format_options.compact = true,
+            "sort-keys" => cli.sort_keys = Some(true),
+            "no-sort-keys" => cli.sort_keys = Some(false),
+            "ascii" => format_options.ascii_only = true,
+            "indent" => match value.parse::<usize>() {
+                Ok(n) => cli.indent = Some(Indent::Spaces(n)),
+                Err(_) => {
+                    eprintln!("error: --indent requires a number of spaces, but {}", value);
+                    exit(1);
+                }
+            },
+            "tab" => cli.indent = Some(Indent::Tab),
+            "max-width" => match value.parse::<usize>() {
+                Ok(n) => cli.max_width = Some(n),
+                Err(_) => {
+                    eprintln!("error: --max-width requires a number of columns, but {}", value);
+                    exit(1);
+                }
+            },
+            "json5" => parse_options.json5 = true,
+            "jsonc" => jsonc = true,
+            "lines" => lines = true,
+            "seq" => seq = true,
+            "multi" => multi = true,
+            "no-config" => no_config = true,
+            "output" => output = Some(PathBuf::from(value)),
+            "write" => mode = Some(Mode::Write),
+            "check" => mode = Some(Mode::Check),
+            "pointer" => match JsonPointer::parse(&value) {
+                Ok(p) => pointer = Some(p),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                }
+            },
+            "jsonpath" => match JsonPath::parse(&value) {
+                Ok(p) => jsonpath = Some(p),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                }
+            },
+            "query" => match Query::parse(&value) {
+                Ok(q) => query = Some(q),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                }
+            },
+            long => unreachable!("--{} is not handled", long),
+        }
+    }
+    if multi && (lines || seq) {
+        eprintln!("error: --multi can't be used with --lines or --seq");
+        exit(1);
+    }
+    if jsonc && (lines || seq || multi) {
+        eprintln!("error: --jsonc can't be used with --lines, --seq or --multi");
+        exit(1);
+    }
+    // コメントを残すため、値を選択するオプションとは組み合わせられない
+    if jsonc && (cli.sort_keys == Some(true) || pointer.is_some() || jsonpath.is_some() || query.is_some()) {
+        eprintln!("error: --jsonc can't be used with --sort-keys, --pointer, --jsonpath or --query");
+        exit(1);
+    }
+
+    if let Some(mode) = mode {
+        if lines || seq || multi || pointer.is_some() || jsonpath.is_some() || query.is_some() {
+            eprintln!("error: --write and --check can't be used with --lines, --seq, --multi, --pointer, --jsonpath or --query");
+            exit(1);
+        }
+        if args.is_empty() {
+            eprintln!("error: --write and --check require at least one PATH");
+            eprint!("{}", usage());
+            exit(1);
+        }
+        if output.is_some() {
+            eprintln!("error: --write and --check can't be used with --output");
+            exit(1);
+        }
+        if args.iter().any(|arg| arg == "-") {
+            eprintln!("error: --write and --check can't read stdin");
+            exit(1);
+        }
+        // ディレクトリからは .json (--jsonc の場合は .jsonc も) のファイルを集める
+        let extensions: &[&str] = if jsonc { &["json", "jsonc"] } else { &["json"] };
+        let files: Vec<PathBuf> = match walk::collect(&args, extensions) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+        };
+        // ファイルは改行で終え、色は付けない
+        format_options.trailing_newline = true;
+        let cli: Settings = Settings { color: Some(false), ..cli };
+        let results = inplace::process_files(&files, mode, |path, input, output| {
+            let (format_options, parse_options) =
+                file_options(Some(path), &format_options, &parse_options, &cli, no_config)?;
+            format_document(input, output, &format_options, &parse_options, jsonc).map_err(|e| match e {
+                JsonPretError::IoError(e) => format!("can't format {}: {}", path.display(), e.message),
+                e => format!("failed to parse {}\n{}", path.display(), e),
+            })
+        });
+
+        let (mut changed, mut unchanged, mut failed): (usize, usize, usize) = (0, 0, 0);
+        for (path, result) in files.iter().zip(results) {
+            match result {
+                Ok(Outcome::Unchanged) => unchanged += 1,
+                Ok(Outcome::Changed) => {
+                    changed += 1;
+                    if mode == Mode::Check {
+                        println!("{}", path.display());
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    failed += 1;
+                }
+            }
+        }
+        match mode {
+            Mode::Write => eprintln!("{} formatted, {} unchanged, {} failed", changed, unchanged, failed),
+            Mode::Check => eprintln!("{} not formatted, {} formatted, {} failed", changed, unchanged, failed),
+        }
+        if failed > 0 || (mode == Mode::Check && changed > 0) {
+            exit(1);
+        }
+        return;
+    }
+
+    if args.iter().any(|arg| Path::new(arg).is_dir()) {
+        eprintln!("error: a directory requires --write or --check");
+        exit(1);
+    }
+    if args.len() > 1 {
+        eprintln!("error: the number of argument must be 0 or 1");
+        eprint!("{}", usage());
+        exit(1);
+    }
+    // - は標準入力を表す
+    let input: Option<&String> = args.first().filter(|arg| *arg != "-");
+
+    let (format_options, parse_options) =
+        match file_options(input.map(Path::new), &format_options, &parse_options, &cli, no_config) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                exit(1);
+            }
+        };
+
+    let (input_name, reader): (String, Box<dyn BufRead>) = if let Some(file_name) = input {
+        match File::open(file_name) {
+            Ok(f) => (file_name.clone(), Box::new(BufReader::new(f))),
+            Err(e) => {
+                eprintln!("error: can't open a file {}: {}", file_name, e);
+                exit(1);
+            }
+        }
+    } else {
+        ("stdin".to_string(), Box::new(stdin().lock()))
+    };
+    let (output_name, mut writer): (String, Output) = match output {
+        Some(path) => {
+            // 入力のファイルを書き換える場合は --write を使う
+            if input.is_some_and(|input| is_same_file(Path::new(input), &path)) {
+                eprintln!("error: --output can't be the input file, use --write instead");
+                exit(1);
+            }
+            (path.display().to_string(), Output::File(path, vec![]))
+        }
+        None => ("stdout".to_string(), Output::Stdout(BufWriter::new(stdout().lock()))),
+    };
+    if lines || seq || multi {
+        // 行ごとの場合は、1つのレコードのエラーで止めず最後まで読み込む
+        let records: Box<dyn Iterator<Item = Result<JsonObject, JsonPretError>>> = if multi {
+            Box::new(JsonStream::with_options(reader, parse_options))
+        } else {
+            Box::new(JsonLines::with_options(reader, parse_options).seq(seq))
+        };
+        let mut failed: bool = false;
+        for record in records {
+            let outputs: Result<Vec<JsonObject>, String> = match record {
+                Ok(value) => select(&value, pointer.as_ref(), jsonpath.as_ref(), query.as_ref()),
+                Err(JsonPretError::IoError(e)) => {
+                    // 読み込めたレコードの出力は書き出してから終了する
+                    flush_or_exit(&mut writer, &output_name);
+                    eprintln!("error: can't read a string from {}: {}", input_name, e.message);
+                    exit(1);
+                }
+                Err(e) => Err(format!("failed to parse json\n{}", e)),
+            };
+            let outputs: Vec<JsonObject> = match outputs {
+                Ok(outputs) => outputs,
+                Err(e) => {
+                    // エラーが前後のレコードの出力と同じ順に並ぶように、先に出力を書き出す
+                    flush_or_exit(&mut writer, &output_name);
+                    eprintln!("error: {}", e);
+                    failed = true;
+                    continue;
+                }
+            };
+            for value in outputs {
+                let result = if seq { writer.write_all(b"\x1E") } else { Ok(()) }
+                    .and_then(|_| json_prettier::to_writer(&mut writer, &value, &format_options));
+                // 各レコードは改行で終える
+                let result = if format_options.trailing_newline {
+                    result
+                } else {
+                    result.and_then(|_| writer.write_all(format_options.newline.as_str().as_bytes()))
+                };
+                if let Err(e) = result {
+                    eprintln!("error: can't write to {}: {}", output_name, e);
+                    exit(1);
+                }
+            }
+        }
+        flush_or_exit(&mut writer, &output_name);
+        if failed {
+            exit(1);
+        }
+        finish_or_exit(writer, &output_name);
+        return;
+    }
+
+    if pointer.is_none() && jsonpath.is_none() && query.is_none() {
+        match format_document(reader, &mut writer, &format_options, &parse_options, jsonc) {
+            Ok(()) => {}
+            Err(JsonPretError::IoError(e)) => {
+                eprintln!("error: can't format {}: {}", input_name, e.message);
+                exit(1);
+            }
+            Err(e) => {
+                eprintln!("error: failed to parse json");
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        finish_or_exit(writer, &output_name);
+        return;
+    }
+
+    let json_value = match json_prettier::parse_reader_with_options(reader, &parse_options) {
+        Ok(v) => v,
+        Err(JsonPretError::IoError(e)) => {
+            eprintln!("error: can't read a string from {}: {}", input_name, e.message);
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: failed to parse json");
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+
+    let outputs: Vec<JsonObject> = match select(&json_value, pointer.as_ref(), jsonpath.as_ref(), query.as_ref()) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    };
+
+    let mut result = Ok(());
+    for (i, value) in outputs.iter().enumerate() {
+        if i > 0 && !format_options.trailing_newline {
+            result = result.and_then(|_| writer.write_all(format_options.newline.as_str().as_bytes()));
+        }
+        result = result.and_then(|_| json_prettier::to_writer(&mut writer, value, &format_options));
+    }
+    if let Err(e) = result {
+        eprintln!("error: can't write to {}: {}", output_name, e);
+        exit(1);
+    }
+    finish_or_exit(writer, &output_name);
+}
+
+/// 出力先
+enum Output {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    /// `--output` のファイルと、書き込む内容。
+    /// エラーで終了する場合に元の内容を消さないように、最後にまとめて置き換える。
+    File(PathBuf, Vec<u8>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(w) => w.write(buf),
+            Output::File(_, contents) => contents.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(w) => w.flush(),
+            Output::File(..) => Ok(()),
+        }
+    }
+}
+
+/// 出力を書き終える。`--output` の場合はここでファイルを置き換える。書き込めない場合は終了する。
+fn finish_or_exit(writer: Output, name: &str) {
+    let result: io::Result<()> = match writer {
+        Output::Stdout(mut w) => w.flush(),
+        Output::File(path, contents) => inplace::write_atomically(&path, &contents),
+    };
+    if let Err(e) = result {
+        eprintln!("error: can't write to {}: {}", name, e);
+        exit(1);
+    }
+}
+
+/// `path` を整形する時の設定を返す。`path` が `None` の場合は標準入力を整形する時の設定を返す。
+///
+/// .editorconfig、.jprc.json、コマンドラインで指定した `cli` の順に、後のものを優先して反映する。
+/// .jprc.json は `path` のディレクトリ(標準入力の場合はカレントディレクトリ)から親へ向かって探す。
+fn file_options(
+    path: Option<&Path>,
+    format_options: &FormatOptions,
+    parse_options: &ParseOptions,
+    cli: &Settings,
+    no_config: bool,
+) -> Result<(FormatOptions, ParseOptions), String> {
+    let mut format_options: FormatOptions = format_options.clone();
+    let mut parse_options: ParseOptions = parse_options.clone();
+    if let Some(path) = path {
+        editorconfig::apply(path, &mut format_options)
+            .map_err(|e| format!("can't read .editorconfig for {}: {}", path.display(), e))?;
+    }
+    if !no_config {
+        let dir: &Path = match path.and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let Some(config) = Config::find(dir)? {
+            config.settings_for(path).apply(&mut format_options, &mut parse_options);
+        }
+    }
+    cli.apply(&mut format_options, &mut parse_options);
+    Ok((format_options, parse_options))
+}
+
+/// `writer` に溜まっている出力を `name` に書き出す。書き出せない場合は終了する。
+fn flush_or_exit(writer: &mut impl Write, name: &str) {
+    if let Err(e) = writer.flush() {
+        eprintln!("error: can't write to {}: {}", name, e);
+        exit(1);
+    }
+}
+
+/// `a` と `b` が同じファイルかどうか。どちらかが存在しない場合は `false` を返す。
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// 1つの JSON の値全体を整形して `writer` に書き込む
+fn format_document<R: BufRead, W: Write>(
+    reader: R,
+    mut writer: W,
+    format_options: &FormatOptions,
+    parse_options: &ParseOptions,
+    jsonc: bool,
+) -> Result<(), JsonPretError> {
+    if jsonc {
+        return json_prettier::format_jsonc(reader, writer, format_options);
+    }
+    if !parse_options.sort_keys && !parse_options.json5 {
+        // キーを並べ替えない場合は JsonObject を作らずに整形する
+        return json_prettier::format_reader(reader, writer, format_options);
+    }
+    let value: JsonObject = json_prettier::parse_reader_with_options(reader, parse_options)?;
+    json_prettier::to_writer(&mut writer, &value, format_options)
+        .map_err(|e| JsonPretError::IoError(IoError::from(e)))
+}
+
+/// JSON pointer、JSONPath、フィルタの順に適用し、出力する値を返す
+fn select(
+    value: &JsonObject,
+    pointer: Option<&JsonPointer>,
+    jsonpath: Option<&JsonPath>,
+    query: Option<&Query>,
+) -> Result<Vec<JsonObject>, String> {
+    let value: &JsonObject = match pointer {
+        Some(pointer) => match pointer.resolve(value) {
+            Some(v) => v,
+            None => return Err(format!("no value at the JSON pointer {}", pointer)),
+        },
+        None => value,
+    };
+
+    // JSONPath で選択した値のそれぞれにフィルタを適用する
+    let selected: Vec<&JsonObject> = match jsonpath {
+        Some(jsonpath) => jsonpath.query(value).into_iter().map(|node| node.value).collect(),
+        None => vec![value],
+    };
+    match query {
+        Some(query) => {
+            let mut outputs: Vec<JsonObject> = vec![];
+            for value in selected {
+                outputs.extend(query.run(value).map_err(|e| e.to_string())?);
+            }
+            Ok(outputs)
+        }
+        None => Ok(selected.into_iter().cloned().collect()),
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use json_prettier::{FormatOptions, Indent, ParseOptions};
+
+    use super::file_options;
+    use crate::{
+        config::{Settings, CONFIG_FILE},
+        testutil::TempDir,
+    };
+
+    #[test]
+    fn test_file_options() {
+        let root: TempDir = TempDir::new("options");
+        fs::write(root.join(CONFIG_FILE), r#"{"sortKeys": true, "indent": 2}"#).unwrap();
+        let path = root.join("a.json");
+        let options = |cli: &Settings| {
+            file_options(Some(&path), &FormatOptions::default(), &ParseOptions::default(), cli, false).unwrap()
+        };
+
+        let (format_options, parse_options) = options(&Settings::default());
+        assert_eq!(format_options.indent, Indent::Spaces(2));
+        assert!(parse_options.sort_keys);
+
+        // コマンドラインの指定を .jprc.json より優先する
+        let cli = Settings { sort_keys: Some(false), indent: Some(Indent::Tab), ..Settings::default() };
+        let (format_options, parse_options) = options(&cli);
+        assert_eq!(format_options.indent, Indent::Tab);
+        assert!(!parse_options.sort_keys);
+    }
+}
@@ -0,0 +1,38 @@
+//! テストで使う一時ディレクトリ
+
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// テスト用の一時ディレクトリ。テストが panic した場合も、drop する時に中身ごと削除する。
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// `jp-{name}-{プロセス ID}` という名前の空のディレクトリを作る
+    pub fn new(name: &str) -> TempDir {
+        let path: PathBuf = env::temp_dir().join(format!("jp-{}-{}", name, process::id()));
+        // 以前のテストで残ったディレクトリは削除する
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
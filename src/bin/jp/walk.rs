@@ -0,0 +1,256 @@
+//! コマンドラインで指定したディレクトリと glob パターンから、整形するファイルを集める
+//!
+//! ディレクトリは再帰的にたどり、`.gitignore` と `.jpignore` に書かれたファイルは除く。
+//! Git のリポジトリの中では、たどり始めたディレクトリより上にある一覧もリポジトリのルートまで読み込む。
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::glob::{to_slash, Glob};
+
+/// 無視するファイルの一覧に書く名前
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".jpignore"];
+
+/// `.gitignore` 形式の1行
+struct Rule {
+    glob: Glob,
+    /// `!` で始まり、無視しないファイルを指定するかどうか
+    negated: bool,
+    /// `/` で終わり、ディレクトリだけに一致するかどうか
+    dir_only: bool,
+}
+
+impl Rule {
+    /// 空行とコメントの場合は `None` を返す
+    fn parse(line: &str) -> Option<Rule> {
+        let line: &str = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line): (bool, &str) = match line.strip_prefix('!') {
+            Some(l) => (true, l),
+            None => (false, line),
+        };
+        let (dir_only, line): (bool, &str) = match line.strip_suffix('/') {
+            Some(l) => (true, l),
+            None => (false, line),
+        };
+        // 途中に / を含むパターンは、無視するファイルの一覧があるディレクトリからのパスに一致する
+        let pattern: String = match line.strip_prefix('/') {
+            Some(l) => l.to_string(),
+            None if line.contains('/') => line.to_string(),
+            None => format!("**/{}", line),
+        };
+        Some(Rule { glob: Glob::new(&pattern), negated, dir_only })
+    }
+}
+
+/// `dir` にある無視するファイルの一覧を読み込む
+fn read_rules(dir: &Path) -> io::Result<Vec<Rule>> {
+    let mut rules: Vec<Rule> = vec![];
+    for name in IGNORE_FILES {
+        match fs::read_to_string(dir.join(name)) {
+            Ok(content) => rules.extend(content.lines().filter_map(Rule::parse)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(rules)
+}
+
+/// ディレクトリをたどりながら、各ディレクトリの無視するファイルの一覧を読み込む
+struct Walker {
+    /// たどり始めたディレクトリ
+    base: PathBuf,
+    /// リポジトリのルートから `base` までのパス。リポジトリの外では空になる。
+    prefix: PathBuf,
+    /// 読み込んだディレクトリの `prefix` からのパスと、そこに書かれたルール
+    rules: Vec<(PathBuf, Vec<Rule>)>,
+}
+
+impl Walker {
+    /// `base` からたどる `Walker` を作る。`base` が Git のリポジトリの中にある場合は、
+    /// リポジトリのルートから `base` の親までの各ディレクトリのルールを読み込んでおく。
+    fn new(base: &Path) -> io::Result<Walker> {
+        let mut walker: Walker = Walker { base: base.to_path_buf(), prefix: PathBuf::new(), rules: vec![] };
+        let absolute: PathBuf = fs::canonicalize(base)?;
+        let Some(root) = absolute.ancestors().find(|dir| dir.join(".git").exists()) else {
+            return Ok(walker);
+        };
+        let mut ancestors: Vec<&Path> = absolute.ancestors().skip(1).take_while(|dir| dir.starts_with(root)).collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            let relative: PathBuf = dir.strip_prefix(root).unwrap_or(dir).to_path_buf();
+            walker.rules.push((relative, read_rules(dir)?));
+        }
+        walker.prefix = absolute.strip_prefix(root).unwrap_or(&absolute).to_path_buf();
+        Ok(walker)
+    }
+
+    /// `base` 以下の `path` を、ルールのディレクトリと比べられるようにリポジトリのルートからのパスにする
+    fn relative(&self, path: &Path) -> PathBuf {
+        self.prefix.join(path.strip_prefix(&self.base).unwrap_or(path))
+    }
+
+    /// `dir` 以下のファイルのうち、無視されず `filter` に一致するものを `files` に追加する
+    fn walk(&mut self, dir: &Path, filter: &dyn Fn(&Path) -> bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        let rules: Vec<Rule> = read_rules(dir)?;
+        self.rules.push((self.relative(dir), rules));
+
+        let mut entries: Vec<(PathBuf, bool)> = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry: fs::DirEntry = entry?;
+            // シンボリックリンクはたどらない
+            let file_type: fs::FileType = entry.file_type()?;
+            if file_type.is_dir() || file_type.is_file() {
+                entries.push((entry.path(), file_type.is_dir()));
+            }
+        }
+        entries.sort();
+
+        for (path, is_dir) in entries {
+            if path.file_name().is_some_and(|n| n == ".git") || self.is_ignored(&path, is_dir) {
+                continue;
+            }
+            if is_dir {
+                self.walk(&path, filter, files)?;
+            } else if filter(&path) {
+                files.push(path);
+            }
+        }
+        self.rules.pop();
+        Ok(())
+    }
+
+    /// 最後に一致したルールで、`path` を無視するかどうかを決める
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let path: PathBuf = self.relative(path);
+        let mut ignored: bool = false;
+        for (dir, rules) in &self.rules {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative: String = to_slash(relative);
+            for rule in rules {
+                if (is_dir || !rule.dir_only) && rule.glob.is_match(&relative) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+/// glob パターンとして扱う文字を含むかどうか
+fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?', '[', '{'])
+}
+
+/// 先頭と途中の `.` を除いたパス。`./a.json` と `a.json` を同じファイルとして扱うために使う。
+fn normalize(path: &Path) -> PathBuf {
+    path.components().filter(|c| *c != Component::CurDir).collect()
+}
+
+/// コマンドラインの引数から整形するファイルを集める。
+///
+/// ディレクトリの場合は `extensions` のいずれかの拡張子を持つファイルを、glob パターンの場合は
+/// パターンに一致するファイルを集める。それ以外はファイル名としてそのまま扱う。
+/// 同じファイルは1度だけ含まれる。
+pub fn collect(args: &[String], extensions: &[&str]) -> Result<Vec<PathBuf>, String> {
+    let mut files: Vec<PathBuf> = vec![];
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for arg in args {
+        let path: &Path = Path::new(arg);
+        let mut found: Vec<PathBuf> = vec![];
+        if path.is_dir() {
+            let filter = |p: &Path| p.extension().is_some_and(|e| extensions.iter().any(|x| e == *x));
+            Walker::new(path)
+                .and_then(|mut walker| walker.walk(path, &filter, &mut found))
+                .map_err(|e| format!("can't read a directory {}: {}", arg, e))?;
+        } else if is_glob(arg) && !path.exists() {
+            // パターンを含まない先頭のディレクトリからたどる
+            let base: PathBuf = path
+                .components()
+                .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+                .collect();
+            let glob: Glob = Glob::new(&to_slash(path));
+            let root: &Path = if base.as_os_str().is_empty() { Path::new(".") } else { &base };
+            let filter = |p: &Path| match base.as_os_str().is_empty() {
+                true => glob.is_match(&to_slash(p.strip_prefix(".").unwrap_or(p))),
+                false => glob.is_match(&to_slash(p)),
+            };
+            if root.is_dir() {
+                Walker::new(root)
+                    .and_then(|mut walker| walker.walk(root, &filter, &mut found))
+                    .map_err(|e| format!("can't read a directory {}: {}", root.display(), e))?;
+            }
+            if found.is_empty() {
+                return Err(format!("no files match {}", arg));
+            }
+        } else {
+            found.push(path.to_path_buf());
+        }
+        for file in found {
+            // `.` からたどった場合の先頭の ./ は付けない
+            let file: PathBuf = match file.strip_prefix(".") {
+                Ok(f) if !path.starts_with(".") => f.to_path_buf(),
+                _ => file,
+            };
+            if seen.insert(normalize(&file)) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::collect;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn test_collect() {
+        let root: TempDir = TempDir::new("walk");
+        for dir in ["src/nested", "src/node_modules", "node_modules/pkg", "build", ".git"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "a.json", "b.txt", "src/c.json", "src/nested/d.json", "src/nested/keep.json",
+            "node_modules/pkg/e.json", "build/f.json", ".git/g.json", "src/skip.json",
+            "src/node_modules/h.json",
+        ] {
+            fs::write(root.join(file), "{}").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "# deps\nnode_modules/\n/build\n/src/skip.json\n").unwrap();
+        fs::write(root.join("src/.jpignore"), "nested/*.json\n!keep.json\n").unwrap();
+
+        let arg = |p: &str| root.join(p).to_string_lossy().into_owned();
+        let files: Vec<PathBuf> = collect(&[arg("")], &["json"]).unwrap();
+        let expect: Vec<PathBuf> = ["a.json", "src/c.json", "src/nested/keep.json"].iter().map(|f| root.join(f)).collect();
+        assert_eq!(files, expect);
+
+        // リポジトリのルートにある一覧は、サブディレクトリからたどる場合にも使う
+        let files: Vec<PathBuf> = collect(&[arg("src")], &["json"]).unwrap();
+        assert_eq!(files, vec![root.join("src/c.json"), root.join("src/nested/keep.json")]);
+
+        // glob パターンと、重複したファイル
+        let files: Vec<PathBuf> = collect(&[arg("src/**/*.json"), arg("src/c.json")], &["json"]).unwrap();
+        assert_eq!(files, vec![root.join("src/c.json"), root.join("src/nested/keep.json")]);
+        let files: Vec<PathBuf> = collect(&[arg("*.{json,txt}")], &["json"]).unwrap();
+        assert_eq!(files, vec![root.join("a.json"), root.join("b.txt")]);
+
+        assert_eq!(collect(&[arg("*.yaml")], &["json"]), Err(format!("no files match {}", arg("*.yaml"))));
+
+        // ./ の有無だけが違うパスも同じファイルとして扱う
+        let args: Vec<String> = ["./x.json", "x.json", "y/./z.json", "y/z.json"].iter().map(|a| a.to_string()).collect();
+        assert_eq!(collect(&args, &["json"]), Ok(vec![PathBuf::from("./x.json"), PathBuf::from("y/./z.json")]));
+    }
+}
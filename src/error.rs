@@ -1,18 +1,132 @@
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+use crate::position::Position;
 
 /// Json Prettier で発生するエラーを扱う enum
 #[derive(Debug, PartialEq)]
 pub enum JsonPretError {
     LexerError(LexerError),
     ParserError(ParserError),
+    EofError(EofError),
+    IoError(IoError),
+    PointerError(PointerError),
+    QueryError(QueryError),
+}
+
+impl JsonPretError {
+    /// エラーメッセージを返す
+    pub fn message(&self) -> &str {
+        match self {
+            JsonPretError::LexerError(e) => &e.message,
+            JsonPretError::ParserError(e) => &e.message,
+            JsonPretError::EofError(e) => &e.message,
+            JsonPretError::IoError(e) => &e.message,
+            JsonPretError::PointerError(e) => &e.message,
+            JsonPretError::QueryError(e) => &e.message,
+        }
+    }
+
+    /// エラーが発生した位置を返す
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            JsonPretError::LexerError(e) => Some(e.position),
+            JsonPretError::ParserError(e) => Some(e.position),
+            JsonPretError::EofError(e) => Some(e.position),
+            JsonPretError::IoError(_)
+            | JsonPretError::PointerError(_)
+            | JsonPretError::QueryError(_) => None,
+        }
+    }
+
+    /// エラーが発生した行の内容を返す
+    pub fn source_line(&self) -> Option<&str> {
+        match self {
+            JsonPretError::LexerError(e) => e.source_line.as_deref(),
+            JsonPretError::ParserError(e) => e.source_line.as_deref(),
+            JsonPretError::EofError(e) => e.source_line.as_deref(),
+            JsonPretError::IoError(_)
+            | JsonPretError::PointerError(_)
+            | JsonPretError::QueryError(_) => None,
+        }
+    }
+
+    /// `source_line` が行の途中から始まる場合に、省略された先頭の文字数を返す
+    pub fn source_line_offset(&self) -> usize {
+        match self {
+            JsonPretError::LexerError(e) => e.source_line_offset,
+            JsonPretError::ParserError(e) => e.source_line_offset,
+            JsonPretError::EofError(e) => e.source_line_offset,
+            JsonPretError::IoError(_)
+            | JsonPretError::PointerError(_)
+            | JsonPretError::QueryError(_) => 0,
+        }
+    }
+
+    /// 入力文字列からエラーが発生した行を取り出し、エラーに付与する
+    pub fn with_source(mut self, input: &str) -> JsonPretError {
+        if let Some(position) = self.position() {
+            if self.source_line().is_none() {
+                if let Some(line) = line_at(input, position.line) {
+                    self.set_source_line(line, 0);
+                }
+            }
+        }
+        self
+    }
+
+    /// 入力の一部だけをパースした場合に、その部分の中での位置を入力全体での位置に直す。
+    /// `lines` はその部分より前の行数、`offset` はその部分の先頭のバイトオフセット。
+    pub(crate) fn shift_position(mut self, lines: usize, offset: usize) -> JsonPretError {
+        let position: &mut Position = match &mut self {
+            JsonPretError::LexerError(e) => &mut e.position,
+            JsonPretError::ParserError(e) => &mut e.position,
+            JsonPretError::EofError(e) => &mut e.position,
+            JsonPretError::IoError(_)
+            | JsonPretError::PointerError(_)
+            | JsonPretError::QueryError(_) => return self,
+        };
+        position.line += lines;
+        position.offset += offset;
+        self
+    }
+
+    /// エラーが発生した行の内容を設定する
+    pub(crate) fn set_source_line(&mut self, line: String, offset: usize) {
+        let (source_line, source_line_offset) = match self {
+            JsonPretError::LexerError(e) => (&mut e.source_line, &mut e.source_line_offset),
+            JsonPretError::ParserError(e) => (&mut e.source_line, &mut e.source_line_offset),
+            JsonPretError::EofError(e) => (&mut e.source_line, &mut e.source_line_offset),
+            JsonPretError::IoError(_)
+            | JsonPretError::PointerError(_)
+            | JsonPretError::QueryError(_) => return,
+        };
+        *source_line = Some(line);
+        *source_line_offset = offset;
+    }
 }
 
 impl Display for JsonPretError {
     fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            JsonPretError::LexerError(e) => write!(f, "LexerError: {}", e.message),
-            JsonPretError::ParserError(e) => write!(f, "ParserError: {}", e.message)
+        let kind = match self {
+            JsonPretError::LexerError(_) => "LexerError",
+            JsonPretError::ParserError(_) => "ParserError",
+            JsonPretError::EofError(_) => "EofError",
+            JsonPretError::IoError(_) => "IoError",
+            JsonPretError::PointerError(_) => "PointerError",
+            JsonPretError::QueryError(_) => "QueryError",
+        };
+        write!(f, "{}: {}", kind, self.message())?;
+
+        if let Some(position) = self.position() {
+            write!(f, " at line {}, column {}", position.line, position.column)?;
+            if let Some(line) = self.source_line() {
+                write_snippet(f, line, self.source_line_offset(), position)?;
+            }
         }
+        Ok(())
     }
 }
 
@@ -22,12 +136,21 @@ impl Display for JsonPretError {
 pub struct LexerError {
     /// エラーメッセージ
     pub message: String,
+    /// エラーが発生した位置
+    pub position: Position,
+    /// エラーが発生した行の内容
+    pub source_line: Option<String>,
+    /// `source_line` が行の途中から始まる場合に、省略された先頭の文字数
+    pub source_line_offset: usize,
 }
 
 impl LexerError {
-    pub fn new(msg: &str) -> LexerError {
+    pub fn new(msg: &str, position: Position) -> LexerError {
         LexerError {
             message: msg.to_string(),
+            position,
+            source_line: None,
+            source_line_offset: 0,
         }
     }
 }
@@ -37,28 +160,160 @@ impl LexerError {
 pub struct ParserError {
     /// エラーメッセージ
     pub message: String,
+    /// エラーが発生した位置
+    pub position: Position,
+    /// エラーが発生した行の内容
+    pub source_line: Option<String>,
+    /// `source_line` が行の途中から始まる場合に、省略された先頭の文字数
+    pub source_line_offset: usize,
 }
 
 impl ParserError {
-    pub fn new(msg: &str) -> ParserError {
+    pub fn new(msg: &str, position: Position) -> ParserError {
         ParserError {
             message: msg.to_string(),
+            position,
+            source_line: None,
+            source_line_offset: 0,
+        }
+    }
+}
+
+/// 入力が途中で終わった時のエラー
+#[derive(Debug, PartialEq)]
+pub struct EofError {
+    /// エラーメッセージ
+    pub message: String,
+    /// 入力の終端の位置
+    pub position: Position,
+    /// 入力の最終行の内容
+    pub source_line: Option<String>,
+    /// `source_line` が行の途中から始まる場合に、省略された先頭の文字数
+    pub source_line_offset: usize,
+}
+
+impl EofError {
+    pub fn new(msg: &str, position: Position) -> EofError {
+        EofError {
+            message: msg.to_string(),
+            position,
+            source_line: None,
+            source_line_offset: 0,
+        }
+    }
+}
+
+/// 入力の読み込み中に発生した I/O エラー
+#[derive(Debug, PartialEq)]
+pub struct IoError {
+    /// エラーメッセージ
+    pub message: String,
+    /// I/O エラーの種類
+    pub kind: io::ErrorKind,
+}
+
+impl From<io::Error> for IoError {
+    fn from(e: io::Error) -> IoError {
+        IoError {
+            message: e.to_string(),
+            kind: e.kind(),
+        }
+    }
+}
+
+/// JSON Pointer の文法が正しくない、または指す場所に値を追加・削除できない時のエラー
+#[derive(Debug, PartialEq)]
+pub struct PointerError {
+    /// エラーメッセージ
+    pub message: String,
+    /// 対象の JSON Pointer
+    pub pointer: String,
+}
+
+impl PointerError {
+    pub fn new(msg: &str, pointer: &str) -> PointerError {
+        PointerError {
+            message: msg.to_string(),
+            pointer: pointer.to_string(),
         }
     }
 }
 
+/// クエリの文法が正しくない、またはクエリの実行中に発生したエラー
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    /// エラーメッセージ
+    pub message: String,
+}
+
+impl QueryError {
+    pub fn new(msg: &str) -> QueryError {
+        QueryError { message: msg.to_string() }
+    }
+}
+
+/// スニペットとして表示する最大の文字数
+const SNIPPET_WIDTH: usize = 80;
+
+/// 入力の `line` 行目(1始まり)を返す
+fn line_at(input: &str, line: usize) -> Option<String> {
+    input
+        .split('\n')
+        .nth(line.checked_sub(1)?)
+        .map(|l| l.trim_end_matches('\r').to_string())
+}
+
+/// エラーが発生した行と、その列を指す ^ を書き込む。
+/// 長い行は `column` の周辺だけを切り出して表示する。
+/// `offset` は `line` より前に省略されている文字数。
+fn write_snippet(
+    f: &mut fmt::Formatter<'_>,
+    line: &str,
+    offset: usize,
+    position: Position,
+) -> fmt::Result {
+    let chars: Vec<char> = line.chars().collect();
+    let caret = position.column.saturating_sub(1 + offset).min(chars.len());
+
+    let start = if chars.len() > SNIPPET_WIDTH {
+        caret.saturating_sub(SNIPPET_WIDTH / 2).min(chars.len() - SNIPPET_WIDTH)
+    } else {
+        0
+    };
+    let end = (start + SNIPPET_WIDTH).min(chars.len());
+
+    let prefix = if start > 0 || offset > 0 { "..." } else { "" };
+    let suffix = if end < chars.len() { "..." } else { "" };
+    let visible: String = chars[start..end].iter().collect();
+
+    // タブ幅がずれないよう、^ の前はタブをそのまま残す
+    let padding: String = prefix
+        .chars()
+        .chain(chars[start..caret].iter().copied())
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let gutter = position.line.to_string();
+    writeln!(f)?;
+    writeln!(f, "{} | {}{}{}", gutter, prefix, visible, suffix)?;
+    write!(f, "{:width$} | {}^", "", padding, width = gutter.len())
+}
+
 
 // --- テストコード ---
 
 #[cfg(test)]
 mod tests {
-    use crate::error::*;
+    use crate::{error::*, position::Position};
     #[test]
     fn test_lexer_error_new() {
         let expect: LexerError = LexerError {
-            message: "Error message".to_string()
+            message: "Error message".to_string(),
+            position: Position::new(2, 3, 10),
+            source_line: None,
+            source_line_offset: 0,
         };
-        let actual: LexerError = LexerError::new("Error message");
+        let actual: LexerError = LexerError::new("Error message", Position::new(2, 3, 10));
 
         assert_eq!(actual, expect);
     }
@@ -66,10 +321,73 @@ mod tests {
     #[test]
     fn test_parser_error_new() {
         let expect: ParserError = ParserError {
-            message: "Error message".to_string()
+            message: "Error message".to_string(),
+            position: Position::new(2, 3, 10),
+            source_line: None,
+            source_line_offset: 0,
+        };
+        let actual: ParserError = ParserError::new("Error message", Position::new(2, 3, 10));
+
+        assert_eq!(actual, expect);
+    }
+
+    #[test]
+    fn test_eof_error_new() {
+        let expect: EofError = EofError {
+            message: "Error message".to_string(),
+            position: Position::new(2, 3, 10),
+            source_line: None,
+            source_line_offset: 0,
         };
-        let actual: ParserError = ParserError::new("Error message");
+        let actual: EofError = EofError::new("Error message", Position::new(2, 3, 10));
 
         assert_eq!(actual, expect);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_display_with_source() {
+        let input = "{\n  \"key\": x\n}";
+        let err = JsonPretError::LexerError(
+            LexerError::new("an unexpected char x", Position::new(2, 10, 11))
+        ).with_source(input);
+
+        assert_eq!(err.source_line(), Some("  \"key\": x"));
+        assert_eq!(
+            err.to_string(),
+            "LexerError: an unexpected char x at line 2, column 10\n2 |   \"key\": x\n  |          ^"
+        );
+    }
+
+    #[test]
+    fn test_display_with_offset() {
+        let mut err = JsonPretError::ParserError(
+            ParserError::new("an unexpected token", Position::new(1, 1005, 1004))
+        );
+        err.set_source_line("[1, 2 3]".to_string(), 998);
+
+        assert_eq!(
+            err.to_string(),
+            "ParserError: an unexpected token at line 1, column 1005\n1 | ...[1, 2 3]\n  |          ^"
+        );
+    }
+
+    #[test]
+    fn test_io_error() {
+        let err = JsonPretError::IoError(IoError::from(io::Error::other("broken pipe")));
+        assert_eq!(err.position(), None);
+        assert_eq!(err.to_string(), "IoError: broken pipe");
+    }
+
+    #[test]
+    fn test_display_long_line() {
+        let line = format!("[{}x]", "1,".repeat(100));
+        let err = JsonPretError::LexerError(
+            LexerError::new("an unexpected char x", Position::new(1, 202, 201))
+        ).with_source(&line);
+
+        let message = err.to_string();
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines[1], format!("1 | ...{}x]", &line[123..201]));
+        assert_eq!(lines[2].find('^'), lines[1].find('x'));
+    }
+}
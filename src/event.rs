@@ -0,0 +1,453 @@
+use std::io::BufRead;
+
+use crate::{
+    error::{EofError, JsonPretError, ParserError},
+    lexer::{is_non_finite, Lexer, SpannedToken, Token},
+    map::Map,
+    parser::MAX_DEPTH,
+    position::Position,
+    JsonNumber, JsonObject,
+};
+
+/// `EventReader` が返すイベント
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Object の開始 `{`
+    StartObject,
+    /// Object の終了 `}`
+    EndObject,
+    /// Array の開始 `[`
+    StartArray,
+    /// Array の終了 `]`
+    EndArray,
+    /// Object のキー
+    Key(String),
+    /// String, Number, Bool, Null のいずれかの値
+    Value(JsonObject),
+}
+
+/// 閉じていない Array または Object
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    Array,
+    Object,
+}
+
+/// 次に読む `Token` の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// 値
+    Value,
+    /// Array の最初の要素か ]
+    FirstElement,
+    /// Object の最初のキーか }
+    FirstKey,
+    /// Object のキーと :
+    Key,
+    /// 値の後の , か閉じ括弧
+    AfterValue,
+    /// ルートの値を読み終えた、またはエラーが発生した
+    Done,
+}
+
+/// `Lexer` から `Token` を読み込み、`Event` を1つずつ返すパーサ。
+///
+/// `JsonObject` の木を作らないので、大きな入力から一部の値だけを取り出す時に使う。
+/// 不要な値は `skip_value` で読み飛ばせる。
+///
+/// ```
+/// use json_prettier::{Event, EventReader, JsonObject};
+///
+/// let mut reader = EventReader::new(r#"{"a": [1, 2], "b": "x"}"#);
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::StartObject));
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::Key("a".to_string())));
+/// reader.skip_value().unwrap();
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::Key("b".to_string())));
+/// assert_eq!(reader.read_value().unwrap(), JsonObject::String("x".to_string()));
+/// assert_eq!(reader.next_event().unwrap(), Some(Event::EndObject));
+/// assert_eq!(reader.next_event().unwrap(), None);
+/// ```
+#[derive(Debug)]
+pub struct EventReader<R> {
+    lexer: Lexer<R>,
+    /// 閉じていない Array と Object
+    stack: Vec<Container>,
+    state: State,
+    /// 先読みした `Event`
+    peeked: Option<Event>,
+}
+
+impl<'a> EventReader<&'a [u8]> {
+    pub fn new(input: &'a str) -> EventReader<&'a [u8]> {
+        EventReader::from_reader(input.as_bytes())
+    }
+}
+
+impl<R: BufRead> EventReader<R> {
+    /// `reader` から必要な分だけ読み込む EventReader を作る
+    pub fn from_reader(reader: R) -> EventReader<R> {
+        EventReader::from_lexer(Lexer::from_reader(reader))
+    }
+
+    pub fn from_lexer(lexer: Lexer<R>) -> EventReader<R> {
+        EventReader {
+            lexer,
+            stack: vec![],
+            state: State::Value,
+            peeked: None,
+        }
+    }
+
+    /// 次の `Event` を返す。
+    /// ルートの値を読み終え、後ろに `Token` が残っていない場合は `None` を返す。
+    pub fn next_event(&mut self) -> Result<Option<Event>, JsonPretError> {
+        if let Some(event) = self.peeked.take() {
+            return Ok(Some(event));
+        }
+        let result = self.read_event();
+        if result.is_err() {
+            self.state = State::Done;
+        }
+        result
+    }
+
+    /// 次の `Event` を読み進めずに返す
+    pub fn peek_event(&mut self) -> Result<Option<&Event>, JsonPretError> {
+        if self.peeked.is_none() {
+            self.peeked = self.next_event()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    /// 次の値を読み飛ばす。Array と Object は対応する閉じ括弧まで読み飛ばす。
+    pub fn skip_value(&mut self) -> Result<(), JsonPretError> {
+        let mut depth: usize = 0;
+        loop {
+            match self.expect_event()? {
+                Event::StartObject | Event::StartArray => depth += 1,
+                Event::EndObject | Event::EndArray if depth > 0 => depth -= 1,
+                event @ (Event::EndObject | Event::EndArray) => {
+                    return Err(self.unexpected_event(event));
+                }
+                Event::Key(_) => continue,
+                Event::Value(_) => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// 次の値を `JsonObject` として読み込む
+    pub fn read_value(&mut self) -> Result<JsonObject, JsonPretError> {
+        match self.expect_event()? {
+            Event::Value(v) => Ok(v),
+            Event::StartArray => {
+                let mut array: Vec<JsonObject> = vec![];
+                while self.peek_event()? != Some(&Event::EndArray) {
+                    array.push(self.read_value()?);
+                }
+                self.next_event()?;
+                Ok(JsonObject::Array(array))
+            }
+            Event::StartObject => {
+                let mut obj: Map = Map::new();
+                // キーの後には必ず値があり、キー以外は EndObject になる
+                while let Event::Key(key) = self.expect_event()? {
+                    obj.insert(key, self.read_value()?);
+                }
+                Ok(JsonObject::Object(obj))
+            }
+            event => Err(self.unexpected_event(event)),
+        }
+    }
+
+    /// 現在の Array と Object の入れ子の深さを返す
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// 次に読み込む文字の位置を返す
+    pub fn position(&self) -> Position {
+        self.lexer.position()
+    }
+
+    /// 値の途中で入力が終わらないよう、`Event` が必ずある場合に使う
+    fn expect_event(&mut self) -> Result<Event, JsonPretError> {
+        match self.next_event()? {
+            Some(event) => Ok(event),
+            None => Err(self.eof_error()),
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event>, JsonPretError> {
+        match self.state {
+            State::Value => {
+                let token: SpannedToken = self.next_token()?;
+                self.value(token).map(Some)
+            }
+            State::FirstElement => {
+                let token: SpannedToken = self.next_token()?;
+                if token.token == Token::RightBracket {
+                    return Ok(Some(self.close()));
+                }
+                self.value(token).map(Some)
+            }
+            State::FirstKey => {
+                let token: SpannedToken = self.next_token()?;
+                if token.token == Token::RightBrace {
+                    return Ok(Some(self.close()));
+                }
+                self.key(token).map(Some)
+            }
+            State::Key => {
+                let token: SpannedToken = self.next_token()?;
+                self.key(token).map(Some)
+            }
+            State::AfterValue => self.after_value(),
+            State::Done => Ok(None),
+        }
+    }
+
+    /// 値の最初の `Token` から `Event` を作る
+    fn value(&mut self, token: SpannedToken) -> Result<Event, JsonPretError> {
+        let value: JsonObject = match token.token {
+            Token::LeftBracket => return self.open(Container::Array, token.span.start),
+            Token::LeftBrace => return self.open(Container::Object, token.span.start),
+            Token::Null => JsonObject::Null,
+            Token::Bool(b) => JsonObject::Bool(b),
+            Token::Number(n) => JsonObject::Number(n),
+            Token::String(s) => JsonObject::String(s),
+            Token::Identifier(name) if is_non_finite(&name) => {
+                JsonObject::Number(JsonNumber::from_non_finite(&name))
+            }
+            t => return Err(self.error(
+                &format!(
+                    "token must start {{ or [ or String or Number or Bool or Null, but start '{}'",
+                    t
+                ),
+                token.span.start,
+            )),
+        };
+        self.state = State::AfterValue;
+        Ok(Event::Value(value))
+    }
+
+    /// Object のキーと : を読み込む
+    fn key(&mut self, key: SpannedToken) -> Result<Event, JsonPretError> {
+        let colon: SpannedToken = self.next_token()?;
+        match (key.token.into_key(self.lexer.is_json5()), colon.token) {
+            (Some(key), Token::Colon) => {
+                self.state = State::Value;
+                Ok(Event::Key(key))
+            }
+            _ => Err(self.error("a pair 'String(key)' and ':' is expected.", colon.span.start)),
+        }
+    }
+
+    /// 値の後に続く , か閉じ括弧を読む。ルートの値の後では `Token` が残っていないことを確認する。
+    fn after_value(&mut self) -> Result<Option<Event>, JsonPretError> {
+        let container: Container = match self.stack.last() {
+            Some(&c) => c,
+            None => {
+                if let Some(t) = self.lexer.read_token()? {
+                    return Err(self.error(
+                        &format!("an unexpected token '{}' after the root value", t.token),
+                        t.span.start,
+                    ));
+                }
+                self.state = State::Done;
+                return Ok(None);
+            }
+        };
+
+        let token: SpannedToken = self.next_token()?;
+        match (container, &token.token) {
+            // JSON5 では , の後に閉じ括弧を許す
+            (Container::Array, Token::Comma) => {
+                self.state = if self.lexer.is_json5() { State::FirstElement } else { State::Value };
+                self.read_event()
+            }
+            (Container::Object, Token::Comma) => {
+                self.state = if self.lexer.is_json5() { State::FirstKey } else { State::Key };
+                self.read_event()
+            }
+            (Container::Array, Token::RightBracket) | (Container::Object, Token::RightBrace) => {
+                Ok(Some(self.close()))
+            }
+            (Container::Array, t) => Err(self.error(
+                &format!("a ']' or ',' is expected, but '{}' is inputed", t),
+                token.span.start,
+            )),
+            (Container::Object, t) => Err(self.error(
+                &format!("{{ or , is expected, but {} is inputed", t),
+                token.span.start,
+            )),
+        }
+    }
+
+    fn open(&mut self, container: Container, position: Position) -> Result<Event, JsonPretError> {
+        if self.stack.len() >= MAX_DEPTH {
+            return Err(self.error(
+                &format!("the nesting depth exceeds the limit of {}", MAX_DEPTH),
+                position,
+            ));
+        }
+        self.stack.push(container);
+        match container {
+            Container::Array => {
+                self.state = State::FirstElement;
+                Ok(Event::StartArray)
+            }
+            Container::Object => {
+                self.state = State::FirstKey;
+                Ok(Event::StartObject)
+            }
+        }
+    }
+
+    fn close(&mut self) -> Event {
+        self.state = State::AfterValue;
+        match self.stack.pop() {
+            Some(Container::Object) => Event::EndObject,
+            _ => Event::EndArray,
+        }
+    }
+
+    fn next_token(&mut self) -> Result<SpannedToken, JsonPretError> {
+        match self.lexer.read_token()? {
+            Some(t) => Ok(t),
+            None => Err(self.eof_error()),
+        }
+    }
+
+    /// 値が必要な位置で閉じ括弧を読んだ時のエラーを作る
+    fn unexpected_event(&mut self, event: Event) -> JsonPretError {
+        let position: Position = self.lexer.position();
+        self.error(&format!("a value is expected, but {:?} is read", event), position)
+    }
+
+    /// `position` で ParserError を作る
+    fn error(&mut self, msg: &str, position: Position) -> JsonPretError {
+        self.lexer.attach_source(JsonPretError::ParserError(ParserError::new(msg, position)))
+    }
+
+    /// 入力の終端で EofError を作る
+    fn eof_error(&mut self) -> JsonPretError {
+        let position: Position = self.lexer.position();
+        self.lexer.attach_source(JsonPretError::EofError(EofError::new(
+            "unexpected end of input",
+            position
+        )))
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = Result<Event, JsonPretError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::{
+        event::{Event, EventReader},
+        lexer::Lexer,
+        parse, parse_with_options, JsonNumber, JsonObject, JsonPretError, ParseOptions,
+    };
+
+    #[test]
+    fn test_events() {
+        let events: Vec<Event> = EventReader::new(r#"{"a": [1, {}], "b": [], "c": null}"#)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events, vec![
+            Event::StartObject,
+            Event::Key("a".to_string()),
+            Event::StartArray,
+            Event::Value(JsonObject::Number(JsonNumber::from(1))),
+            Event::StartObject,
+            Event::EndObject,
+            Event::EndArray,
+            Event::Key("b".to_string()),
+            Event::StartArray,
+            Event::EndArray,
+            Event::Key("c".to_string()),
+            Event::Value(JsonObject::Null),
+            Event::EndObject,
+        ]);
+
+        let events: Vec<Event> = EventReader::new(" true ").collect::<Result<_, _>>().unwrap();
+        assert_eq!(events, vec![Event::Value(JsonObject::Bool(true))]);
+    }
+
+    #[test]
+    fn test_skip_value() {
+        let json = r#"{"skip": {"x": [1, [2, {"y": 3}]]}, "pick": [true, "v"], "rest": 1}"#;
+        let reader = BufReader::with_capacity(5, json.as_bytes());
+        let mut reader = EventReader::from_reader(reader);
+
+        assert_eq!(reader.next_event().unwrap(), Some(Event::StartObject));
+        let mut picked = None;
+        while let Some(Event::Key(key)) = reader.next_event().unwrap() {
+            if key == "pick" {
+                picked = Some(reader.read_value().unwrap());
+            } else {
+                reader.skip_value().unwrap();
+            }
+            assert_eq!(reader.depth(), 1);
+        }
+        assert_eq!(picked, Some(parse(r#"[true, "v"]"#).unwrap()));
+        assert_eq!(reader.next_event().unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_value_matches_parse() {
+        for json in [r#"{"a": [1, {"b": []}], "c": {}}"#, "[[], [[]], {\"k\": \"v\"}]", "-1.5e3"] {
+            assert_eq!(EventReader::new(json).read_value(), parse(json));
+        }
+    }
+
+    #[test]
+    fn test_json5() {
+        let options = ParseOptions { json5: true, ..ParseOptions::default() };
+        for json in ["{a: [1, 0x10,], 'b': Infinity, /* c */ null: {},}", "[-NaN]", "[,]", "{a: 1,,}", "[1,,]"] {
+            let mut reader = EventReader::from_lexer(Lexer::new(json).json5(true));
+            let actual = reader.read_value().and_then(|v| match reader.next_event()? {
+                None => Ok(v),
+                Some(e) => Err(reader.unexpected_event(e)),
+            });
+            let expect = parse_with_options(json, &options);
+            assert_eq!(actual.is_ok(), expect.is_ok(), "{}", json);
+            if let (Ok(actual), Ok(expect)) = (actual, expect) {
+                assert_eq!(actual, expect);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        for input in ["", "[1, 2", "[1,]", r#"{"a": 1,}"#, r#"{"a" 1}"#, "[1] 2", "[1 2]", "{]", "[}"] {
+            let expect = parse(input).unwrap_err();
+            let actual = EventReader::new(input)
+                .collect::<Result<Vec<Event>, JsonPretError>>()
+                .unwrap_err();
+            assert_eq!(actual.message(), expect.message(), "{}", input);
+            assert_eq!(actual.position(), expect.position(), "{}", input);
+        }
+
+        // エラーの後はイベントを返さない
+        let mut reader = EventReader::new("[1 2]");
+        assert!(reader.by_ref().any(|e| e.is_err()));
+        assert!(reader.next().is_none());
+
+        let json = format!("{}{}", "[".repeat(1000), "]".repeat(1000));
+        assert!(EventReader::new(&json).any(|e| matches!(e, Err(JsonPretError::ParserError(_)))));
+    }
+}
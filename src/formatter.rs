@@ -0,0 +1,343 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufRead},
+};
+
+use crate::{
+    error::{IoError, JsonPretError},
+    event::{Event, EventReader},
+    serializer::{IoWriter, Serializer, WidthCounter},
+    FormatOptions,
+};
+
+/// `EventReader` から読み込んだ `Event` を、`JsonObject` を作らずにそのまま整形して書き込む
+struct StreamFormatter<R: BufRead, W: io::Write> {
+    reader: EventReader<R>,
+    serializer: Serializer<IoWriter<W>>,
+    /// 閉じていない Array と Object
+    stack: Vec<Level>,
+    /// 1行に収まるかを調べるために先読みした `Event`
+    pending: VecDeque<Event>,
+    /// `max_width` が指定されているかどうか
+    max_width: bool,
+}
+
+/// 閉じていない Array または Object の状態
+struct Level {
+    /// Object かどうか
+    object: bool,
+    /// まだ要素を書き込んでいないかどうか
+    first: bool,
+    /// 1行に書き込んでいるかどうか
+    flat: bool,
+    /// Object の場合、直前のキーに続く値が始まる桁
+    value_column: usize,
+}
+
+impl<R: BufRead, W: io::Write> StreamFormatter<R, W> {
+    fn format(&mut self) -> Result<(), JsonPretError> {
+        while let Some(event) = self.next_event()? {
+            match event {
+                Event::StartArray => self.open(false)?,
+                Event::StartObject => self.open(true)?,
+                Event::EndArray => self.close(']')?,
+                Event::EndObject => self.close('}')?,
+                Event::Key(key) => {
+                    self.write_separator()?;
+                    self.write(|s| s.write_key(&key))?;
+                    if self.max_width {
+                        let depth: usize = self.stack.len();
+                        let column: usize = self.serializer.indent_width(depth) + self.width(|s| s.write_key(&key));
+                        if let Some(level) = self.stack.last_mut() {
+                            level.value_column = column;
+                        }
+                    }
+                }
+                Event::Value(value) => {
+                    self.write_element_separator()?;
+                    let depth: usize = self.stack.len();
+                    self.write(|s| s.write_value(&value, depth))?;
+                }
+            }
+        }
+        self.write(|s| s.write_trailing_newline())
+    }
+
+    /// 開き括弧を書き込む。空の Array と Object は閉じ括弧まで書き込む。
+    fn open(&mut self, object: bool) -> Result<(), JsonPretError> {
+        let (open, close, end): (char, char, Event) = match object {
+            true => ('{', '}', Event::EndObject),
+            false => ('[', ']', Event::EndArray),
+        };
+        self.write_element_separator()?;
+        self.write(|s| s.write_punct(open))?;
+
+        // 空の Array と Object
+        if self.peek_event()? == Some(&end) {
+            self.next_event()?;
+            return self.write(|s| s.write_punct(close));
+        }
+
+        let column: usize = match self.stack.last() {
+            Some(level) if level.object => level.value_column,
+            _ => self.serializer.indent_width(self.stack.len()),
+        };
+        let flat: bool = match self.serializer.flat_limit(column) {
+            Some(limit) => self.fits(object, limit)?,
+            None => false,
+        };
+        if flat {
+            self.serializer.set_flat(true);
+        }
+        self.stack.push(Level { object, first: true, flat, value_column: 0 });
+        Ok(())
+    }
+
+    /// 閉じ括弧を書き込む
+    fn close(&mut self, c: char) -> Result<(), JsonPretError> {
+        let level: Option<Level> = self.stack.pop();
+        let depth: usize = self.stack.len();
+        self.write(|s| s.write_line_break(depth))?;
+        self.write(|s| s.write_punct(c))?;
+        if level.is_some_and(|l| l.flat) {
+            self.serializer.set_flat(false);
+        }
+        Ok(())
+    }
+
+    /// 開き括弧の後の `Event` を先読みし、閉じ括弧と後ろに続く , までが
+    /// 1行で `limit` 文字に収まるかどうかを返す。先読みは `limit` を超えた所で止める。
+    fn fits(&mut self, object: bool, limit: usize) -> Result<bool, JsonPretError> {
+        let mut events: Vec<Event> = vec![];
+        // 入れ子の Array と Object について、Object かどうかと最初の要素かどうか
+        let mut levels: Vec<(bool, bool)> = vec![(object, true)];
+        let mut width: usize = 1;
+        let mut fits: bool = false;
+
+        while width <= limit {
+            let Some(event) = self.next_event()? else {
+                break;
+            };
+            let after_key: bool = matches!(levels.last(), Some((true, _))) && !matches!(event, Event::Key(_));
+            let separator: usize = match levels.last_mut() {
+                Some((_, first)) if !after_key && !matches!(event, Event::EndArray | Event::EndObject) => {
+                    if std::mem::replace(first, false) { 0 } else { 2 }
+                }
+                _ => 0,
+            };
+            width += separator + match &event {
+                Event::StartArray | Event::StartObject => {
+                    levels.push((event == Event::StartObject, true));
+                    1
+                }
+                Event::EndArray | Event::EndObject => {
+                    levels.pop();
+                    1
+                }
+                Event::Key(key) => self.width(|s| s.write_key(key)),
+                Event::Value(value) => self.width(|s| s.write_value(value, 0)),
+            };
+            events.push(event);
+
+            if levels.is_empty() {
+                // 外側の Array または Object の中で後ろに要素が続く場合は , の分も必要
+                let next: Option<Event> = self.next_event()?;
+                let comma: bool = !self.stack.is_empty()
+                    && matches!(next, Some(Event::Key(_) | Event::Value(_) | Event::StartArray | Event::StartObject));
+                fits = width + usize::from(comma) <= limit;
+                events.extend(next);
+                break;
+            }
+        }
+
+        for event in events.into_iter().rev() {
+            self.pending.push_front(event);
+        }
+        Ok(fits)
+    }
+
+    /// `write` が1行に書き込む文字列の幅を返す
+    fn width(&self, write: impl FnOnce(&mut Serializer<WidthCounter>) -> fmt::Result) -> usize {
+        self.serializer.measure(usize::MAX, write).unwrap_or(0)
+    }
+
+    /// 次の `Event` を返す。先読みした `Event` があればそれを先に返す。
+    fn next_event(&mut self) -> Result<Option<Event>, JsonPretError> {
+        match self.pending.pop_front() {
+            Some(event) => Ok(Some(event)),
+            None => self.reader.next_event(),
+        }
+    }
+
+    fn peek_event(&mut self) -> Result<Option<&Event>, JsonPretError> {
+        if self.pending.is_empty() {
+            return self.reader.peek_event();
+        }
+        Ok(self.pending.front())
+    }
+
+    /// Array の要素または Object のキーの前の , と改行を書き込む
+    fn write_separator(&mut self) -> Result<(), JsonPretError> {
+        let depth: usize = self.stack.len();
+        match self.stack.last_mut() {
+            Some(level) if level.first => level.first = false,
+            Some(_) => self.write(|s| s.write_comma())?,
+            // ルートの値
+            None => return Ok(()),
+        }
+        self.write(|s| s.write_line_break(depth))
+    }
+
+    /// 値の前の区切りを書き込む。Object の値はキーの直後に続くので何も書き込まない。
+    fn write_element_separator(&mut self) -> Result<(), JsonPretError> {
+        match self.stack.last() {
+            Some(level) if level.object => Ok(()),
+            _ => self.write_separator(),
+        }
+    }
+
+    /// `Serializer` で書き込み、失敗した場合は I/O エラーを返す
+    fn write(
+        &mut self,
+        f: impl FnOnce(&mut Serializer<IoWriter<W>>) -> fmt::Result,
+    ) -> Result<(), JsonPretError> {
+        match f(&mut self.serializer) {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                let e: io::Error = self.serializer.get_mut().take_error();
+                Err(JsonPretError::IoError(IoError::from(e)))
+            }
+        }
+    }
+}
+
+/// `reader` から読み込んだ JSON を `options` に従って整形し、`writer` に書き込む。
+///
+/// `JsonObject` を作らずに `Event` ごとに書き込むので、入力の大きさに関わらず
+/// 使用するメモリは入れ子の深さ分だけで済む。`max_width` を指定した場合は、
+/// 1行に収まるかを調べるために最大で `max_width` 文字分の `Event` を先読みする。
+/// 入力が JSON として正しくない場合は、途中まで書き込んだ後にエラーを返す。
+/// Object のキーは入力順のまま出力され、重複したキーもまとめずに出力される。
+pub fn format_reader<R: BufRead, W: io::Write>(
+    reader: R,
+    writer: W,
+    options: &FormatOptions,
+) -> Result<(), JsonPretError> {
+    let mut formatter: StreamFormatter<R, W> = StreamFormatter {
+        reader: EventReader::from_reader(reader),
+        serializer: Serializer::with_options(IoWriter::new(writer), options.clone()),
+        stack: vec![],
+        pending: VecDeque::new(),
+        max_width: options.max_width.is_some(),
+    };
+    formatter.format()
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Write};
+
+    use crate::{
+        formatter::format_reader, parse, to_string_with_options, FormatOptions, Indent,
+        JsonPretError, Newline,
+    };
+
+    /// ストリームで整形した結果を返す
+    fn format(input: &str, options: &FormatOptions) -> Result<String, JsonPretError> {
+        let mut output: Vec<u8> = vec![];
+        format_reader(BufReader::with_capacity(4, input.as_bytes()), &mut output, options)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_format_reader_matches_tree() {
+        let inputs = [
+            r#"{"b": [1, true, null], "a": {"c": "d", "e": []}, "f": {}}"#,
+            r#"[[], {}, [[1, 2], {"x": {"y": [3]}}], "あ\n", -0.5e10]"#,
+            r#"  "scalar"  "#,
+            "123456789012345678901234567890",
+            r#"{"points": [[1, 2], [3, 4], [5, 6]], "name": "a long name to wrap", "tags": ["x", {"y": []}]}"#,
+        ];
+        let options = [
+            FormatOptions::default(),
+            FormatOptions::compact(),
+            FormatOptions {
+                indent: Indent::Tab,
+                newline: Newline::CrLf,
+                trailing_newline: true,
+                ..FormatOptions::default()
+            },
+            FormatOptions {
+                color: true,
+                ascii_only: true,
+                ..FormatOptions::default()
+            },
+            FormatOptions {
+                max_width: Some(24),
+                ..FormatOptions::default()
+            },
+            FormatOptions {
+                indent: Indent::Tab,
+                color: true,
+                max_width: Some(40),
+                ..FormatOptions::default()
+            },
+        ];
+        for input in inputs {
+            let value = parse(input).unwrap();
+            for options in &options {
+                assert_eq!(
+                    format(input, options).unwrap(),
+                    to_string_with_options(&value, options),
+                    "{}",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_reader_duplicate_keys() {
+        // 重複したキーはまとめずにそのまま出力する
+        let input = r#"{"z": 1, "a": 2, "z": 3}"#;
+        assert_eq!(format(input, &FormatOptions::compact()).unwrap(), r#"{"z":1,"a":2,"z":3}"#);
+    }
+
+    #[test]
+    fn test_format_reader_errors() {
+        for input in ["", "[1, 2", "[1,]", r#"{"a": 1,}"#, r#"{"a" 1}"#, "[1] 2", "[1 2]", "{]"] {
+            let expect = parse(input).unwrap_err();
+            let actual = format(input, &FormatOptions::default()).unwrap_err();
+            assert_eq!(actual.message(), expect.message(), "{}", input);
+            assert_eq!(actual.position(), expect.position(), "{}", input);
+        }
+
+        let json = format!("{}{}", "[".repeat(1000), "]".repeat(1000));
+        assert!(matches!(
+            format(&json, &FormatOptions::default()),
+            Err(JsonPretError::ParserError(_))
+        ));
+    }
+
+    /// 書き込みに失敗する Writer
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken pipe"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_format_reader_write_error() {
+        let err = format_reader("[1]".as_bytes(), BrokenWriter, &FormatOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "IoError: broken pipe");
+    }
+}
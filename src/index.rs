@@ -0,0 +1,177 @@
+use std::ops::{Index, IndexMut};
+
+use crate::{map::Map, JsonObject, NULL};
+
+/// `JsonObject` の `get` と `[]` で使える添字。
+/// Object のキー(`str`, `String`)と Array の添字(`usize`)に実装されている。
+pub trait JsonIndex: private::Sealed {
+    /// 値が無い場合や型が合わない場合は `None` を返す
+    #[doc(hidden)]
+    fn index_into<'a>(&self, value: &'a JsonObject) -> Option<&'a JsonObject>;
+
+    #[doc(hidden)]
+    fn index_into_mut<'a>(&self, value: &'a mut JsonObject) -> Option<&'a mut JsonObject>;
+
+    /// `IndexMut` で使う。必要な場合は値を追加する。
+    #[doc(hidden)]
+    fn index_or_insert<'a>(&self, value: &'a mut JsonObject) -> &'a mut JsonObject;
+}
+
+impl JsonIndex for usize {
+    fn index_into<'a>(&self, value: &'a JsonObject) -> Option<&'a JsonObject> {
+        match value {
+            JsonObject::Array(array) => array.get(*self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut JsonObject) -> Option<&'a mut JsonObject> {
+        match value {
+            JsonObject::Array(array) => array.get_mut(*self),
+            _ => None,
+        }
+    }
+
+    fn index_or_insert<'a>(&self, value: &'a mut JsonObject) -> &'a mut JsonObject {
+        match value {
+            JsonObject::Array(array) => {
+                let len: usize = array.len();
+                array.get_mut(*self).unwrap_or_else(|| {
+                    panic!("An index {} is out of bounds for an array of length {}", self, len)
+                })
+            }
+            _ => panic!("A JsonObject is not array"),
+        }
+    }
+}
+
+impl JsonIndex for str {
+    fn index_into<'a>(&self, value: &'a JsonObject) -> Option<&'a JsonObject> {
+        match value {
+            JsonObject::Object(map) => map.get(self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut JsonObject) -> Option<&'a mut JsonObject> {
+        match value {
+            JsonObject::Object(map) => map.get_mut(self),
+            _ => None,
+        }
+    }
+
+    fn index_or_insert<'a>(&self, value: &'a mut JsonObject) -> &'a mut JsonObject {
+        // Null は空の Object として扱う
+        if value.is_null() {
+            *value = JsonObject::Object(Map::new());
+        }
+        match value {
+            JsonObject::Object(map) => map.entry(self).or_insert(JsonObject::Null),
+            _ => panic!("A JsonObject is not object"),
+        }
+    }
+}
+
+impl JsonIndex for String {
+    fn index_into<'a>(&self, value: &'a JsonObject) -> Option<&'a JsonObject> {
+        self.as_str().index_into(value)
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut JsonObject) -> Option<&'a mut JsonObject> {
+        self.as_str().index_into_mut(value)
+    }
+
+    fn index_or_insert<'a>(&self, value: &'a mut JsonObject) -> &'a mut JsonObject {
+        self.as_str().index_or_insert(value)
+    }
+}
+
+impl<T: JsonIndex + ?Sized> JsonIndex for &T {
+    fn index_into<'a>(&self, value: &'a JsonObject) -> Option<&'a JsonObject> {
+        (**self).index_into(value)
+    }
+
+    fn index_into_mut<'a>(&self, value: &'a mut JsonObject) -> Option<&'a mut JsonObject> {
+        (**self).index_into_mut(value)
+    }
+
+    fn index_or_insert<'a>(&self, value: &'a mut JsonObject) -> &'a mut JsonObject {
+        (**self).index_or_insert(value)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for usize {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl<T: Sealed + ?Sized> Sealed for &T {}
+}
+
+/// {"key": true}
+/// v["key"] => JsonObject::Bool(true)
+/// [null, false, 3]
+/// v[2] => JsonObject::Number(3)
+///
+/// キーや添字が無い場合、型が合わない場合は Null を返す。
+impl<I: JsonIndex> Index<I> for JsonObject {
+    type Output = JsonObject;
+    fn index(&self, index: I) -> &Self::Output {
+        index.index_into(self).unwrap_or(&NULL)
+    }
+}
+
+/// v["key"] = JsonObject::Null
+///
+/// Object に無いキーは Null で追加し、Null の値は空の Object にしてからキーを追加する。
+/// Object 以外をキーで、Array 以外を添字で参照した場合と、
+/// Array の範囲外を参照した場合はパニックする。
+impl<I: JsonIndex> IndexMut<I> for JsonObject {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        index.index_or_insert(self)
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, JsonObject};
+
+    #[test]
+    fn test_index() {
+        let value = parse(r#"{"a": [1, {"b": "c"}], "d": null}"#).unwrap();
+        assert_eq!(value["a"][1]["b"].as_str(), Some("c"));
+        assert_eq!(value[&"a".to_string()][0].as_i64(), Some(1));
+
+        // 値が無い場合や型が合わない場合は Null
+        assert!(value["x"].is_null());
+        assert!(value["a"][5].is_null());
+        assert!(value["a"]["b"].is_null());
+        assert!(value[0].is_null());
+        assert!(value["x"]["y"][0].is_null());
+    }
+
+    #[test]
+    fn test_index_mut() {
+        let mut value = parse(r#"{"a": [1, 2]}"#).unwrap();
+        value["a"][0] = JsonObject::Bool(true);
+        value["b"]["c"] = JsonObject::String("d".to_string());
+        value["e"] = JsonObject::Null;
+        assert_eq!(value, parse(r#"{"a": [true, 2], "b": {"c": "d"}, "e": null}"#).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_index_mut_out_of_bounds() {
+        let mut value = parse("[1]").unwrap();
+        value[1] = JsonObject::Null;
+    }
+
+    #[test]
+    #[should_panic(expected = "A JsonObject is not object")]
+    fn test_index_mut_not_object() {
+        let mut value = parse("[1]").unwrap();
+        value["a"] = JsonObject::Null;
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+};
+
+use crate::{
+    error::{EofError, IoError, JsonPretError, ParserError},
+    lexer::{is_non_finite, Lexer, SpannedToken, Token},
+    map::Map,
+    parser::MAX_DEPTH,
+    position::Position,
+    serializer::{IoWriter, Serializer},
+    FormatOptions, JsonNumber, JsonObject,
+};
+
+/// コメントを保持した JSON の値
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// String, Number, Bool, Null のいずれか
+    Scalar(JsonObject),
+    Array(Container),
+    Object(Container),
+}
+
+/// コメントを保持した Array または Object
+#[derive(Debug, PartialEq)]
+struct Container {
+    /// 開き括弧と同じ行のコメント
+    opening: Vec<String>,
+    members: Vec<Member>,
+    /// 最後の要素より後の行にある、閉じ括弧の前のコメント
+    dangling: Vec<String>,
+}
+
+/// Array の要素または Object のメンバーと、それに付いたコメント
+#[derive(Debug, PartialEq)]
+struct Member {
+    /// 前の行にあるコメント
+    leading: Vec<String>,
+    /// Object のキー
+    key: Option<String>,
+    /// キーと値の間のコメント
+    inner: Vec<String>,
+    value: Node,
+    /// 値と同じ行にあるコメント
+    trailing: Vec<String>,
+}
+
+impl Node {
+    /// コメントを含まない場合は `JsonObject` にする。重複したキーがある場合も `None` を返す。
+    fn to_value(&self) -> Option<JsonObject> {
+        let (container, object): (&Container, bool) = match self {
+            Node::Scalar(value) => return Some(value.clone()),
+            Node::Array(c) => (c, false),
+            Node::Object(c) => (c, true),
+        };
+        if !container.opening.is_empty() || !container.dangling.is_empty() {
+            return None;
+        }
+        let mut values: Vec<JsonObject> = vec![];
+        let mut map: Map = Map::new();
+        for member in &container.members {
+            if !member.leading.is_empty() || !member.inner.is_empty() || !member.trailing.is_empty() {
+                return None;
+            }
+            let value: JsonObject = member.value.to_value()?;
+            match &member.key {
+                Some(key) if object => {
+                    if map.insert(key.clone(), value).is_some() {
+                        return None;
+                    }
+                }
+                _ => values.push(value),
+            }
+        }
+        Some(if object { JsonObject::Object(map) } else { JsonObject::Array(values) })
+    }
+}
+
+/// コメントを含む `Token` を読み込み、`Node` の木を組み立てる
+struct JsoncParser<R> {
+    lexer: Lexer<R>,
+    /// 先読みした `Token`
+    peeked: Option<SpannedToken>,
+    /// 直前に読んだ `Token` の終了行
+    line: usize,
+    /// 現在の Array と Object の入れ子の深さ
+    depth: usize,
+}
+
+impl<R: BufRead> JsoncParser<R> {
+    /// ルートの値と、その前後のコメントを読み込む。
+    /// ルートの値より後の行にあるコメントも返す。
+    fn parse_root(&mut self) -> Result<(Member, Vec<String>), JsonPretError> {
+        let (mut leading, following): (Vec<String>, Vec<String>) = self.comments()?;
+        leading.extend(following);
+        let value: Node = self.parse_value()?;
+        let (trailing, dangling): (Vec<String>, Vec<String>) = self.comments()?;
+        if let Some(t) = self.peek()? {
+            let msg: String = format!("an unexpected token '{}' after the root value", t.token);
+            let position: Position = t.span.start;
+            return Err(self.error(&msg, position));
+        }
+        let root: Member = Member { leading, key: None, inner: vec![], value, trailing };
+        Ok((root, dangling))
+    }
+
+    fn parse_value(&mut self) -> Result<Node, JsonPretError> {
+        let token: SpannedToken = self.next()?;
+        let value: JsonObject = match token.token {
+            Token::LeftBracket => return self.nested(false, token.span.start).map(Node::Array),
+            Token::LeftBrace => return self.nested(true, token.span.start).map(Node::Object),
+            Token::Null => JsonObject::Null,
+            Token::Bool(b) => JsonObject::Bool(b),
+            Token::Number(n) => JsonObject::Number(n),
+            Token::String(s) => JsonObject::String(s),
+            Token::Identifier(name) if is_non_finite(&name) => {
+                JsonObject::Number(JsonNumber::from_non_finite(&name))
+            }
+            t => return Err(self.error(
+                &format!(
+                    "token must start {{ or [ or String or Number or Bool or Null, but start '{}'",
+                    t
+                ),
+                token.span.start,
+            )),
+        };
+        Ok(Node::Scalar(value))
+    }
+
+    /// 入れ子の深さを数えながら、開き括弧の後の Array または Object を読み込む
+    fn nested(&mut self, object: bool, position: Position) -> Result<Container, JsonPretError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(
+                &format!("the nesting depth exceeds the limit of {}", MAX_DEPTH),
+                position,
+            ));
+        }
+        self.depth += 1;
+        let result = self.parse_container(object);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_container(&mut self, object: bool) -> Result<Container, JsonPretError> {
+        let close: Token = if object { Token::RightBrace } else { Token::RightBracket };
+        let mut opening: Vec<String> = vec![];
+        let mut members: Vec<Member> = vec![];
+
+        loop {
+            // 直前の , または開き括弧と同じ行のコメント
+            let (same_line, leading): (Vec<String>, Vec<String>) = self.comments()?;
+            match members.last_mut() {
+                Some(member) => member.trailing.extend(same_line),
+                None => opening.extend(same_line),
+            }
+
+            // 空の Array と Object、末尾の ,
+            if self.peek()?.map(|t| &t.token) == Some(&close) {
+                self.next()?;
+                return Ok(Container { opening, members, dangling: leading });
+            }
+
+            let mut inner: Vec<String> = vec![];
+            let key: Option<String> = if object {
+                let token: SpannedToken = self.next()?;
+                let key: String = match token.token.into_key(true) {
+                    Some(key) => key,
+                    None => return Err(self.error(
+                        "a pair 'String(key)' and ':' is expected.",
+                        token.span.start,
+                    )),
+                };
+                self.extend_comments(&mut inner)?;
+                let colon: SpannedToken = self.next()?;
+                if colon.token != Token::Colon {
+                    return Err(self.error("a pair 'String(key)' and ':' is expected.", colon.span.start));
+                }
+                self.extend_comments(&mut inner)?;
+                Some(key)
+            } else {
+                None
+            };
+
+            let value: Node = self.parse_value()?;
+            let (trailing, following): (Vec<String>, Vec<String>) = self.comments()?;
+            let mut member: Member = Member { leading, key, inner, value, trailing };
+
+            let token: SpannedToken = self.next()?;
+            match token.token {
+                Token::Comma => {
+                    // , の前の行にあるコメントは値の後ろに置く
+                    member.trailing.extend(following);
+                    members.push(member);
+                }
+                t if t == close => {
+                    members.push(member);
+                    return Ok(Container { opening, members, dangling: following });
+                }
+                t if object => return Err(self.error(
+                    &format!("{{ or , is expected, but {} is inputed", t),
+                    token.span.start,
+                )),
+                t => return Err(self.error(
+                    &format!("a ']' or ',' is expected, but '{}' is inputed", t),
+                    token.span.start,
+                )),
+            }
+        }
+    }
+
+    /// 続くコメントを読み込み、直前の `Token` と同じ行から続くものと、それより後の行のものに分けて返す
+    fn comments(&mut self) -> Result<(Vec<String>, Vec<String>), JsonPretError> {
+        let mut same_line: Vec<String> = vec![];
+        let mut following: Vec<String> = vec![];
+        while let Some(SpannedToken { token: Token::Comment(_), span }) = self.peek()? {
+            let on_same_line: bool = span.start.line == self.line && following.is_empty();
+            if let Token::Comment(text) = self.next()?.token {
+                if on_same_line {
+                    same_line.push(text);
+                } else {
+                    following.push(text);
+                }
+            }
+        }
+        Ok((same_line, following))
+    }
+
+    /// 続くコメントを全て `comments` に追加する
+    fn extend_comments(&mut self, comments: &mut Vec<String>) -> Result<(), JsonPretError> {
+        let (same_line, following): (Vec<String>, Vec<String>) = self.comments()?;
+        comments.extend(same_line);
+        comments.extend(following);
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<&SpannedToken>, JsonPretError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.read_token_with_comments()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    /// 次の `Token` を返す。入力の終わりでは EofError を返す。
+    fn next(&mut self) -> Result<SpannedToken, JsonPretError> {
+        self.peek()?;
+        match self.peeked.take() {
+            Some(t) => {
+                self.line = t.span.end.line;
+                Ok(t)
+            }
+            None => {
+                let position: Position = self.lexer.position();
+                Err(self.lexer.attach_source(JsonPretError::EofError(EofError::new(
+                    "unexpected end of input",
+                    position,
+                ))))
+            }
+        }
+    }
+
+    /// `position` で ParserError を作る
+    fn error(&mut self, msg: &str, position: Position) -> JsonPretError {
+        self.lexer.attach_source(JsonPretError::ParserError(ParserError::new(msg, position)))
+    }
+}
+
+/// `Node` の木をコメントと一緒に書き込む
+struct JsoncWriter<W: fmt::Write> {
+    serializer: Serializer<W>,
+    compact: bool,
+}
+
+impl<W: fmt::Write> JsoncWriter<W> {
+    fn write_root(&mut self, root: &Member, dangling: &[String]) -> fmt::Result {
+        for comment in &root.leading {
+            self.serializer.write_comment(comment)?;
+            self.serializer.write_line_break(0)?;
+        }
+        let trailing: usize = self.comments_width(&root.trailing);
+        self.write_node(&root.value, 0, 0, trailing)?;
+        self.write_trailing(&root.trailing)?;
+        for comment in dangling {
+            self.serializer.write_line_break(0)?;
+            self.serializer.write_comment(comment)?;
+        }
+        self.serializer.write_trailing_newline()
+    }
+
+    /// `column` 桁目から始まり、同じ行に `trailing` 文字が続く `node` を書き込む
+    fn write_node(&mut self, node: &Node, depth: usize, column: usize, trailing: usize) -> fmt::Result {
+        let (container, open, close): (&Container, char, char) = match node {
+            Node::Scalar(value) => return self.serializer.write_value(value, depth),
+            Node::Array(c) => (c, '[', ']'),
+            Node::Object(c) => (c, '{', '}'),
+        };
+        // コメントの無い Array と Object は、`max_width` に収まれば1行に書く
+        if self.serializer.flat_limit(column).is_some() {
+            if let Some(value) = node.to_value() {
+                return self.serializer.write_value_at(&value, depth, column, trailing);
+            }
+        }
+
+        self.serializer.write_punct(open)?;
+        self.write_trailing(&container.opening)?;
+        // コメントの無い空の Array と Object
+        if container.members.is_empty() && container.opening.is_empty() && container.dangling.is_empty() {
+            return self.serializer.write_punct(close);
+        }
+
+        for (i, member) in container.members.iter().enumerate() {
+            self.serializer.write_line_break(depth + 1)?;
+            for comment in &member.leading {
+                self.serializer.write_comment(comment)?;
+                self.serializer.write_line_break(depth + 1)?;
+            }
+            let mut column: usize = self.serializer.indent_width(depth + 1);
+            if let Some(key) = &member.key {
+                self.serializer.write_key(key)?;
+                column += self.serializer.measure(usize::MAX, |s| s.write_key(key)).unwrap_or(0);
+            }
+            for comment in &member.inner {
+                self.serializer.write_comment(comment)?;
+                if comment.starts_with("//") {
+                    self.serializer.write_line_break(depth + 1)?;
+                    column = self.serializer.indent_width(depth + 1);
+                } else {
+                    column += self.comments_width(std::slice::from_ref(comment));
+                    if !self.compact {
+                        self.serializer.write_punct(' ')?;
+                    }
+                }
+            }
+            let comma: usize = if i + 1 < container.members.len() { 1 } else { 0 };
+            let trailing: usize = comma + self.comments_width(&member.trailing);
+            self.write_node(&member.value, depth + 1, column, trailing)?;
+            if i + 1 < container.members.len() {
+                self.serializer.write_punct(',')?;
+            }
+            self.write_trailing(&member.trailing)?;
+        }
+        for comment in &container.dangling {
+            self.serializer.write_line_break(depth + 1)?;
+            self.serializer.write_comment(comment)?;
+        }
+        self.serializer.write_line_break(depth)?;
+        self.serializer.write_punct(close)
+    }
+
+    /// `write_trailing` で書き込むコメントの幅
+    fn comments_width(&self, comments: &[String]) -> usize {
+        let space: usize = if self.compact { 0 } else { 1 };
+        comments.iter().map(|c| c.chars().count() + space).sum()
+    }
+
+    /// 値と同じ行に続くコメントを書き込む
+    fn write_trailing(&mut self, comments: &[String]) -> fmt::Result {
+        for comment in comments {
+            if !self.compact {
+                self.serializer.write_punct(' ')?;
+            }
+            self.serializer.write_comment(comment)?;
+        }
+        Ok(())
+    }
+}
+
+/// `reader` から読み込んだ JSONC (コメント付きの JSON)を、コメントを残したまま
+/// `options` に従って整形し、`writer` に書き込む。
+///
+/// コメントは、前の行にあれば次の要素の前に、値と同じ行にあればその値の後ろに置かれる。
+/// JSON5 の文法(末尾の `,`、`'` で囲んだ文字列、引用符の無いキーなど)も受け付け、
+/// 値は JSON の表記に直して出力する。入力全体を読み込んでから書き込むので、
+/// 入力が正しくない場合は何も書き込まずにエラーを返す。
+///
+/// ```
+/// use json_prettier::{format_jsonc, FormatOptions};
+///
+/// let input = "{\n  // コメント\n  a: 1, /* 1 */\n}";
+/// let mut output: Vec<u8> = vec![];
+/// format_jsonc(input.as_bytes(), &mut output, &FormatOptions::default()).unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "{\n   // コメント\n   \"a\": 1 /* 1 */\n}");
+/// ```
+pub fn format_jsonc<R: BufRead, W: io::Write>(
+    reader: R,
+    writer: W,
+    options: &FormatOptions,
+) -> Result<(), JsonPretError> {
+    let mut parser: JsoncParser<R> = JsoncParser {
+        lexer: Lexer::from_reader(reader).json5(true),
+        peeked: None,
+        line: 1,
+        depth: 0,
+    };
+    let (root, dangling): (Member, Vec<String>) = parser.parse_root()?;
+
+    let mut writer: JsoncWriter<IoWriter<W>> = JsoncWriter {
+        serializer: Serializer::with_options(IoWriter::new(writer), options.clone()),
+        compact: options.compact,
+    };
+    match writer.write_root(&root, &dangling) {
+        Ok(()) => Ok(()),
+        Err(_) => {
+            let e: io::Error = writer.serializer.get_mut().take_error();
+            Err(JsonPretError::IoError(IoError::from(e)))
+        }
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{jsonc::format_jsonc, FormatOptions, JsonPretError};
+
+    fn format(input: &str, options: &FormatOptions) -> Result<String, JsonPretError> {
+        let mut output: Vec<u8> = vec![];
+        format_jsonc(input.as_bytes(), &mut output, options)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_format_jsonc() {
+        let input = r#"// tsconfig
+{
+  /* コンパイラの設定 */
+  "compilerOptions": { // 開き括弧の後
+    "target": "es2020", // 出力
+    "strict": true /* 厳密 */ ,
+    'paths': {},
+    lib: [
+      "dom",
+      // "webworker",
+    ],
+  },
+  "include": /* 対象 */ ["src"]
+  // 最後
+} // ルートの後
+"#;
+        let expect = r#"// tsconfig
+{
+   /* コンパイラの設定 */
+   "compilerOptions": { // 開き括弧の後
+      "target": "es2020", // 出力
+      "strict": true, /* 厳密 */
+      "paths": {},
+      "lib": [
+         "dom"
+         // "webworker",
+      ]
+   },
+   "include": /* 対象 */ [
+      "src"
+   ]
+   // 最後
+} // ルートの後"#;
+        assert_eq!(format(input, &FormatOptions::default()).unwrap(), expect);
+
+        // 整形した結果をもう一度整形しても変わらない
+        assert_eq!(format(expect, &FormatOptions::default()).unwrap(), expect);
+    }
+
+    #[test]
+    fn test_format_jsonc_compact() {
+        let input = "[ // a\n 1, /* b */ 2 ]";
+        assert_eq!(format(input, &FormatOptions::compact()).unwrap(), "[// a\n1,/* b */2]");
+        assert_eq!(format("{ /* x */ }", &FormatOptions::default()).unwrap(), "{ /* x */\n}");
+        assert_eq!(format("{}", &FormatOptions::default()).unwrap(), "{}");
+        // コメントの無い Array と Object だけを1行にする
+        let options = FormatOptions { max_width: Some(25), ..FormatOptions::default() };
+        assert_eq!(
+            format("{\"a\":[1,2],\"b\":{\"c\":3}, // b\n\"d\": [ /* d */ 4]}", &options).unwrap(),
+            "{\n   \"a\": [1, 2],\n   \"b\": {\"c\": 3}, // b\n   \"d\": [ /* d */\n      4\n   ]\n}"
+        );
+        assert_eq!(format("{\"a\": [1, 2, 3, 4, 5, 6]}", &options).unwrap(), "{\"a\": [1, 2, 3, 4, 5, 6]}");
+        // 後ろに続く , とコメントも幅に含める
+        assert_eq!(
+            format("{\"a\": [1, 2, 3, 4, 5], \"b\": [1, 2, 3, 4, 5] // b\n}", &options).unwrap(),
+            "{\n   \"a\": [1, 2, 3, 4, 5],\n   \"b\": [\n      1,\n      2,\n      3,\n      4,\n      5\n   ] // b\n}"
+        );
+        assert_eq!(format("{\"a\": 1, \"a\": 2}", &options).unwrap(), "{\n   \"a\": 1,\n   \"a\": 2\n}");
+
+        // JSON で表せない数値は null にする
+        assert_eq!(format("[+Infinity, NaN]", &FormatOptions::compact()).unwrap(), "[null,null]");
+        let options = FormatOptions { non_finite: true, ..FormatOptions::compact() };
+        assert_eq!(format("[+Infinity, NaN]", &options).unwrap(), "[Infinity,NaN]");
+    }
+
+    #[test]
+    fn test_format_jsonc_errors() {
+        for input in ["", "// c", "[1 2]", "{a 1}", "[1,,]", "{\"a\": 1} 2", "/* c"] {
+            assert!(format(input, &FormatOptions::default()).is_err(), "{}", input);
+        }
+        let err = format("{\n  // c\n  a: 1 2\n}", &FormatOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ParserError: { or , is expected, but number 2 is inputed at line 3, column 8\n3 |   a: 1 2\n  |        ^"
+        );
+    }
+}
@@ -0,0 +1,1261 @@
+//! RFC 9535 の JSONPath で `JsonObject` から値を取り出す
+//!
+//! 子孫(`..`)、ワイルドカード(`*`)、添字、スライス(`[start:end:step]`)、
+//! フィルタ(`[?...]`)と、関数 `length()`, `count()`, `match()`, `search()`, `value()` に対応している。
+//!
+//! ```
+//! use json_prettier::{jsonpath::JsonPath, parse};
+//!
+//! let value = parse(r#"{"store": {"book": [
+//!     {"title": "a", "price": 8}, {"title": "b", "price": 12}
+//! ]}}"#).unwrap();
+//! let path = JsonPath::parse("$..book[?@.price < 10].title").unwrap();
+//! let nodes = path.query(&value);
+//! assert_eq!(nodes.len(), 1);
+//! assert_eq!(nodes[0].path.to_string(), "$['store']['book'][0]['title']");
+//! assert_eq!(nodes[0].value.as_str(), Some("a"));
+//! ```
+
+use std::{borrow::Cow, cmp::Ordering, fmt, str::FromStr};
+
+use crate::{
+    error::{JsonPretError, QueryError},
+    pointer::JsonPointer,
+    regex::{Regex, RegexError},
+    JsonNumber, JsonObject,
+};
+
+/// I-JSON で正確に表せる整数の最大値 (2^53 - 1)
+const MAX_INT: i64 = 9007199254740991;
+
+/// フィルタの括弧や論理演算子の入れ子の深さの上限。
+/// 1段ごとに再帰下降の関数を何段も呼ぶため、JSON の入れ子の上限より小さくしている。
+const MAX_DEPTH: usize = 64;
+
+/// パース済みの JSONPath
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// JSONPath をパースする。関数の引数や比較の型が正しくない場合もエラーを返す。
+    pub fn parse(path: &str) -> Result<JsonPath, JsonPretError> {
+        let mut parser: PathParser = PathParser {
+            chars: path.chars().collect(),
+            index: 0,
+            depth: 0,
+        };
+        if !parser.eat('$') {
+            return Err(parser.error("a JSONPath must start with '$'"));
+        }
+        let segments: Vec<Segment> = parser.parse_segments()?;
+        if let Some(c) = parser.peek() {
+            return Err(parser.error(&format!("an unexpected char {}", c)));
+        }
+        Ok(JsonPath { segments })
+    }
+
+    /// `root` に JSONPath を適用し、選択されたノードを順に返す
+    pub fn query<'a>(&self, root: &'a JsonObject) -> Vec<Node<'a>> {
+        let nodes: Vec<(NormalizedPath, &'a JsonObject)> = select(
+            &self.segments,
+            root,
+            vec![(NormalizedPath::default(), root)],
+            &|path: &NormalizedPath, step: Step<'_>| path.join(step),
+        );
+        nodes
+            .into_iter()
+            .map(|(path, value)| Node { path, value })
+            .collect()
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = JsonPretError;
+
+    fn from_str(s: &str) -> Result<JsonPath, JsonPretError> {
+        JsonPath::parse(s)
+    }
+}
+
+/// JSONPath で選択された値と、その場所
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<'a> {
+    pub path: NormalizedPath,
+    pub value: &'a JsonObject,
+}
+
+/// ルートからの場所を表す正規化パス。`$['store']['book'][0]` のように表示する。
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NormalizedPath {
+    elements: Vec<PathElement>,
+}
+
+/// 正規化パスの要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathElement {
+    /// Object のキー
+    Name(String),
+    /// Array の添字
+    Index(usize),
+}
+
+impl NormalizedPath {
+    pub fn elements(&self) -> &[PathElement] {
+        &self.elements
+    }
+
+    /// 同じ場所を指す JSON Pointer に変換する
+    pub fn to_pointer(&self) -> JsonPointer {
+        JsonPointer::from_tokens(self.elements.iter().map(|e| match e {
+            PathElement::Name(name) => name.clone(),
+            PathElement::Index(i) => i.to_string(),
+        }))
+    }
+
+    fn join(&self, step: Step<'_>) -> NormalizedPath {
+        let mut elements: Vec<PathElement> = self.elements.clone();
+        elements.push(match step {
+            Step::Name(name) => PathElement::Name(name.to_string()),
+            Step::Index(i) => PathElement::Index(i),
+        });
+        NormalizedPath { elements }
+    }
+}
+
+impl fmt::Display for NormalizedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for element in &self.elements {
+            match element {
+                PathElement::Index(i) => write!(f, "[{}]", i)?,
+                PathElement::Name(name) => {
+                    f.write_str("['")?;
+                    for c in name.chars() {
+                        match c {
+                            '\u{08}' => f.write_str("\\b")?,
+                            '\u{0C}' => f.write_str("\\f")?,
+                            '\n' => f.write_str("\\n")?,
+                            '\r' => f.write_str("\\r")?,
+                            '\t' => f.write_str("\\t")?,
+                            '\'' => f.write_str("\\'")?,
+                            '\\' => f.write_str("\\\\")?,
+                            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+                            c => write!(f, "{}", c)?,
+                        }
+                    }
+                    f.write_str("']")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// --- 構文木 ---
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    /// `..` の場合は `true`
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expr),
+}
+
+/// フィルタの論理式
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(CompareOp, Operand, Operand),
+    /// クエリが 1 つ以上のノードを選択するかどうか
+    Exists(FilterQuery),
+    /// 真偽値を返す関数
+    Call(FunctionCall),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// フィルタの中のクエリ。`@` から始まる場合は `relative` が `true`。
+#[derive(Debug, Clone, PartialEq)]
+struct FilterQuery {
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+impl FilterQuery {
+    /// 最大 1 つのノードしか選択しないクエリかどうか
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|s| {
+            !s.descendant
+                && matches!(s.selectors.as_slice(), [Selector::Name(_) | Selector::Index(_)])
+        })
+    }
+}
+
+/// 比較と関数の引数
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Literal(JsonObject),
+    Query(FilterQuery),
+    Call(FunctionCall),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FunctionCall {
+    function: Function,
+    args: Vec<Operand>,
+    /// `match()` と `search()` の正規表現がリテラルの場合に、あらかじめ読み込んだ結果
+    regex: Option<Result<Regex, RegexError>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Length,
+    Count,
+    Match,
+    Search,
+    Value,
+}
+
+/// RFC 9535 の関数の引数と戻り値の型
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Value,
+    Logical,
+    Nodes,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "length" => Some(Function::Length),
+            "count" => Some(Function::Count),
+            "match" => Some(Function::Match),
+            "search" => Some(Function::Search),
+            "value" => Some(Function::Value),
+            _ => None,
+        }
+    }
+
+    fn params(&self) -> &'static [Type] {
+        match self {
+            Function::Length => &[Type::Value],
+            Function::Count | Function::Value => &[Type::Nodes],
+            Function::Match | Function::Search => &[Type::Value, Type::Value],
+        }
+    }
+
+    fn result(&self) -> Type {
+        match self {
+            Function::Length | Function::Count | Function::Value => Type::Value,
+            Function::Match | Function::Search => Type::Logical,
+        }
+    }
+}
+
+impl Operand {
+    /// ValueType として比較や関数の引数に使えるかどうか
+    fn is_value(&self) -> bool {
+        match self {
+            Operand::Literal(_) => true,
+            Operand::Query(query) => query.is_singular(),
+            Operand::Call(call) => call.function.result() == Type::Value,
+        }
+    }
+}
+
+// --- 構文解析 ---
+
+struct PathParser {
+    chars: Vec<char>,
+    index: usize,
+    /// フィルタの括弧と論理演算子の入れ子の深さ
+    depth: usize,
+}
+
+impl PathParser {
+    /// 0 個以上のセグメント。セグメントの前の空白は読み飛ばす。
+    fn parse_segments(&mut self) -> Result<Vec<Segment>, JsonPretError> {
+        let mut segments: Vec<Segment> = vec![];
+        loop {
+            let start: usize = self.index;
+            self.skip_blank();
+            match self.peek() {
+                Some('.') | Some('[') => segments.push(self.parse_segment()?),
+                _ => {
+                    // 末尾の空白はセグメントの一部ではない
+                    self.index = start;
+                    return Ok(segments);
+                }
+            }
+        }
+    }
+
+    fn parse_segment(&mut self) -> Result<Segment, JsonPretError> {
+        if self.eat('[') {
+            let selectors: Vec<Selector> = self.parse_bracketed()?;
+            return Ok(Segment { descendant: false, selectors });
+        }
+        self.expect('.')?;
+        let descendant: bool = self.eat('.');
+        let selector: Selector = if descendant && self.eat('[') {
+            let selectors: Vec<Selector> = self.parse_bracketed()?;
+            return Ok(Segment { descendant, selectors });
+        } else if self.eat('*') {
+            Selector::Wildcard
+        } else {
+            Selector::Name(self.parse_member_name()?)
+        };
+        Ok(Segment { descendant, selectors: vec![selector] })
+    }
+
+    /// `.` の後のキー名
+    fn parse_member_name(&mut self) -> Result<String, JsonPretError> {
+        let is_first = |c: char| c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+        match self.peek() {
+            Some(c) if is_first(c) => {}
+            _ => return Err(self.unexpected()),
+        }
+        let mut name: String = String::new();
+        while let Some(c) = self.peek().filter(|&c| is_first(c) || c.is_ascii_digit()) {
+            name.push(c);
+            self.index += 1;
+        }
+        Ok(name)
+    }
+
+    /// `[` の後の `selector, ...]` を読む
+    fn parse_bracketed(&mut self) -> Result<Vec<Selector>, JsonPretError> {
+        let mut selectors: Vec<Selector> = vec![];
+        loop {
+            self.skip_blank();
+            selectors.push(self.parse_selector()?);
+            self.skip_blank();
+            if self.eat(']') {
+                return Ok(selectors);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_selector(&mut self) -> Result<Selector, JsonPretError> {
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(Selector::Name(self.parse_string()?)),
+            Some('*') => {
+                self.index += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.index += 1;
+                self.skip_blank();
+                Ok(Selector::Filter(self.parse_or()?))
+            }
+            Some('-') | Some('0'..='9') | Some(':') => {
+                let start: Option<i64> = self.parse_optional_int()?;
+                let before_colon: usize = self.index;
+                self.skip_blank();
+                if !self.eat(':') {
+                    self.index = before_colon;
+                    return match start {
+                        Some(i) => Ok(Selector::Index(i)),
+                        None => Err(self.unexpected()),
+                    };
+                }
+                self.skip_blank();
+                let end: Option<i64> = self.parse_optional_int()?;
+                self.skip_blank();
+                let step: Option<i64> = if self.eat(':') {
+                    self.skip_blank();
+                    self.parse_optional_int()?
+                } else {
+                    None
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_optional_int(&mut self) -> Result<Option<i64>, JsonPretError> {
+        match self.peek() {
+            Some('-') | Some('0'..='9') => Ok(Some(self.parse_int()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// 添字とスライスの整数。`-0` と先頭の 0、I-JSON の範囲外の整数はエラー。
+    fn parse_int(&mut self) -> Result<i64, JsonPretError> {
+        let start: usize = self.index;
+        self.eat('-');
+        let digits: String = self.take_digits();
+        let negative: bool = self.chars[start] == '-';
+        if digits.is_empty() || (digits.starts_with('0') && (digits.len() > 1 || negative)) {
+            self.index = start;
+            return Err(self.error("an invalid integer"));
+        }
+        match digits.parse::<i64>() {
+            Ok(n) if n <= MAX_INT => Ok(if negative { -n } else { n }),
+            _ => {
+                self.index = start;
+                Err(self.error("an integer out of range"))
+            }
+        }
+    }
+
+    fn take_digits(&mut self) -> String {
+        let mut digits: String = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.index += 1;
+        }
+        digits
+    }
+
+    /// ' または " で囲まれた文字列リテラル
+    fn parse_string(&mut self) -> Result<String, JsonPretError> {
+        let quote: char = match self.next() {
+            Some(c) => c,
+            None => return Err(self.unexpected()),
+        };
+        let mut s: String = String::new();
+        loop {
+            let c: char = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error("an unterminated string literal")),
+            };
+            match c {
+                c if c == quote => return Ok(s),
+                '\\' => {
+                    let escaped: char = match self.next() {
+                        Some('b') => '\u{08}',
+                        Some('f') => '\u{0C}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('/') => '/',
+                        Some('\\') => '\\',
+                        Some(c) if c == quote => c,
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => {
+                            self.index -= 1;
+                            return Err(self.error("an invalid escape sequence"));
+                        }
+                    };
+                    s.push(escaped);
+                }
+                c if c < ' ' => {
+                    self.index -= 1;
+                    return Err(self.error("an unescaped control character"));
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// `\u` の後の 4 桁の 16 進数。サロゲートペアは 2 つ続けて読む。
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonPretError> {
+        let high: u32 = self.parse_hex4()?;
+        let code: u32 = match high {
+            0xD800..=0xDBFF => {
+                if !(self.eat('\\') && self.eat('u')) {
+                    return Err(self.error("a lone surrogate"));
+                }
+                match self.parse_hex4()? {
+                    low @ 0xDC00..=0xDFFF => 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+                    _ => return Err(self.error("a lone surrogate")),
+                }
+            }
+            0xDC00..=0xDFFF => return Err(self.error("a lone surrogate")),
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| self.error("an invalid unicode escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonPretError> {
+        let hex: String = self.chars.iter().skip(self.index).take(4).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(n) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                self.index += 4;
+                Ok(n)
+            }
+            _ => Err(self.error("an invalid unicode escape")),
+        }
+    }
+
+    /// `a || b`
+    fn parse_or(&mut self) -> Result<Expr, JsonPretError> {
+        let depth: usize = self.depth;
+        self.enter()?;
+        let mut lhs: Expr = self.parse_and()?;
+        while self.eat_operator("||") {
+            self.enter()?;
+            let rhs: Expr = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    /// `a && b`
+    fn parse_and(&mut self) -> Result<Expr, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut lhs: Expr = self.parse_basic()?;
+        while self.eat_operator("&&") {
+            self.enter()?;
+            let rhs: Expr = self.parse_basic()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    /// 括弧で囲まれた式、比較、存在の確認、真偽値を返す関数
+    fn parse_basic(&mut self) -> Result<Expr, JsonPretError> {
+        if self.eat('!') {
+            self.skip_blank();
+            let expr: Expr = if self.eat('(') {
+                self.parse_paren()?
+            } else {
+                let start: usize = self.index;
+                let operand: Operand = self.parse_operand()?;
+                self.test_expr(operand, start)?
+            };
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        if self.eat('(') {
+            return self.parse_paren();
+        }
+
+        let start: usize = self.index;
+        let lhs: Operand = self.parse_operand()?;
+        let before_op: usize = self.index;
+        self.skip_blank();
+        let op: CompareOp = match self.parse_compare_op() {
+            Some(op) => op,
+            None => {
+                self.index = before_op;
+                return self.test_expr(lhs, start);
+            }
+        };
+        self.skip_blank();
+        let rhs_start: usize = self.index;
+        let rhs: Operand = self.parse_operand()?;
+        for (operand, at) in [(&lhs, start), (&rhs, rhs_start)] {
+            if !operand.is_value() {
+                self.index = at;
+                return Err(self.error("only literals, singular queries and value functions can be compared"));
+            }
+        }
+        Ok(Expr::Compare(op, lhs, rhs))
+    }
+
+    /// `(` の後の `expr)` を読む
+    fn parse_paren(&mut self) -> Result<Expr, JsonPretError> {
+        self.skip_blank();
+        let expr: Expr = self.parse_or()?;
+        self.skip_blank();
+        self.expect(')')?;
+        Ok(expr)
+    }
+
+    /// 比較せずに使う `operand` を論理式にする
+    fn test_expr(&mut self, operand: Operand, start: usize) -> Result<Expr, JsonPretError> {
+        match operand {
+            Operand::Query(query) => Ok(Expr::Exists(query)),
+            Operand::Call(call) if call.function.result() == Type::Logical => Ok(Expr::Call(call)),
+            _ => {
+                self.index = start;
+                Err(self.error("a literal or a value function must be compared"))
+            }
+        }
+    }
+
+    fn parse_compare_op(&mut self) -> Option<CompareOp> {
+        let ops: [(&str, CompareOp); 6] = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ];
+        ops.into_iter().find(|(s, _)| self.eat_str(s)).map(|(_, op)| op)
+    }
+
+    /// リテラル、クエリ、関数呼び出し
+    fn parse_operand(&mut self) -> Result<Operand, JsonPretError> {
+        match self.peek() {
+            Some(c @ ('@' | '$')) => {
+                self.index += 1;
+                let segments: Vec<Segment> = self.parse_segments()?;
+                Ok(Operand::Query(FilterQuery { relative: c == '@', segments }))
+            }
+            Some('\'') | Some('"') => Ok(Operand::Literal(JsonObject::String(self.parse_string()?))),
+            Some('-') | Some('0'..='9') => self.parse_number(),
+            Some('a'..='z') => {
+                let start: usize = self.index;
+                let mut name: String = String::new();
+                while let Some(c) = self
+                    .peek()
+                    .filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_')
+                {
+                    name.push(c);
+                    self.index += 1;
+                }
+                if self.eat('(') {
+                    return self.parse_call(&name, start);
+                }
+                match name.as_str() {
+                    "true" => Ok(Operand::Literal(JsonObject::Bool(true))),
+                    "false" => Ok(Operand::Literal(JsonObject::Bool(false))),
+                    "null" => Ok(Operand::Literal(JsonObject::Null)),
+                    _ => {
+                        self.index = start;
+                        Err(self.unexpected())
+                    }
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Operand, JsonPretError> {
+        let start: usize = self.index;
+        let mut lexeme: String = String::new();
+        if self.eat('-') {
+            lexeme.push('-');
+        }
+        lexeme.push_str(&self.take_digits());
+        if self.peek() == Some('.') {
+            self.index += 1;
+            lexeme.push('.');
+            lexeme.push_str(&self.take_digits());
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            self.index += 1;
+            lexeme.push(e);
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                self.index += 1;
+                lexeme.push(sign);
+            }
+            lexeme.push_str(&self.take_digits());
+        }
+        match JsonNumber::from_lexeme(&lexeme) {
+            Some(n) => Ok(Operand::Literal(JsonObject::Number(n))),
+            None => {
+                self.index = start;
+                Err(self.error("an invalid number"))
+            }
+        }
+    }
+
+    /// `name(` の後の引数を読み、引数の数と型を確認する
+    fn parse_call(&mut self, name: &str, start: usize) -> Result<Operand, JsonPretError> {
+        let function: Function = match Function::from_name(name) {
+            Some(f) => f,
+            None => {
+                self.index = start;
+                return Err(self.error(&format!("an unknown function {}()", name)));
+            }
+        };
+        // 引数の中でも関数を呼べるため、呼び出しも入れ子の深さに数える
+        let depth: usize = self.depth;
+        self.enter()?;
+
+        let mut args: Vec<(Operand, usize)> = vec![];
+        self.skip_blank();
+        if !self.eat(')') {
+            loop {
+                let at: usize = self.index;
+                args.push((self.parse_operand()?, at));
+                self.skip_blank();
+                if self.eat(')') {
+                    break;
+                }
+                self.expect(',')?;
+                self.skip_blank();
+            }
+        }
+
+        let params: &[Type] = function.params();
+        if args.len() != params.len() {
+            self.index = start;
+            return Err(self.error(&format!(
+                "{}() takes {} argument(s) but {} given",
+                name,
+                params.len(),
+                args.len()
+            )));
+        }
+        for ((arg, at), param) in args.iter().zip(params) {
+            let ok: bool = match param {
+                Type::Value => arg.is_value(),
+                Type::Nodes => matches!(arg, Operand::Query(_)),
+                Type::Logical => false,
+            };
+            if !ok {
+                self.index = *at;
+                return Err(self.error(&format!("an argument of {}() has a wrong type", name)));
+            }
+        }
+        // リテラルの正規表現は、ノードごとに読み込み直さないように1度だけ読み込む
+        let mut regex: Option<Result<Regex, RegexError>> = None;
+        if let (Function::Match | Function::Search, [_, (Operand::Literal(JsonObject::String(pattern)), at)]) =
+            (function, args.as_slice())
+        {
+            let parsed: Result<Regex, RegexError> = Regex::parse(pattern);
+            // 対応していない正規表現は、評価すると常に false になるためエラーにする
+            if parsed == Err(RegexError::Unsupported) {
+                self.index = *at;
+                return Err(self.error("an unsupported regular expression (e.g. \\p{..})"));
+            }
+            regex = Some(parsed);
+        }
+        self.depth = depth;
+        Ok(Operand::Call(FunctionCall {
+            function,
+            args: args.into_iter().map(|(arg, _)| arg).collect(),
+            regex,
+        }))
+    }
+
+    /// 入れ子を1段深くする。深さが `MAX_DEPTH` に達した場合はエラーを返す。
+    fn enter(&mut self) -> Result<(), JsonPretError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(&format!("the nesting depth exceeds the limit of {}", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// 空白(スペース、タブ、改行、復帰)を読み飛ばす
+    fn skip_blank(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.index += 1;
+        }
+    }
+
+    /// 前後の空白を含めて演算子 `op` を読む。無い場合は読み進めない。
+    fn eat_operator(&mut self, op: &str) -> bool {
+        let start: usize = self.index;
+        self.skip_blank();
+        if self.eat_str(op) {
+            self.skip_blank();
+            true
+        } else {
+            self.index = start;
+            false
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let len: usize = s.chars().count();
+        if self.chars.iter().skip(self.index).take(len).copied().eq(s.chars()) {
+            self.index += len;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c: Option<char> = self.peek();
+        self.index += 1;
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JsonPretError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn unexpected(&self) -> JsonPretError {
+        match self.peek() {
+            Some(c) => self.error(&format!("an unexpected char {}", c)),
+            None => self.error("unexpected end of the JSONPath"),
+        }
+    }
+
+    /// 現在の位置の列番号(1始まり)を付けたエラーを作る
+    fn error(&self, msg: &str) -> JsonPretError {
+        JsonPretError::QueryError(QueryError::new(&format!("{} at column {}", msg, self.index + 1)))
+    }
+}
+
+// --- 評価 ---
+
+/// 選択した値の、親から見た場所
+#[derive(Clone, Copy)]
+enum Step<'a> {
+    Name(&'a str),
+    Index(usize),
+}
+
+/// `nodes` に `segments` を順に適用する。
+/// `P` は値の場所で、子の場所は `join` で作る。フィルタの中では場所を使わないので `()` にする。
+fn select<'a, P: Clone>(
+    segments: &[Segment],
+    root: &'a JsonObject,
+    mut nodes: Vec<(P, &'a JsonObject)>,
+    join: &dyn Fn(&P, Step<'_>) -> P,
+) -> Vec<(P, &'a JsonObject)> {
+    for segment in segments {
+        let mut outputs: Vec<(P, &'a JsonObject)> = vec![];
+        for (path, value) in nodes {
+            if segment.descendant {
+                let mut descendants: Vec<(P, &'a JsonObject)> = vec![];
+                collect_descendants(path, value, join, &mut descendants);
+                for (path, value) in &descendants {
+                    for selector in &segment.selectors {
+                        apply(selector, root, path, value, join, &mut outputs);
+                    }
+                }
+            } else {
+                for selector in &segment.selectors {
+                    apply(selector, root, &path, value, join, &mut outputs);
+                }
+            }
+        }
+        nodes = outputs;
+    }
+    nodes
+}
+
+/// `value` とその子孫を、親が子より先になる順に集める
+fn collect_descendants<'a, P: Clone>(
+    path: P,
+    value: &'a JsonObject,
+    join: &dyn Fn(&P, Step<'_>) -> P,
+    outputs: &mut Vec<(P, &'a JsonObject)>,
+) {
+    let children: Vec<(P, &'a JsonObject)> = children(&path, value, join);
+    outputs.push((path, value));
+    for (path, child) in children {
+        collect_descendants(path, child, join, outputs);
+    }
+}
+
+/// Array の要素または Object の値
+fn children<'a, P: Clone>(
+    path: &P,
+    value: &'a JsonObject,
+    join: &dyn Fn(&P, Step<'_>) -> P,
+) -> Vec<(P, &'a JsonObject)> {
+    match value {
+        JsonObject::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (join(path, Step::Index(i)), v))
+            .collect(),
+        JsonObject::Object(map) => map
+            .iter()
+            .map(|(k, v)| (join(path, Step::Name(k)), v))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// `value` に `selector` を適用し、選択された値を `outputs` に追加する
+fn apply<'a, P: Clone>(
+    selector: &Selector,
+    root: &'a JsonObject,
+    path: &P,
+    value: &'a JsonObject,
+    join: &dyn Fn(&P, Step<'_>) -> P,
+    outputs: &mut Vec<(P, &'a JsonObject)>,
+) {
+    match (selector, value) {
+        (Selector::Name(name), JsonObject::Object(map)) => {
+            if let Some(v) = map.get(name) {
+                outputs.push((join(path, Step::Name(name)), v));
+            }
+        }
+        (Selector::Wildcard, _) => outputs.extend(children(path, value, join)),
+        (Selector::Index(i), JsonObject::Array(array)) => {
+            let len: i64 = array.len() as i64;
+            let i: i64 = if *i < 0 { len + i } else { *i };
+            if (0..len).contains(&i) {
+                outputs.push((join(path, Step::Index(i as usize)), &array[i as usize]));
+            }
+        }
+        (Selector::Slice(start, end, step), JsonObject::Array(array)) => {
+            for i in slice_indices(array.len() as i64, *start, *end, step.unwrap_or(1)) {
+                outputs.push((join(path, Step::Index(i)), &array[i]));
+            }
+        }
+        (Selector::Filter(expr), _) => {
+            for (path, child) in children(path, value, join) {
+                if test(expr, root, child) {
+                    outputs.push((path, child));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// RFC 9535 2.3.4.2.2 のスライスで選択される添字
+fn slice_indices(len: i64, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut indices: Vec<usize> = vec![];
+    if step > 0 {
+        let lower: i64 = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper: i64 = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i: i64 = lower;
+        while i < upper {
+            indices.push(i as usize);
+            i += step;
+        }
+    } else if step < 0 {
+        let upper: i64 = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower: i64 = normalize(end.unwrap_or(-len - 1)).clamp(-1, len - 1);
+        let mut i: i64 = upper;
+        while lower < i {
+            indices.push(i as usize);
+            i += step;
+        }
+    }
+    indices
+}
+
+/// フィルタの論理式を `current`(`@`)について評価する
+fn test(expr: &Expr, root: &JsonObject, current: &JsonObject) -> bool {
+    match expr {
+        Expr::Or(lhs, rhs) => test(lhs, root, current) || test(rhs, root, current),
+        Expr::And(lhs, rhs) => test(lhs, root, current) && test(rhs, root, current),
+        Expr::Not(inner) => !test(inner, root, current),
+        Expr::Compare(op, lhs, rhs) => {
+            let lhs: Option<Cow<JsonObject>> = evaluate(lhs, root, current);
+            let rhs: Option<Cow<JsonObject>> = evaluate(rhs, root, current);
+            compare(*op, lhs.as_deref(), rhs.as_deref())
+        }
+        Expr::Exists(query) => !select_values(query, root, current).is_empty(),
+        Expr::Call(call) => call_logical(call, root, current),
+    }
+}
+
+/// フィルタの中のクエリで選択される値
+fn select_values<'a>(query: &FilterQuery, root: &'a JsonObject, current: &'a JsonObject) -> Vec<&'a JsonObject> {
+    let start: &JsonObject = if query.relative { current } else { root };
+    select(&query.segments, root, vec![((), start)], &|_, _| ())
+        .into_iter()
+        .map(|(_, v)| v)
+        .collect()
+}
+
+/// ValueType の値を求める。値が無い(Nothing)場合は `None`。
+fn evaluate<'a>(operand: &'a Operand, root: &'a JsonObject, current: &'a JsonObject) -> Option<Cow<'a, JsonObject>> {
+    match operand {
+        Operand::Literal(value) => Some(Cow::Borrowed(value)),
+        Operand::Query(query) => match select_values(query, root, current).as_slice() {
+            [value] => Some(Cow::Borrowed(*value)),
+            _ => None,
+        },
+        Operand::Call(call) => {
+            let count = |value: usize| Some(Cow::Owned(JsonObject::Number(JsonNumber::from(value))));
+            match call.function {
+                Function::Length => match evaluate(&call.args[0], root, current)?.as_ref() {
+                    JsonObject::String(s) => count(s.chars().count()),
+                    JsonObject::Array(array) => count(array.len()),
+                    JsonObject::Object(map) => count(map.len()),
+                    _ => None,
+                },
+                Function::Count => count(nodes_arg(&call.args[0], root, current).len()),
+                Function::Value => match nodes_arg(&call.args[0], root, current).as_slice() {
+                    [value] => Some(Cow::Borrowed(*value)),
+                    _ => None,
+                },
+                Function::Match | Function::Search => unreachable!(),
+            }
+        }
+    }
+}
+
+/// NodesType の引数を評価する。引数は `parse_call` でクエリであることを確認している。
+fn nodes_arg<'a>(arg: &'a Operand, root: &'a JsonObject, current: &'a JsonObject) -> Vec<&'a JsonObject> {
+    match arg {
+        Operand::Query(query) => select_values(query, root, current),
+        _ => unreachable!(),
+    }
+}
+
+/// `match()` と `search()`。文字列でない場合と、I-Regexp として正しくない場合は `false`。
+fn call_logical(call: &FunctionCall, root: &JsonObject, current: &JsonObject) -> bool {
+    let input: Option<Cow<JsonObject>> = evaluate(&call.args[0], root, current);
+    let pattern: Option<Cow<JsonObject>> = evaluate(&call.args[1], root, current);
+    let (input, pattern): (&str, &str) = match (input.as_deref(), pattern.as_deref()) {
+        (Some(JsonObject::String(input)), Some(JsonObject::String(pattern))) => (input, pattern),
+        _ => return false,
+    };
+    let parsed: Result<Regex, RegexError>;
+    let regex: &Result<Regex, RegexError> = match &call.regex {
+        Some(regex) => regex,
+        None => {
+            parsed = Regex::parse(pattern);
+            &parsed
+        }
+    };
+    match regex {
+        Ok(regex) if call.function == Function::Match => regex.is_match(input),
+        Ok(regex) => regex.is_found(input),
+        Err(_) => false,
+    }
+}
+
+fn compare(op: CompareOp, lhs: Option<&JsonObject>, rhs: Option<&JsonObject>) -> bool {
+    match op {
+        CompareOp::Eq => equals(lhs, rhs),
+        CompareOp::Ne => !equals(lhs, rhs),
+        CompareOp::Lt => less(lhs, rhs),
+        CompareOp::Le => less(lhs, rhs) || equals(lhs, rhs),
+        CompareOp::Gt => less(rhs, lhs),
+        CompareOp::Ge => less(rhs, lhs) || equals(lhs, rhs),
+    }
+}
+
+/// どちらも Nothing の場合も等しい
+fn equals(lhs: Option<&JsonObject>, rhs: Option<&JsonObject>) -> bool {
+    match (lhs, rhs) {
+        (None, None) => true,
+        (Some(a), Some(b)) => value_equals(a, b),
+        _ => false,
+    }
+}
+
+/// Number は数値として比べる
+fn value_equals(a: &JsonObject, b: &JsonObject) -> bool {
+    match (a, b) {
+        (JsonObject::Number(x), JsonObject::Number(y)) => compare_numbers(x, y) == Some(Ordering::Equal),
+        (JsonObject::Array(x), JsonObject::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| value_equals(x, y))
+        }
+        (JsonObject::Object(x), JsonObject::Object(y)) => {
+            x.len() == y.len()
+                && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| value_equals(v, w)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Number どうしと String どうしだけが大小を比べられる
+fn less(lhs: Option<&JsonObject>, rhs: Option<&JsonObject>) -> bool {
+    match (lhs, rhs) {
+        (Some(JsonObject::Number(x)), Some(JsonObject::Number(y))) => compare_numbers(x, y) == Some(Ordering::Less),
+        (Some(JsonObject::String(x)), Some(JsonObject::String(y))) => x < y,
+        _ => false,
+    }
+}
+
+/// 数値として比べる。f64 の範囲を超える数は、符号に応じた無限大として扱う。
+fn compare_numbers(x: &JsonNumber, y: &JsonNumber) -> Option<Ordering> {
+    // as_f64() は範囲を超える数を None にするため、表記から直接変換する
+    let to_f64 = |n: &JsonNumber| n.as_str().parse::<f64>().ok();
+    to_f64(x)?.partial_cmp(&to_f64(y)?)
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{jsonpath::JsonPath, parse, to_string, JsonPretError};
+
+    /// RFC 9535 の例で使われている JSON
+    const STORE: &str = r#"{ "store": {
+        "book": [
+            { "category": "reference", "author": "Nigel Rees",
+              "title": "Sayings of the Century", "price": 8.95 },
+            { "category": "fiction", "author": "Evelyn Waugh",
+              "title": "Sword of Honour", "price": 12.99 },
+            { "category": "fiction", "author": "Herman Melville",
+              "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99 },
+            { "category": "fiction", "author": "J. R. R. Tolkien",
+              "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99 }
+        ],
+        "bicycle": { "color": "red", "price": 399 }
+    } }"#;
+
+    /// 選択された値を compact な JSON にして空白区切りで返す
+    fn values(path: &str, input: &str) -> String {
+        let input = parse(input).unwrap();
+        let path = JsonPath::parse(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        path.query(&input)
+            .iter()
+            .map(|n| to_string(n.value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn paths(path: &str, input: &str) -> Vec<String> {
+        let input = parse(input).unwrap();
+        let path = JsonPath::parse(path).unwrap();
+        path.query(&input).iter().map(|n| n.path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_store_examples() {
+        assert_eq!(
+            values("$.store.book[*].author", STORE),
+            r#""Nigel Rees" "Evelyn Waugh" "Herman Melville" "J. R. R. Tolkien""#
+        );
+        assert_eq!(paths("$..author", STORE).len(), 4);
+        assert_eq!(paths("$.store.*", STORE), ["$['store']['book']", "$['store']['bicycle']"]);
+        assert_eq!(values("$.store..price", STORE), "8.95 12.99 8.99 22.99 399");
+        assert_eq!(paths("$..book[2]", STORE), ["$['store']['book'][2]"]);
+        assert_eq!(values("$..book[2].author", STORE), r#""Herman Melville""#);
+        assert_eq!(values("$..book[2].publisher", STORE), "");
+        assert_eq!(values("$..book[-1].title", STORE), r#""The Lord of the Rings""#);
+        assert_eq!(values("$..book[0,1].price", STORE), "8.95 12.99");
+        assert_eq!(values("$..book[:2].price", STORE), "8.95 12.99");
+        assert_eq!(values("$..book[?@.isbn].title", STORE), r#""Moby Dick" "The Lord of the Rings""#);
+        assert_eq!(values("$..book[?@.price<10].title", STORE), r#""Sayings of the Century" "Moby Dick""#);
+        assert_eq!(paths("$..*", STORE).len(), 27);
+    }
+
+    #[test]
+    fn test_selectors() {
+        let input = r#"{"a": [0, 1, 2, 3, 4, 5, 6], "o": {"j": 1, "k": "x"}, "a b": {"'": true}}"#;
+        assert_eq!(values("$.a[1:5:2]", input), "1 3");
+        assert_eq!(values("$.a[5:1:-2]", input), "5 3");
+        assert_eq!(values("$.a[::-3]", input), "6 3 0");
+        assert_eq!(values("$.a[-2:]", input), "5 6");
+        assert_eq!(values("$.a[1:3:0]", input), "");
+        assert_eq!(values("$.a[7]", input), "");
+        assert_eq!(values("$.a[ 0 , -1 ]", input), "0 6");
+        assert_eq!(values(r#"$["a b"]['\'']"#, input), "true");
+        assert_eq!(values("$.o[*]", input), r#"1 "x""#);
+        assert_eq!(values("$.o[0]", input), "");
+        assert_eq!(values("$.a.b", input), "");
+        assert_eq!(values("$..[0]", input), "0");
+        assert_eq!(values("$ .o .j", input), "1");
+    }
+
+    #[test]
+    fn test_filters() {
+        let input = r#"[
+            {"n": 1, "s": "abc", "t": [1, 2]},
+            {"n": 2, "s": "b", "t": [1, 2], "x": null},
+            {"n": 3.0, "s": "xbz"},
+            "text", 4
+        ]"#;
+        assert_eq!(values("$[?@.n == 3].n", input), "3.0");
+        assert_eq!(values("$[?@.n > 1 && @.n <= 3].n", input), "2 3.0");
+        assert_eq!(values("$[?@.n == 1 || !@.s].n", input), "1");
+        assert_eq!(values("$[?!(@.n < 2)].n", input), "2 3.0");
+        assert_eq!(values("$[?@.x].n", input), "2");
+        assert_eq!(values("$[?@.x == null].n", input), "2");
+        assert_eq!(values("$[?@.t == $[0].t].n", input), "1 2");
+        assert_eq!(paths("$[?@.missing == @.other]", input).len(), 5);
+        assert_eq!(values("$[?@ == 4]", input), "4");
+        assert_eq!(values("$[?@ > 'a']", input), r#""text""#);
+        assert_eq!(values("$[?length(@.s) == 1].n", input), "2");
+        assert_eq!(paths("$[?length(@) == 4]", input), ["$[1]", "$[3]"]);
+        assert_eq!(values("$[?count(@.*) == 4].n", input), "2");
+        assert_eq!(values("$[?match(@.s, 'a.c')].n", input), "1");
+        assert_eq!(values("$[?search(@.s, 'b')].n", input), "1 2 3.0");
+        assert_eq!(values("$[?match(@.s, 'b')].n", input), "2");
+        assert_eq!(values("$[?match(@.s, '(')]", input), "");
+        // クエリで指定した正規表現はノードごとに読み込む
+        assert_eq!(values("$[?match(@.s, $[1].s)].n", input), "2");
+        assert_eq!(values("$[?value(@..n) == 2].s", input), r#""b""#);
+
+        // f64 の範囲を超える数
+        let input = "[1e400, 5, -1e400]";
+        assert_eq!(values("$[?@ < 5]", input), "-1e400");
+        assert_eq!(values("$[?@ > 5]", input), "1e400");
+        assert_eq!(values("$[?@ == $[2]]", input), "-1e400");
+        assert_eq!(values("$[?@ == 1e400]", input), "1e400");
+    }
+
+    #[test]
+    fn test_normalized_path() {
+        let input = r#"{"a": [{"b'\\\n\u0001": 1}]}"#;
+        assert_eq!(paths("$..*", input), [
+            "$['a']",
+            "$['a'][0]",
+            r"$['a'][0]['b\'\\\n\u0001']",
+        ]);
+        let input = parse(input).unwrap();
+        let nodes = JsonPath::parse("$.a[0].*").unwrap().query(&input);
+        assert_eq!(nodes[0].path.to_pointer().to_string(), "/a/0/b'\\\n\u{1}");
+        assert_eq!(paths("$", "1"), ["$"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let invalid = [
+            "", "a", " $", "$ ", "$.", "$..", "$[", "$[1", "$[01]", "$[-0]", "$[1.0]",
+            "$[9007199254740992]", "$['a]", r#"$["\a"]"#, "$.1a", "$[?@.a = 1]", "$[?1]",
+            "$[?@.* == 1]", "$[?@..a == 1]", "$[?length(@.*) == 1]", "$[?count(1) == 1]",
+            "$[?length(@)]", "$[?match(@.a, 'a') == true]", "$[?foo(@)]", "$[?length(@, @)]",
+            "$[?(@.a]", "$[?@.t == [1]]", "@.a", "$[?@.a == tru]", "$['\u{1}']",
+        ];
+        for path in invalid {
+            assert!(
+                matches!(JsonPath::parse(path), Err(JsonPretError::QueryError(_))),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            JsonPath::parse("$.a[?@.b = 1]").unwrap_err().to_string(),
+            "QueryError: an unexpected char = at column 10"
+        );
+        assert_eq!(
+            JsonPath::parse(r"$[?match(@, '\\p{L}+')]").unwrap_err().to_string(),
+            r"QueryError: an unsupported regular expression (e.g. \p{..}) at column 13"
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let deep: [String; 4] = [
+            format!("$[?{}@{}]", "(".repeat(50000), ")".repeat(50000)),
+            format!("$[?{}]", vec!["@"; 50000].join(" || ")),
+            format!("${}{}", "[?@".repeat(50000), "]".repeat(50000)),
+            format!("$[?{}@{} == 1]", "length(".repeat(100000), ")".repeat(100000)),
+        ];
+        for path in &deep {
+            let err = JsonPath::parse(path).unwrap_err().to_string();
+            assert!(err.starts_with("QueryError: the nesting depth exceeds the limit of 64"), "{}", err);
+        }
+        assert_eq!(values(&format!("$[?{}@{}]", "(".repeat(60), ")".repeat(60)), "[1]"), "1");
+        assert_eq!(values(&format!("$[?{}]", vec!["@"; 60].join(" || ")), "[1]"), "1");
+        assert!(JsonPath::parse(&format!("${}{}", "[?@".repeat(60), "]".repeat(60))).is_ok());
+        assert!(JsonPath::parse(&format!("$[?{}@{} == 1]", "length(".repeat(60), ")".repeat(60))).is_ok());
+    }
+}
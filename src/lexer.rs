@@ -1,13 +1,18 @@
 use std::{
-    iter::Peekable,
-    str::Chars
+    fmt,
+    io::{BufRead, ErrorKind},
+};
+
+use crate::{
+    error::{EofError, IoError, JsonPretError, LexerError},
+    number::JsonNumber,
+    position::{Position, Span},
 };
-use crate::error::{JsonPretError, LexerError};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     String(String), // 文字列
-    Number(f64),    // 数値
+    Number(JsonNumber), // 数値
     Bool(bool),     // 真偽値
     Null,           // Null
     WhiteSpace,     // 空白
@@ -17,194 +22,593 @@ pub enum Token {
     RightBracket,   // ]　JSON array  終了文字
     Comma,          // ,　JSON value  区切り文字
     Colon,          // :　"key":value 区切り文字
+    Identifier(String), // JSON5 の引用符の無いキー、Infinity と NaN
+    Comment(String),    // JSON5 の // または /* */ のコメント
+}
+
+impl Token {
+    /// Object のキーとして使える場合はキーの文字列を返す。
+    /// JSON5 では引用符の無い識別子と true, false, null もキーになる。
+    pub(crate) fn into_key(self, json5: bool) -> Option<String> {
+        match self {
+            Token::String(s) => Some(s),
+            Token::Identifier(name) if json5 && !name.starts_with(['+', '-']) => Some(name),
+            Token::Bool(b) if json5 => Some(b.to_string()),
+            Token::Null if json5 => Some("null".to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// エラーメッセージ用に、入力の表記で書き出す
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::String(s) => write!(f, "string {:?}", s),
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Bool(b) => write!(f, "{}", b),
+            Token::Null => f.write_str("null"),
+            Token::WhiteSpace => f.write_str("whitespace"),
+            Token::LeftBrace => f.write_str("{"),
+            Token::RightBrace => f.write_str("}"),
+            Token::LeftBracket => f.write_str("["),
+            Token::RightBracket => f.write_str("]"),
+            Token::Comma => f.write_str(","),
+            Token::Colon => f.write_str(":"),
+            Token::Identifier(name) => write!(f, "identifier {}", name),
+            Token::Comment(_) => f.write_str("comment"),
+        }
+    }
+}
+
+/// 入力中の範囲を持つ `Token`
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
 
+/// エラー表示のために保持する、現在の行の最大のバイト数
+const MAX_LINE_BUFFER: usize = 4096;
+
+/// スニペット表示のため、エラーの位置より後ろに読み込む最大の文字数
+const SNIPPET_LOOKAHEAD: usize = 40;
+
+/// `BufRead` から1文字ずつ読み込み、`Token` に分割する
 #[derive(Debug)]
-pub struct Lexer<'a> {
-    chars: Peekable<Chars<'a>>
+pub struct Lexer<R> {
+    reader: R,
+    /// 先読みした文字
+    peeked: Option<char>,
+    /// 次に読み込む文字の位置
+    position: Position,
+    /// 現在の行のうち読み込んだ部分。長い行は先頭を省略する。
+    line: String,
+    /// `line` の先頭で省略した文字数
+    line_offset: usize,
+    /// JSON5 の文法を受け付けるかどうか
+    json5: bool,
 }
 
-impl<'a> Lexer<'a> {
-    pub fn new(raw_str: &str) -> Lexer {
+impl<'a> Lexer<&'a [u8]> {
+    pub fn new(raw_str: &'a str) -> Lexer<&'a [u8]> {
+        Lexer::from_reader(raw_str.as_bytes())
+    }
+}
+
+impl<R: BufRead> Lexer<R> {
+    /// `reader` から必要な分だけ読み込む Lexer を作る
+    pub fn from_reader(reader: R) -> Lexer<R> {
         Lexer {
-            chars: raw_str.chars().peekable()
+            reader,
+            peeked: None,
+            position: Position::default(),
+            line: String::new(),
+            line_offset: 0,
+            json5: false,
         }
     }
 
-    pub fn lexical_analyze(&mut self) -> Result<Vec<Token>, JsonPretError> {
-        let mut tokens: Vec<Token> = vec![];
-        while let Some(token) = self.next_token().unwrap() {
-            match token {
-                Token::WhiteSpace => {}
-                _ => tokens.push(token),
-            }
+    /// JSON5 の文法(コメント、' で囲んだ文字列、引用符の無いキー、16進数、
+    /// Infinity, NaN, 先頭の + と小数点)を受け付けるかどうかを設定する
+    pub fn json5(mut self, enabled: bool) -> Lexer<R> {
+        self.json5 = enabled;
+        self
+    }
+
+    /// JSON5 の文法を受け付けるかどうかを返す
+    pub fn is_json5(&self) -> bool {
+        self.json5
+    }
+
+    pub fn lexical_analyze(&mut self) -> Result<Vec<SpannedToken>, JsonPretError> {
+        let mut tokens: Vec<SpannedToken> = vec![];
+        while let Some(token) = self.read_token()? {
+            tokens.push(token);
         }
         Ok(tokens)
     }
 
-    /// 文字列を読み込み、マッチしたTokenを返す
-    fn next_token(&mut self) -> Result<Option<Token>, JsonPretError> {
-        match self.chars.peek() {
+    /// 空白とコメントを読み飛ばし、次の `Token` を返す。入力の終わりでは `None` を返す。
+    pub fn read_token(&mut self) -> Result<Option<SpannedToken>, JsonPretError> {
+        loop {
+            match self.next_token()? {
+                Some(SpannedToken { token: Token::WhiteSpace | Token::Comment(_), .. }) => continue,
+                token => return Ok(token),
+            }
+        }
+    }
+
+    /// 空白を読み飛ばし、コメントを含めて次の `Token` を返す。入力の終わりでは `None` を返す。
+    pub fn read_token_with_comments(&mut self) -> Result<Option<SpannedToken>, JsonPretError> {
+        loop {
+            match self.next_token()? {
+                Some(SpannedToken { token: Token::WhiteSpace, .. }) => continue,
+                token => return Ok(token),
+            }
+        }
+    }
+
+    /// 次に読み込む文字の位置を返す
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// 文字列を読み込み、マッチしたTokenを範囲付きで返す
+    fn next_token(&mut self) -> Result<Option<SpannedToken>, JsonPretError> {
+        let start: Position = self.position;
+        let token: Token = match self.peek()? {
             Some(c) => match c {
-                c if c.is_whitespace() || *c == '\n' => Ok(Some(self.get_token(Token::WhiteSpace))),
-                c if is_number(*c, true) => Ok(Some(self.parse_number().unwrap())),
-                '{' => Ok(Some(self.get_token(Token::LeftBrace))),
-                '}' => Ok(Some(self.get_token(Token::RightBrace))),
-                '[' => Ok(Some(self.get_token(Token::LeftBracket))),
-                ']' => Ok(Some(self.get_token(Token::RightBracket))),
-                ',' => Ok(Some(self.get_token(Token::Comma))),
-                ':' => Ok(Some(self.get_token(Token::Colon))),
-                '"' => Ok(Some(self.parse_string().unwrap())),
-                't' => Ok(Some(self.parse_boolean(true).unwrap())),
-                'f' => Ok(Some(self.parse_boolean(false).unwrap())),
-                'n' => Ok(Some(self.parse_null().unwrap())),
-                _ => Err(JsonPretError::LexerError(
-                    LexerError::new(&format!("an unexpected char {}", c))
-                )),
-            }, 
-            None => Ok(None)
+                c if is_whitespace(c) || (self.json5 && is_json5_whitespace(c)) => {
+                    self.get_token(Token::WhiteSpace)?
+                }
+                '/' if self.json5 => self.parse_comment(start)?,
+                '\'' if self.json5 => self.parse_string()?,
+                c if self.json5 && (is_number(c, true) || c == '+' || c == '.') => {
+                    self.parse_json5_number()?
+                }
+                c if self.json5 && is_identifier_start(c) => self.parse_identifier()?,
+                c if is_number(c, true) => self.parse_number()?,
+                '{' => self.get_token(Token::LeftBrace)?,
+                '}' => self.get_token(Token::RightBrace)?,
+                '[' => self.get_token(Token::LeftBracket)?,
+                ']' => self.get_token(Token::RightBracket)?,
+                ',' => self.get_token(Token::Comma)?,
+                ':' => self.get_token(Token::Colon)?,
+                '"' => self.parse_string()?,
+                't' => self.parse_boolean(true)?,
+                'f' => self.parse_boolean(false)?,
+                'n' => self.parse_null()?,
+                _ => return Err(self.error(&format!("an unexpected char {}", c), start)),
+            },
+            None => return Ok(None)
+        };
+        Ok(Some(SpannedToken { token, span: Span::new(start, self.position) }))
+    }
+
+    /// 次の文字を読み進めずに返す
+    fn peek(&mut self) -> Result<Option<char>, JsonPretError> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_char()?;
         }
+        Ok(self.peeked)
     }
 
-    fn get_token(&mut self, token: Token) -> Token {
-        self.chars.next();
-        token
+    /// 1文字読み進め、位置を更新する
+    fn bump(&mut self) -> Result<Option<char>, JsonPretError> {
+        let c: char = match self.peeked.take() {
+            Some(c) => c,
+            None => match self.read_char()? {
+                Some(c) => c,
+                None => return Ok(None),
+            },
+        };
+        self.position.advance(c);
+        self.push_line(c);
+        Ok(Some(c))
+    }
+
+    /// `reader` から UTF-8 の1文字を読み込む
+    fn read_char(&mut self) -> Result<Option<char>, JsonPretError> {
+        let first: u8 = match self.read_byte()? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        let width: usize = match first {
+            0x00..=0x7F => return Ok(Some(first as char)),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Err(self.invalid_utf8()),
+        };
+
+        let mut bytes: [u8; 4] = [first, 0, 0, 0];
+        for byte in bytes.iter_mut().take(width).skip(1) {
+            *byte = match self.read_byte()? {
+                Some(b) => b,
+                None => return Err(self.invalid_utf8()),
+            };
+        }
+        match std::str::from_utf8(&bytes[..width]) {
+            Ok(s) => Ok(s.chars().next()),
+            Err(_) => Err(self.invalid_utf8()),
+        }
+    }
+
+    /// `reader` から1バイト読み込む
+    fn read_byte(&mut self) -> Result<Option<u8>, JsonPretError> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buffer) => {
+                    let byte: Option<u8> = buffer.first().copied();
+                    if byte.is_some() {
+                        self.reader.consume(1);
+                    }
+                    return Ok(byte);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(JsonPretError::IoError(IoError::from(e))),
+            }
+        }
+    }
+
+    /// 読み込んだ文字を現在の行に追加する
+    fn push_line(&mut self, c: char) {
+        if c == '\n' {
+            self.line.clear();
+            self.line_offset = 0;
+            return;
+        }
+        self.line.push(c);
+        if self.line.len() > MAX_LINE_BUFFER {
+            // 前半を捨てる
+            let mut cut: usize = self.line.len() / 2;
+            while !self.line.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.line_offset += self.line[..cut].chars().count();
+            self.line.drain(..cut);
+        }
+    }
+
+    /// `err` の位置が現在の行にある場合、行の内容をエラーに付与する。
+    /// 行の続きを表示するため、少しだけ先を読み込む。
+    pub(crate) fn attach_source(&mut self, mut err: JsonPretError) -> JsonPretError {
+        let position: Position = match err.position() {
+            Some(p) => p,
+            None => return err,
+        };
+        if position.line != self.position.line || position.column <= self.line_offset {
+            return err;
+        }
+
+        for _ in 0..SNIPPET_LOOKAHEAD {
+            match self.peek() {
+                Ok(Some(c)) if c != '\n' => {
+                    let _ = self.bump();
+                }
+                _ => break,
+            }
+        }
+        err.set_source_line(self.line.trim_end_matches('\r').to_string(), self.line_offset);
+        err
+    }
+
+    /// `position` で発生した LexerError を作る
+    fn error(&mut self, msg: &str, position: Position) -> JsonPretError {
+        self.attach_source(JsonPretError::LexerError(LexerError::new(msg, position)))
+    }
+
+    /// 現在の位置で入力が終わった時の EofError を作る
+    fn eof_error(&mut self, msg: &str) -> JsonPretError {
+        let position: Position = self.position;
+        self.attach_source(JsonPretError::EofError(EofError::new(msg, position)))
+    }
+
+    /// 現在の位置で UTF-8 として正しくない入力を読んだ時の LexerError を作る
+    fn invalid_utf8(&mut self) -> JsonPretError {
+        // 不正なバイト列は行の内容として表示できないため、スニペットは付けない
+        JsonPretError::LexerError(LexerError::new("invalid UTF-8 sequence", self.position))
+    }
+
+    fn get_token(&mut self, token: Token) -> Result<Token, JsonPretError> {
+        self.bump()?;
+        Ok(token)
     } 
 
     fn parse_number(&mut self) -> Result<Token, JsonPretError>{
+        let start: Position = self.position;
         let mut number_str: String = String::new();
-        while let Some(&c) = self.chars.peek() {
+        while let Some(c) = self.peek()? {
             if is_number(c, false) {
-                self.chars.next();
+                self.bump()?;
                 number_str.push(c);
             } else {
                 break;
             }
         }
 
-        match number_str.parse::<f64>() {
-            Ok(number) => Ok(Token::Number(number)),
-            Err(e) => Err(JsonPretError::LexerError(
-                LexerError::new(&e.to_string()),
-            ))
+        if !is_valid_number(&number_str) {
+            return Err(self.error(&format!("'{number_str}' is not a valid number"), start));
         }
+
+        Ok(Token::Number(JsonNumber::from_valid_lexeme(number_str)))
+    }
+
+    /// JSON5 の数値を JSON の表記に直して読み込む。
+    /// 先頭の + と小数点の前後の省略を補い、16進数は10進数にする。
+    /// 符号付きの Infinity と NaN は `Token::Identifier` として返す。
+    fn parse_json5_number(&mut self) -> Result<Token, JsonPretError> {
+        let start: Position = self.position;
+        let mut raw: String = String::new();
+        while let Some(c) = self.peek()? {
+            if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.') {
+                self.bump()?;
+                raw.push(c);
+            } else {
+                break;
+            }
+        }
+
+        let (sign, body): (&str, &str) = match raw.strip_prefix('-') {
+            Some(body) => ("-", body),
+            None => ("", raw.strip_prefix('+').unwrap_or(&raw)),
+        };
+        if body == "Infinity" || body == "NaN" {
+            return Ok(Token::Identifier(raw.clone()));
+        }
+
+        let lexeme: Option<String> = if body.starts_with(['+', '-']) {
+            None
+        } else if let Some(hex) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+            hex_to_decimal(hex).map(|digits| format!("{sign}{digits}"))
+        } else {
+            let mut body: String = body.to_string();
+            if body.starts_with('.') {
+                // .5 => 0.5
+                body.insert(0, '0');
+            } else if let Some(i) = body.find('.') {
+                // 5. => 5, 5.e3 => 5e3
+                if !body[i + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+                    body.remove(i);
+                }
+            }
+            Some(format!("{sign}{body}"))
+        };
+
+        match lexeme {
+            Some(lexeme) if is_valid_number(&lexeme) => {
+                Ok(Token::Number(JsonNumber::from_valid_lexeme(lexeme)))
+            }
+            _ => Err(self.error(&format!("'{raw}' is not a valid number"), start)),
+        }
+    }
+
+    /// JSON5 の引用符の無い識別子を読み込む。true, false, null はそれぞれの `Token` になる。
+    fn parse_identifier(&mut self) -> Result<Token, JsonPretError> {
+        let mut name: String = String::new();
+        while let Some(c) = self.peek()? {
+            if is_identifier_start(c) || c.is_ascii_digit() {
+                self.bump()?;
+                name.push(c);
+            } else {
+                break;
+            }
+        }
+        Ok(match name.as_str() {
+            "true" => Token::Bool(true),
+            "false" => Token::Bool(false),
+            "null" => Token::Null,
+            _ => Token::Identifier(name),
+        })
+    }
+
+    /// JSON5 の `//` と `/* */` のコメントを読み込む。行コメントは行末の改行を含まない。
+    fn parse_comment(&mut self, start: Position) -> Result<Token, JsonPretError> {
+        self.bump()?;
+        let mut text: String = String::from("/");
+        match self.bump()? {
+            Some('/') => {
+                text.push('/');
+                while let Some(c) = self.peek()? {
+                    if c == '\n' {
+                        break;
+                    }
+                    self.bump()?;
+                    text.push(c);
+                }
+                if text.ends_with('\r') {
+                    text.pop();
+                }
+            }
+            Some('*') => {
+                text.push('*');
+                loop {
+                    match self.bump()? {
+                        Some(c) => {
+                            text.push(c);
+                            if text.len() > 3 && text.ends_with("*/") {
+                                break;
+                            }
+                        }
+                        None => return Err(
+                            self.eof_error("unexpected end of input while reading a comment")
+                        ),
+                    }
+                }
+            }
+            _ => return Err(self.error("an unexpected char /", start)),
+        }
+        Ok(Token::Comment(text))
     }
 
     fn parse_boolean(&mut self, b: bool) -> Result<Token, JsonPretError> {
+        let start: Position = self.position;
         // true の場合は4文字、falseの場合は5文字取得
-        let string: String =  match b {
-            true => self.get_string(4),
-            false => self.get_string(5),
+        let (string, keyword): (String, &str) =  match b {
+            true => (self.get_string(4)?, "true"),
+            false => (self.get_string(5)?, "false"),
         };
 
-        if &string == "true" || &string == "false" {
+        if string == keyword {
             Ok(Token::Bool(b))
+        } else if keyword.starts_with(&string) {
+            Err(self.eof_error(&format!("unexpected end of input while reading '{keyword}'")))
         } else {
-            Err(JsonPretError::LexerError(
-                LexerError::new(&format!("'{string}' is syntactically incorrect."))
-            ))
+            Err(self.error(&format!("'{string}' is syntactically incorrect."), start))
         }
     }
 
     fn parse_null(&mut self) -> Result<Token, JsonPretError> {
+        let start: Position = self.position;
         // 4文字取得
-        let string: String = self.get_string(4);
+        let string: String = self.get_string(4)?;
         
         // 読み込んだ文字が "null" の場合、Token を返す。
         if &string == "null" {
             Ok(Token::Null)
+        } else if "null".starts_with(&string) {
+            Err(self.eof_error("unexpected end of input while reading 'null'"))
         } else {
-            Err(JsonPretError::LexerError(
-                LexerError::new(&format!("'{string}' is syntactically incorrect."))
-            ))
+            Err(self.error(&format!("'{string}' is syntactically incorrect."), start))
         }
     }
 
     fn parse_string(&mut self) -> Result<Token, JsonPretError>{
-        self.chars.next(); // 最初の " の分を進める。
+        // 最初の " の分を進める。JSON5 では ' で囲むこともできる。
+        let quote: Option<char> = self.bump()?;
 
         let mut utf16: Vec<u16> = vec![];
         let mut string: String = String::new();
 
-        while let Some(c) = self.chars.next() {
+        loop {
+            let char_start: Position = self.position;
+            let c: char = match self.bump()? {
+                Some(c) => c,
+                None => return Err(
+                    self.eof_error("unexpected end of input while reading a string")
+                ),
+            };
             match c {
                 '\\' => {
-                    let escaped_c = self.chars.next().unwrap();
-                    println!("{escaped_c}");
+                    let escaped_c: char = match self.bump()? {
+                        Some(c) => c,
+                        None => return Err(
+                            self.eof_error("unexpected end of input while reading an escape sequence")
+                        ),
+                    };
                     match escaped_c {
                         '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' => {
                             // エスケープ文字の時の処理
-                            match self.push_utf16(&mut string, &mut utf16) {
-                                Ok(()) => string.push_str(&format!("\\{escaped_c}")),
-                                Err(e) => return Err(e)
-                            }
+                            self.push_utf16(&mut string, &mut utf16, char_start)?;
+                            string.push(unescape(escaped_c));
                         }
                         'u' => {
                             // utf16の時の処理
-                            let code_point = match self.get_code_point() {
-                                Ok(point) => point,
-                                Err(e) => return Err(e),
-                            };
+                            let code_point: u16 = self.get_code_point()?;
                             utf16.push(code_point);
                         }
-                        _ => return Err(JsonPretError::LexerError(
-                            LexerError::new(&format!("an unexpected escaped char {escaped_c}"))
+                        c if self.json5 => {
+                            self.push_utf16(&mut string, &mut utf16, char_start)?;
+                            if let Some(c) = self.json5_escape(c, char_start)? {
+                                string.push(c);
+                            }
+                        }
+                        _ => return Err(self.error(
+                            &format!("an unexpected escaped char {escaped_c}"),
+                            char_start
                         ))
                     }
                 }
-                '\"' => {
+                c if Some(c) == quote => {
                     // 文字列パースの終了時の処理
-                    match self.push_utf16(&mut string, &mut utf16) {
-                        Ok(_) => break,
-                        Err(e) => return Err(e)
-                    }
+                    self.push_utf16(&mut string, &mut utf16, char_start)?;
+                    break;
                 },
                 _ => {
                     // 普通の文字の時の処理
-                    match self.push_utf16(&mut string, &mut utf16) {
-                        Ok(_) => string.push(c),
-                        Err(e) => return Err(e)
+                    self.push_utf16(&mut string, &mut utf16, char_start)?;
+                    if c < '\u{20}' {
+                        return Err(self.error(
+                            &format!("an unescaped control character {:?} in a string", c),
+                            char_start
+                        ));
                     }
-
+                    string.push(c);
                 }
             }
         }
         Ok(Token::String(string))
     }
 
+    /// JSON5 だけで使えるエスケープ(`\'`, `\v`, `\0`, `\xHH`, 行の継続など)を元の文字に戻す。
+    /// 行の継続の場合は `None` を返す。
+    fn json5_escape(&mut self, c: char, start: Position) -> Result<Option<char>, JsonPretError> {
+        match c {
+            'v' => Ok(Some('\u{0B}')),
+            '0' if !matches!(self.peek()?, Some('0'..='9')) => Ok(Some('\0')),
+            '1'..='9' | '0' => Err(self.error(&format!("an unexpected escaped char {c}"), start)),
+            'x' => {
+                let hexs: String = self.get_string(2)?;
+                match u8::from_str_radix(&hexs, 16) {
+                    Ok(b) if hexs.len() == 2 && hexs.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        Ok(Some(b as char))
+                    }
+                    _ => Err(self.error(&format!("an invalid hex escape \\x{hexs}"), start)),
+                }
+            }
+            '\r' => {
+                if self.peek()? == Some('\n') {
+                    self.bump()?;
+                }
+                Ok(None)
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => Ok(None),
+            c => Ok(Some(c)),
+        }
+    }
+
     /// 指定した文字数を取得する
-    fn get_string(&mut self, length: usize) -> String {
+    fn get_string(&mut self, length: usize) -> Result<String, JsonPretError> {
         let mut string: String = String::new();
         for _ in 0..length {
-            match self.chars.next() {
-                Some(c) => string.push(c),
-                None => {}
+            if let Some(c) = self.bump()? {
+                string.push(c);
             }
         }
-        string
+        Ok(string)
     }
 
     /// utf16のコードポイントを取得する
     fn get_code_point(&mut self) -> Result<u16, JsonPretError> {
-        let hexs = (0..4).filter_map(|_| {
-            let c: char = self.chars.next().unwrap();
-            if c.is_ascii_hexdigit() {
-                Some(c)
-            } else {
-                None
+        let start: Position = self.position;
+        let mut hexs: String = String::new();
+        for _ in 0..4 {
+            match self.bump()? {
+                Some(c) if c.is_ascii_hexdigit() => hexs.push(c),
+                Some(c) => return Err(self.error(
+                    &format!("an unexpected char {c} in a unicode escape"),
+                    start
+                )),
+                None => return Err(
+                    self.eof_error("unexpected end of input while reading a unicode escape")
+                ),
             }
-        });
+        }
 
         // 読み込んだ文字列を16新数に変換して、utf16のバッファにpushする
-        match u16::from_str_radix(&hexs.collect::<String>(), 16) {
+        match u16::from_str_radix(&hexs, 16) {
             Ok(code_point) => Ok(code_point),
-            Err(e) => Err(JsonPretError::LexerError(
-                LexerError::new(&e.to_string())
-            ))
+            Err(e) => Err(self.error(&e.to_string(), start))
         }
     }
     /// utf16のバッファを文字列に結合する
-    fn push_utf16(&mut self, string: &mut String, utf16: &mut Vec<u16>) -> Result<(), JsonPretError>{
+    fn push_utf16(
+        &mut self,
+        string: &mut String,
+        utf16: &mut Vec<u16>,
+        position: Position,
+    ) -> Result<(), JsonPretError>{
         if utf16.is_empty() {
             return Ok(());
         }
@@ -215,39 +619,144 @@ impl<'a> Lexer<'a> {
                 utf16.clear();
                 Ok(())
             }
-            Err(e) => return Err(JsonPretError::LexerError(
-                LexerError::new(&e.to_string())
-            ))
+            Err(e) => Err(self.error(&e.to_string(), position))
         }
     }
 }
 
-/// Numberで使用される文字([0-9], +, -, .)かどうかを返す。  
+/// Numberで使用される文字([0-9], +, -, ., e, E)かどうかを返す。  
+/// 先頭になれるのは [0-9] と - のみ。
 fn is_number(c: char, is_prefix: bool) -> bool {
     if is_prefix {
-        c.is_numeric() || matches!(c, '+' | '-' | '.')
+        c.is_ascii_digit() || c == '-'
     } else {
-        c.is_numeric() || matches!(c, '+' | '-' | 'e' | 'E' | '.')
+        c.is_ascii_digit() || matches!(c, '+' | '-' | 'e' | 'E' | '.')
+
+    }
+}
+
+/// エスケープされた文字(`\` の次の文字)を元の文字に戻す
+fn unescape(c: char) -> char {
+    match c {
+        'b' => '\u{08}',
+        'f' => '\u{0C}',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        _ => c,
+    }
+}
+
+/// RFC 8259 で空白として扱われる文字(スペース, タブ, 改行, 復帰)かどうかを返す。
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+/// JSON5 で追加で空白として扱われる文字(Unicode の空白と BOM)かどうかを返す。
+fn is_json5_whitespace(c: char) -> bool {
+    c.is_whitespace() || c == '\u{FEFF}'
+}
+
+/// JSON5 の識別子の先頭になれる文字かどうかを返す。
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '$' || c == '_'
+}
+
+/// 符号付きを含む Infinity または NaN の識別子かどうかを返す。
+pub(crate) fn is_non_finite(name: &str) -> bool {
+    matches!(name.strip_prefix(['+', '-']).unwrap_or(name), "Infinity" | "NaN")
+}
+
+/// 16進数の文字列を10進数の文字列にする。桁数に制限は無い。
+fn hex_to_decimal(hex: &str) -> Option<String> {
+    if hex.is_empty() {
+        return None;
+    }
+    // 10進数の各桁(下の桁から順)
+    let mut digits: Vec<u32> = vec![0];
+    for c in hex.chars() {
+        let mut carry: u32 = c.to_digit(16)?;
+        for d in digits.iter_mut() {
+            let n: u32 = *d * 16 + carry;
+            *d = n % 10;
+            carry = n / 10;
+        }
+        while carry > 0 {
+            digits.push(carry % 10);
+            carry /= 10;
+        }
+    }
+    while digits.len() > 1 && digits.last() == Some(&0) {
+        digits.pop();
+    }
+    Some(digits.iter().rev().map(|d| char::from_digit(*d, 10).unwrap()).collect())
+}
+
+/// RFC 8259 の number の文法に従っているかを返す。
+/// number = [ "-" ] int [ frac ] [ exp ]
+pub(crate) fn is_valid_number(s: &str) -> bool {
+    let bytes: &[u8] = s.as_bytes();
+    let mut i: usize = 0;
+
+    // 指定した位置から続く数字の個数を返す
+    let digits = |i: usize| bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+
+    if bytes.get(i) == Some(&b'-') {
+        i += 1;
+    }
+
+    // int = zero / ( digit1-9 *DIGIT )
+    match bytes.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => i += digits(i),
+        _ => return false,
+    }
+
+    // frac = "." 1*DIGIT
+    if bytes.get(i) == Some(&b'.') {
+        let n: usize = digits(i + 1);
+        if n == 0 {
+            return false;
+        }
+        i += 1 + n;
+    }
 
+    // exp = ( "e" / "E" ) [ "-" / "+" ] 1*DIGIT
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        let n: usize = digits(i);
+        if n == 0 {
+            return false;
+        }
+        i += n;
     }
+
+    i == bytes.len()
 }
 
 // --- テストコード ---
 
 #[cfg(test)]
 mod tests {
-    use crate::{error::{JsonPretError, LexerError}, lexer::{Lexer, Token, is_number}};
+    use std::io::{self, BufReader, Read};
+
+    use crate::{
+        error::JsonPretError,
+        lexer::{Lexer, SpannedToken, Token, hex_to_decimal, is_number, is_valid_number, is_whitespace},
+        number::JsonNumber,
+        position::{Position, Span},
+    };
 
     #[test]
     fn test_lexer_new() {
-        let expect = Lexer {
-            chars: r##"{"key" : "value}"##.chars().peekable()
-        };
-
-        let actual = Lexer::new(r##"{"key" : "value}"##);
-        for (ac, ec) in actual.chars.zip(expect.chars) {
-            assert_eq!(ac, ec);
-        }
+        let mut actual = Lexer::new(r##"{"key" : "value}"##);
+        assert_eq!(actual.position(), Position::default());
+        assert_eq!(actual.bump().unwrap(), Some('{'));
+        assert_eq!(actual.peek().unwrap(), Some('"'));
+        assert_eq!(actual.position(), Position::new(1, 2, 1));
     }
 
     // #[test]
@@ -257,7 +766,7 @@ mod tests {
 
     #[test]
     fn test_parse_number() {
-        let expect = Token::Number(1.0);
+        let expect = Token::Number(JsonNumber::from_lexeme("1.0").unwrap());
         let mut lexer = Lexer::new("1.0");
         let actual = lexer.parse_number().unwrap();
 
@@ -280,21 +789,19 @@ mod tests {
 
         // t で true 以外の文字のケース(エラー)
         let err_str_t = "test";
-        let expect_err_t = JsonPretError::LexerError(
-            LexerError::new(&format!("'{err_str_t}' is syntactically incorrect."))
-        );
-        let mut lexer_err_t = Lexer::new(&err_str_t);
+        let mut lexer_err_t = Lexer::new(err_str_t);
         let actual_err_t = lexer_err_t.parse_boolean(true).unwrap_err();
-        assert_eq!(actual_err_t, expect_err_t);
+        assert!(matches!(actual_err_t, JsonPretError::LexerError(_)));
+        assert_eq!(actual_err_t.message(), format!("'{err_str_t}' is syntactically incorrect."));
+        assert_eq!(actual_err_t.position(), Some(Position::default()));
 
         // f で false 以外の文字のケース(エラー)
         let err_str_f = "fight";
-        let expect_err_f = JsonPretError::LexerError(
-            LexerError::new(&format!("'{err_str_f}' is syntactically incorrect."))
-        );
-        let mut lexer_err_f = Lexer::new(&err_str_f);
+        let mut lexer_err_f = Lexer::new(err_str_f);
         let actual_err_f = lexer_err_f.parse_boolean(false).unwrap_err();
-        assert_eq!(actual_err_f, expect_err_f);
+        assert!(matches!(actual_err_f, JsonPretError::LexerError(_)));
+        assert_eq!(actual_err_f.message(), format!("'{err_str_f}' is syntactically incorrect."));
+        assert_eq!(actual_err_f.position(), Some(Position::default()));
     }
 
     #[test]
@@ -320,15 +827,19 @@ mod tests {
         let token = Lexer::new(s).parse_string().unwrap();
         assert_eq!(token, Token::String("あいうabc".to_string()));
 
-        let s = format!(r#"\\b\f\n\r\t\/\""#);
-        let token = Lexer::new(&s).parse_string().unwrap();
+        let s = r#""\b\f\n\r\t\/\"""#;
+        let token = Lexer::new(s).parse_string().unwrap();
         assert_eq!(
             token,
-            Token::String(r#"\b\f\n\r\t\/\""#.to_string())
+            Token::String("\u{08}\u{0C}\n\r\t/\"".to_string())
         );
 
+        let s = r#""a\\b\u0022\\u0000""#;
+        let token = Lexer::new(s).parse_string().unwrap();
+        assert_eq!(token, Token::String(r#"a\b"\u0000"#.to_string()));
+
         let s = r#""\uD83D\uDE04\uD83D\uDE07\uD83D\uDC7A""#;
-        let token = Lexer::new(&s).parse_string().unwrap();
+        let token = Lexer::new(s).parse_string().unwrap();
         assert_eq!(token, Token::String(r#"😄😇👺"#.to_string()));
     }
 
@@ -337,17 +848,124 @@ mod tests {
     fn test_get_string() {
         let expect = String::from("test");
         let mut lexer = Lexer::new("test");
-        let actual = lexer.get_string(4);
+        let actual = lexer.get_string(4).unwrap();
         assert_eq!(actual, expect);
     }
 
     #[test]
     fn test_is_number() {
-        assert_eq!(is_number('1', true), true);
-        assert_eq!(is_number('+', true), true);
-        assert_eq!(is_number('e', true), false);
-        assert_eq!(is_number('e', false), true);
-        assert_eq!(is_number('a', false), false);
+        assert!(is_number('1', true));
+        assert!(is_number('-', true));
+        assert!(!is_number('+', true));
+        assert!(!is_number('.', true));
+        assert!(!is_number('٣', true));
+        assert!(!is_number('e', true));
+        assert!(is_number('e', false));
+        assert!(!is_number('a', false));
+    }
+
+    #[test]
+    fn test_is_whitespace() {
+        for c in [' ', '\t', '\n', '\r'] {
+            assert!(is_whitespace(c));
+        }
+        for c in ['\u{0B}', '\u{0C}', '\u{A0}', '\u{3000}', '\u{FEFF}'] {
+            assert!(!is_whitespace(c));
+        }
+    }
+
+    #[test]
+    fn test_lexical_analyze_json5() {
+        let input = "// c\n{a$1: '\\'\\x41\\\n', /* x\n */ +0x1F: [.5, 5., -Infinity, +1e3,],}\u{A0}";
+        let tokens: Vec<Token> = Lexer::new(input)
+            .json5(true)
+            .lexical_analyze()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .collect();
+        let number = |s: &str| Token::Number(JsonNumber::from_lexeme(s).unwrap());
+        let expect = vec![
+            Token::LeftBrace,
+            Token::Identifier("a$1".to_string()),
+            Token::Colon,
+            Token::String("'A".to_string()),
+            Token::Comma,
+            number("31"),
+            Token::Colon,
+            Token::LeftBracket,
+            number("0.5"),
+            Token::Comma,
+            number("5"),
+            Token::Comma,
+            Token::Identifier("-Infinity".to_string()),
+            Token::Comma,
+            number("1e3"),
+            Token::Comma,
+            Token::RightBracket,
+            Token::Comma,
+            Token::RightBrace,
+        ];
+        assert_eq!(tokens, expect);
+
+        for input in ["/", "/* x", "0x", "0xG", "+-1", ".e3", "'a\\1'", "'\\x4'", "#"] {
+            assert!(Lexer::new(input).json5(true).lexical_analyze().is_err(), "{}", input);
+        }
+        // JSON5 を有効にしない場合はコメントを受け付けない
+        let err = Lexer::new("// c").lexical_analyze().unwrap_err();
+        assert_eq!(err.message(), "an unexpected char /");
+    }
+
+    #[test]
+    fn test_read_token_with_comments() {
+        let mut lexer = Lexer::new("1 // a\r\n/* b */2").json5(true);
+        let mut tokens: Vec<SpannedToken> = vec![];
+        while let Some(t) = lexer.read_token_with_comments().unwrap() {
+            tokens.push(t);
+        }
+        let kinds: Vec<&Token> = tokens.iter().map(|t| &t.token).collect();
+        assert_eq!(kinds, vec![
+            &Token::Number(JsonNumber::from(1)),
+            &Token::Comment("// a".to_string()),
+            &Token::Comment("/* b */".to_string()),
+            &Token::Number(JsonNumber::from(2)),
+        ]);
+        assert_eq!(tokens[2].span.start, Position::new(2, 1, 8));
+    }
+
+    #[test]
+    fn test_hex_to_decimal() {
+        assert_eq!(hex_to_decimal("0").as_deref(), Some("0"));
+        assert_eq!(hex_to_decimal("00ff").as_deref(), Some("255"));
+        assert_eq!(
+            hex_to_decimal("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF").as_deref(),
+            Some("340282366920938463463374607431768211455")
+        );
+        assert_eq!(hex_to_decimal(""), None);
+        assert_eq!(hex_to_decimal("1g"), None);
+    }
+
+    #[test]
+    fn test_is_valid_number() {
+        for s in ["0", "-0", "1", "-12", "1.5", "0.25", "1e3", "1E+3", "-1.5e-10", "0e0"] {
+            assert!(is_valid_number(s), "{}", s);
+        }
+        for s in ["", "-", "+1", ".5", "01", "-01", "1.", "1.e3", "1e", "1e+", "1.5.5", "1e3e3", "1-2"] {
+            assert!(!is_valid_number(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_string_control_character() {
+        let err = Lexer::new("\"a\tb\"").parse_string().unwrap_err();
+        assert_eq!(err.position(), Some(Position::new(1, 3, 2)));
+    }
+
+    #[test]
+    fn test_parse_string_lone_surrogate() {
+        assert!(Lexer::new(r#""\uD83D""#).parse_string().is_err());
+        assert!(Lexer::new(r#""\uDE04""#).parse_string().is_err());
+        assert!(Lexer::new(r#""\uD83Dx""#).parse_string().is_err());
     }
 
     #[test]
@@ -370,7 +988,7 @@ mod tests {
             // begin: "number": 123,
             Token::String("number".to_string()),
             Token::Colon,
-            Token::Number(123f64),
+            Token::Number(JsonNumber::from(123)),
             Token::Comma,
             // end
 
@@ -395,7 +1013,7 @@ mod tests {
             // begin: "number": 2E10,
             Token::String("number".to_string()),
             Token::Colon,
-            Token::Number(20000000000f64),
+            Token::Number(JsonNumber::from_lexeme("2E10").unwrap()),
             // end
             Token::RightBrace,
             // end
@@ -404,6 +1022,7 @@ mod tests {
         ];
         tokens
             .iter()
+            .map(|t| &t.token)
             .zip(result_tokens.iter())
             .enumerate()
             .for_each(|(i, (x, y))| {
@@ -426,7 +1045,124 @@ mod tests {
         ];
         tokens
             .iter()
+            .map(|t| &t.token)
             .zip(result_tokens.iter())
             .for_each(|(x, y)| assert_eq!(x, y));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_lexical_analyze_span() {
+        let tokens = Lexer::new("{\n  \"あ\": 12\n}").lexical_analyze().unwrap();
+        let expect = vec![
+            SpannedToken {
+                token: Token::LeftBrace,
+                span: Span::new(Position::new(1, 1, 0), Position::new(1, 2, 1)),
+            },
+            SpannedToken {
+                token: Token::String("あ".to_string()),
+                span: Span::new(Position::new(2, 3, 4), Position::new(2, 6, 9)),
+            },
+            SpannedToken {
+                token: Token::Colon,
+                span: Span::new(Position::new(2, 6, 9), Position::new(2, 7, 10)),
+            },
+            SpannedToken {
+                token: Token::Number(JsonNumber::from(12)),
+                span: Span::new(Position::new(2, 8, 11), Position::new(2, 10, 13)),
+            },
+            SpannedToken {
+                token: Token::RightBrace,
+                span: Span::new(Position::new(3, 1, 14), Position::new(3, 2, 15)),
+            },
+        ];
+        assert_eq!(tokens, expect);
+    }
+
+    #[test]
+    fn test_lexical_analyze_error_position() {
+        let mut lexer = Lexer::new("[1,\n  @]");
+        let err = loop {
+            match lexer.next_token() {
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(err.position(), Some(Position::new(2, 3, 6)));
+        assert_eq!(err.source_line(), Some("  @]"));
+    }
+
+    /// 1回の `read` で返すバイト数を制限する Reader
+    struct ChunkedReader<'a> {
+        bytes: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            if n == 0 && !buf.is_empty() && self.chunk == 0 {
+                return Err(io::Error::other("broken pipe"));
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let input = "{\"あ\": [\"😄\", 1.5e3, null]}";
+        let expect = Lexer::new(input).lexical_analyze().unwrap();
+
+        // マルチバイト文字がバッファの境界で分かれても同じ結果になる
+        for chunk in 1..4 {
+            let reader = ChunkedReader { bytes: input.as_bytes(), chunk };
+            let actual = Lexer::from_reader(BufReader::with_capacity(2, reader))
+                .lexical_analyze()
+                .unwrap();
+            assert_eq!(actual, expect);
+        }
+    }
+
+    #[test]
+    fn test_from_reader_errors() {
+        // UTF-8 として正しくない入力
+        let bytes: &[u8] = b"[\"a\xFFb\"]";
+        let err = Lexer::from_reader(bytes).lexical_analyze().unwrap_err();
+        assert_eq!(err.message(), "invalid UTF-8 sequence");
+        assert_eq!(err.position(), Some(Position::new(1, 4, 3)));
+
+        // 読み込み中の I/O エラー
+        let reader = ChunkedReader { bytes: b"", chunk: 0 };
+        let err = Lexer::from_reader(BufReader::new(reader)).lexical_analyze().unwrap_err();
+        assert_eq!(err.to_string(), "IoError: broken pipe");
+    }
+
+    #[test]
+    fn test_error_source_line_long_input() {
+        // 長い行でもエラーの周辺だけを保持する
+        let input = format!("[{}x, 1, 2]", "1, ".repeat(5000));
+        let err = Lexer::new(&input).lexical_analyze().unwrap_err();
+        let column = err.position().unwrap().column;
+        assert_eq!(column, 15002);
+
+        let line = err.source_line().unwrap();
+        let offset = err.source_line_offset();
+        assert!(line.len() <= super::MAX_LINE_BUFFER + super::SNIPPET_LOOKAHEAD);
+        assert_eq!(line.chars().nth(column - 1 - offset), Some('x'));
+        assert!(line.ends_with("x, 1, 2]"));
+    }
+
+    #[test]
+    fn test_token_display() {
+        let tokens = [
+            Token::Number(JsonNumber::from(2)),
+            Token::String("a\"b".to_string()),
+            Token::Null,
+            Token::RightBrace,
+            Token::Identifier("foo".to_string()),
+        ];
+        let shown: Vec<String> = tokens.iter().map(|t| t.to_string()).collect();
+        assert_eq!(shown, ["number 2", "string \"a\\\"b\"", "null", "}", "identifier foo"]);
+    }
+}
\ No newline at end of file
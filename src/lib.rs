@@ -1,63 +1,430 @@
 mod lexer;
 mod parser;
 mod error;
+mod position;
+mod number;
+mod serializer;
+mod formatter;
+mod event;
+mod index;
+mod jsonc;
+mod lines;
+mod regex;
+pub mod jsonpath;
+pub mod map;
+pub mod pointer;
+pub mod query;
 
-use std::collections::BTreeMap;
-use std::ops::Index;
+use std::io::BufRead;
 
-use error::JsonPretError;
-use lexer::Lexer;
-use parser::Parser;
+pub use error::{EofError, IoError, JsonPretError, LexerError, ParserError, PointerError, QueryError};
+pub use event::{Event, EventReader};
+pub use formatter::format_reader;
+pub use index::JsonIndex;
+pub use jsonc::format_jsonc;
+pub use lines::JsonLines;
+pub use jsonpath::JsonPath;
+pub use lexer::{Lexer, SpannedToken, Token};
+pub use map::Map;
+pub use number::JsonNumber;
+pub use pointer::JsonPointer;
+pub use position::{Position, Span};
+pub use query::Query;
+pub use serializer::{
+    to_string, to_string_pretty, to_string_with_options, to_writer,
+    FormatOptions, Indent, Newline, Serializer,
+};
+pub use parser::{JsonStream, Parser};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum JsonObject {
     String(String),                  // 文字列
-    Number(f64),                     // 数値
+    Number(JsonNumber),              // 数値
     Bool(bool),                      // 真偽値
+    #[default]
     Null,                            // Null
     Array(Vec<JsonObject>),               // JSON Array
-    Object(BTreeMap<String, JsonObject>), // JSON Object
+    Object(Map),                          // JSON Object
 }
 
-/// {"key": true}
-/// v["key"] => JsonObject::Bool(true)
-impl Index<&str> for JsonObject {
-    type Output = JsonObject;
-    fn index(&self, key: &str) -> &Self::Output {
+/// 値が無い場合に `Index` が返す Null
+static NULL: JsonObject = JsonObject::Null;
+
+impl JsonObject {
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonObject::Null)
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, JsonObject::Bool(_))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, JsonObject::Number(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, JsonObject::String(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, JsonObject::Array(_))
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, JsonObject::Object(_))
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
         match self {
-            JsonObject::Object(map) => map
-                .get(key)
-                .unwrap_or_else(|| panic!("A key is not found: {}", key)),
-            _ => {
-                panic!("A JsonObject is not object");
-            }
+            JsonObject::Bool(b) => Some(*b),
+            _ => None,
         }
     }
-}
 
-/// [null, false, 3]
-/// v[3] => JsonObject::Number(3f64)
-impl Index<usize> for JsonObject {
-    type Output = JsonObject;
-    fn index(&self, idx: usize) -> &Self::Output {
+    pub fn as_number(&self) -> Option<&JsonNumber> {
         match self {
-            JsonObject::Array(array) => &array[idx],
-            _ => {
-                panic!("A JsonObject is not array");
-            }
+            JsonObject::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Number を i64 で返す。i64 で表せない場合は `None` を返す。
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number()?.as_i64()
+    }
+
+    /// Number を u64 で返す。u64 で表せない場合は `None` を返す。
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number()?.as_u64()
+    }
+
+    /// Number を f64 で返す。f64 の範囲を超える場合は `None` を返す。
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number()?.as_f64()
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonObject::String(s) => Some(s),
+            _ => None,
         }
     }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonObject>> {
+        match self {
+            JsonObject::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<JsonObject>> {
+        match self {
+            JsonObject::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Map> {
+        match self {
+            JsonObject::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut Map> {
+        match self {
+            JsonObject::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Object のキーまたは Array の添字で値を取得する。
+    /// 値が無い場合や型が合わない場合は `None` を返す。
+    ///
+    /// ```
+    /// let value = json_prettier::parse(r#"{"a": [1, 2]}"#).unwrap();
+    /// assert_eq!(value.get("a").and_then(|a| a.get(1)).and_then(|n| n.as_i64()), Some(2));
+    /// assert!(value.get("b").is_none());
+    /// assert!(value.get(0).is_none());
+    /// ```
+    pub fn get<I: JsonIndex>(&self, index: I) -> Option<&JsonObject> {
+        index.index_into(self)
+    }
+
+    /// Object のキーまたは Array の添字で値を変更可能な参照で取得する
+    pub fn get_mut<I: JsonIndex>(&mut self, index: I) -> Option<&mut JsonObject> {
+        index.index_into_mut(self)
+    }
+
+    /// 値を取り出し、代わりに Null を置く
+    pub fn take(&mut self) -> JsonObject {
+        std::mem::replace(self, JsonObject::Null)
+    }
 }
 
+/// パース時の設定
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Object のキーを入力順ではなく辞書順に並べる
+    pub sort_keys: bool,
+    /// JSON5 と JSONC の文法を受け付ける。
+    /// コメント、末尾の `,`、`'` で囲んだ文字列、引用符の無いキー、16進数、先頭の `+` などを読み込める。
+    /// 数値は JSON の表記に直す。JSON で表せない `Infinity` と `NaN` は、そのままの表記の Number として保持し、
+    /// 書き出す時は `null` にする(`FormatOptions::non_finite` の場合はそのまま書く)。
+    pub json5: bool,
+}
 
 /// JSON文字列を受け取り、JsonObjectを返す。
+/// Object のキーは入力順に保持される。
 pub fn parse(input: &str) -> Result<JsonObject, JsonPretError> {
-    let mut lexer: Lexer<'_> =  Lexer::new(input);
-    let tokens: Vec<lexer::Token> = match lexer.lexical_analyze() {
-        Ok(t) => t,
-        Err(e) => return Err(e)
+    parse_with_options(input, &ParseOptions::default())
+}
+
+/// JSON文字列を `options` に従ってパースし、JsonObjectを返す。
+pub fn parse_with_options(input: &str, options: &ParseOptions) -> Result<JsonObject, JsonPretError> {
+    parse_reader_with_options(input.as_bytes(), options).map_err(|e| e.with_source(input))
+}
+
+/// `reader` から JSON を読み込み、JsonObjectを返す。
+/// 入力全体をメモリに読み込まず、必要な分だけ読み進める。
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<JsonObject, JsonPretError> {
+    parse_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// `reader` から JSON を読み込み、`options` に従ってパースする。
+pub fn parse_reader_with_options<R: BufRead>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<JsonObject, JsonPretError> {
+    let mut parser: Parser<R> = Parser::new(Lexer::from_reader(reader), options.clone());
+    parser.parse_root()
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::{
+        parse, parse_reader, parse_with_options, to_string, to_string_with_options, FormatOptions, JsonObject,
+        JsonPretError, ParseOptions,
     };
 
-    let mut parser: Parser = Parser::new(tokens);
-    parser.parse()
-}
\ No newline at end of file
+    /// テスト用の疑似乱数生成器 (xorshift64)
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    /// ランダムな JSON 文字列を生成する
+    fn gen_json(rng: &mut Rng, depth: usize) -> String {
+        let kind = if depth == 0 { rng.below(4) } else { rng.below(6) };
+        match kind {
+            0 => rng.pick(&["null", "true", "false"]).to_string(),
+            1 => rng.pick(&["0", "-1", "3.14", "2E10", "1e-3", "12345678"]).to_string(),
+            2 => rng.pick(&[r#""""#, r#""abc""#, r#""\n\"""#, r#""\u3042""#, r#""\uD83D\uDE04""#]).to_string(),
+            3 => rng.pick(&["1", r#""x""#]).to_string(),
+            4 => {
+                let items: Vec<String> = (0..rng.below(4) + 1).map(|_| gen_json(rng, depth - 1)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            _ => {
+                let members: Vec<String> = (0..rng.below(4) + 1)
+                    .map(|i| format!(r#""k{}": {}"#, i, gen_json(rng, depth - 1)))
+                    .collect();
+                format!("{{{}}}", members.join(", "))
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_generated_json() {
+        let mut rng = Rng(0x2545F4914F6CDD1D);
+        for _ in 0..500 {
+            let json = gen_json(&mut rng, 4);
+            assert!(parse(&json).is_ok(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_parse_truncated_json_returns_err() {
+        let mut rng = Rng(0x9E3779B97F4A7C15);
+        for _ in 0..500 {
+            let json = gen_json(&mut rng, 4);
+            if !json.starts_with(['[', '{']) {
+                continue;
+            }
+            for (i, _) in json.char_indices().skip(1) {
+                assert!(parse(&json[..i]).is_err(), "{}", &json[..i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_random_input_never_panics() {
+        let alphabet = [
+            "{", "}", "[", "]", ",", ":", "\"", "\\", "u", "n", "t", "f", "e", "-", "+", ".",
+            "0", "9", " ", "\n", "a", "あ", "\u{1F600}", "true", "null", "\\u", "\\uD83D",
+        ];
+        let mut rng = Rng(0xDEADBEEFCAFEBABE);
+        for _ in 0..5000 {
+            let input: String = (0..rng.below(24)).map(|_| rng.pick(&alphabet)).collect();
+            let _ = parse(&input);
+        }
+    }
+
+    #[test]
+    fn test_parse_mutated_json_never_panics() {
+        let mut rng = Rng(0x0123456789ABCDEF);
+        for _ in 0..1000 {
+            let mut chars: Vec<char> = gen_json(&mut rng, 3).chars().collect();
+            for _ in 0..rng.below(3) + 1 {
+                let i = rng.below(chars.len());
+                match rng.below(3) {
+                    0 => { chars.remove(i); }
+                    1 => chars.insert(i, ['{', ']', '"', '\\', ',', 'x'][rng.below(6)]),
+                    _ => {
+                        let j = rng.below(chars.len());
+                        chars.swap(i, j);
+                    }
+                }
+                if chars.is_empty() {
+                    break;
+                }
+            }
+            let input: String = chars.into_iter().collect();
+            let _ = parse(&input);
+        }
+    }
+
+    #[test]
+    fn test_parse_unexpected_eof() {
+        for input in ["", "[", "{\"a\":", "[1,", "\"abc", "\"\\", "\"\\u30", "tr", "nul"] {
+            match parse(input) {
+                Err(JsonPretError::EofError(_)) => {}
+                other => panic!("{:?} => {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_deeply_nested() {
+        let json = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(matches!(parse(&json), Err(JsonPretError::ParserError(_))));
+
+        let json = format!("{}1{}", "[".repeat(200), "]".repeat(200));
+        assert!(parse(&json).is_ok());
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut value = parse(r#"{"s": "x", "n": -2.5, "b": true, "z": null, "a": [1], "o": {}}"#).unwrap();
+        assert_eq!(value["s"].as_str(), Some("x"));
+        assert_eq!(value["n"].as_f64(), Some(-2.5));
+        assert_eq!(value["n"].as_i64(), None);
+        assert_eq!(value["a"][0].as_u64(), Some(1));
+        assert_eq!(value["b"].as_bool(), Some(true));
+        assert!(value["z"].is_null());
+        assert_eq!(value["a"].as_array().map(Vec::len), Some(1));
+        assert!(value["o"].as_object().is_some_and(|m| m.is_empty()));
+
+        // 型が合わない場合は None
+        assert_eq!(value["s"].as_f64(), None);
+        assert_eq!(value["n"].as_str(), None);
+        assert!(value["a"].as_object().is_none());
+        assert!(!value["b"].is_string());
+
+        value.get_mut("a").and_then(|a| a.as_array_mut()).unwrap().push(JsonObject::Null);
+        value.as_object_mut().unwrap().remove("o");
+        let s = value.get_mut("s").unwrap().take();
+        assert_eq!(s.as_str(), Some("x"));
+        assert_eq!(
+            value,
+            parse(r#"{"s": null, "n": -2.5, "b": true, "z": null, "a": [1, null]}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_keeps_key_order() {
+        let json = r#"{"b": 1, "a": {"d": 2, "c": 3}}"#;
+
+        let obj = parse(json).unwrap();
+        let keys: Vec<&String> = match &obj {
+            JsonObject::Object(map) => map.keys().collect(),
+            _ => unreachable!(),
+        };
+        assert_eq!(keys, ["b", "a"]);
+
+        let options = ParseOptions { sort_keys: true, ..ParseOptions::default() };
+        let obj = parse_with_options(json, &options).unwrap();
+        match &obj["a"] {
+            JsonObject::Object(map) => assert_eq!(map.keys().collect::<Vec<_>>(), ["c", "d"]),
+            _ => unreachable!(),
+        }
+        match &obj {
+            JsonObject::Object(map) => assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "b"]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_json5() {
+        let json = r#"// 設定ファイル
+{
+    name: 'jp',
+    /* 16進数と先頭の + */
+    "mask": 0xFF, offset: +1,
+    ratio: .5, limits: [Infinity, -NaN,],
+}"#;
+        let options = ParseOptions { json5: true, ..ParseOptions::default() };
+        assert_eq!(
+            parse_with_options(json, &options).unwrap(),
+            parse_with_options(
+                r#"{"name": "jp", "mask": 255, "offset": 1, "ratio": 0.5, "limits": [Infinity, -NaN]}"#,
+                &options
+            )
+            .unwrap()
+        );
+        let value = parse_with_options("[+Infinity, -Infinity, NaN]", &options).unwrap();
+        // JSON として読み込める出力にする
+        assert_eq!(to_string(&value), "[null,null,null]");
+        assert!(parse(&to_string(&value)).is_ok());
+        let json5 = FormatOptions { non_finite: true, ..FormatOptions::compact() };
+        assert_eq!(to_string_with_options(&value, &json5), "[Infinity,-Infinity,NaN]");
+
+        let err = parse(json).unwrap_err();
+        assert_eq!(err.to_string(), "LexerError: an unexpected char / at line 1, column 1\n1 | // 設定ファイル\n  | ^");
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let mut rng = Rng(0x5851F42D4C957F2D);
+        for _ in 0..200 {
+            let json = gen_json(&mut rng, 4);
+            let reader = BufReader::with_capacity(3, json.as_bytes());
+            assert_eq!(parse_reader(reader), parse(&json), "{}", json);
+        }
+
+        let err = parse_reader("[1,\n  @]".as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "LexerError: an unexpected char @ at line 2, column 3\n2 |   @]\n  |   ^"
+        );
+    }
+}
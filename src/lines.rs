@@ -0,0 +1,204 @@
+//! 改行区切りの JSON (NDJSON / JSON Lines) と、RFC 7464 の JSON テキストシーケンスを1レコードずつ読み込む
+//!
+//! レコードごとにパースするため、壊れたレコードがあっても残りのレコードを読み続けられる。
+
+use std::io::BufRead;
+
+use crate::{
+    error::{IoError, JsonPretError},
+    parse_reader_with_options, JsonObject, ParseOptions,
+};
+
+/// RFC 7464 (JSON Text Sequences) のレコードの区切り文字
+const RECORD_SEPARATOR: u8 = 0x1E;
+
+/// 改行区切りの JSON (NDJSON / JSON Lines) を読み込み、1行ずつ `JsonObject` を返すイテレータ。
+///
+/// 各行は独立してパースされるので、ある行がエラーになっても次の行から読み続けられる。
+/// エラーの位置は入力全体での行番号になる。空白だけの行は読み飛ばす。
+/// I/O エラーが発生した場合は、そのエラーを返した後に終了する。
+///
+/// ```
+/// use json_prettier::JsonLines;
+///
+/// let input = "{\"a\": 1}\n\n[1,\n{\"b\": 2}\n";
+/// let records: Vec<_> = JsonLines::new(input.as_bytes()).collect();
+/// assert_eq!(records.len(), 3);
+/// assert_eq!(records[0].as_ref().unwrap()["a"].as_i64(), Some(1));
+/// assert_eq!(records[1].as_ref().unwrap_err().position().unwrap().line, 3);
+/// assert_eq!(records[2].as_ref().unwrap()["b"].as_i64(), Some(2));
+/// ```
+pub struct JsonLines<R> {
+    /// 読み込み元
+    reader: R,
+    /// 各レコードをパースする時の設定
+    options: ParseOptions,
+    /// RFC 7464 の形式で読み込むかどうか
+    seq: bool,
+    /// これまでに読み込んだ行数
+    lines: usize,
+    /// これまでに読み込んだバイト数
+    offset: usize,
+    /// 直前に区切り文字を読み込んだかどうか
+    after_separator: bool,
+    /// I/O エラーまたは入力の終わりに達したかどうか
+    done: bool,
+}
+
+impl<R: BufRead> JsonLines<R> {
+    pub fn new(reader: R) -> JsonLines<R> {
+        JsonLines::with_options(reader, ParseOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: ParseOptions) -> JsonLines<R> {
+        JsonLines {
+            reader,
+            options,
+            seq: false,
+            lines: 0,
+            offset: 0,
+            after_separator: false,
+            done: false,
+        }
+    }
+
+    /// `true` の場合、改行ではなく RFC 7464 の区切り文字(0x1E)で区切られたレコードとして読み込む
+    pub fn seq(mut self, seq: bool) -> JsonLines<R> {
+        self.seq = seq;
+        self
+    }
+
+    /// 次のレコードを読み込み、その内容と、その前までの行数とバイト数を返す
+    fn read_record(&mut self) -> Result<Option<(Vec<u8>, usize, usize)>, JsonPretError> {
+        loop {
+            let mut record: Vec<u8> = vec![];
+            let lines: usize = self.lines;
+            let mut offset: usize = self.offset;
+            // 列番号がずれないよう、レコードの前の区切り文字は空白に置き換える
+            if self.after_separator {
+                record.push(b' ');
+                offset -= 1;
+            }
+            let delimiter: u8 = if self.seq { RECORD_SEPARATOR } else { b'\n' };
+            let n: usize = match self.reader.read_until(delimiter, &mut record) {
+                Ok(n) => n,
+                Err(e) => return Err(JsonPretError::IoError(IoError::from(e))),
+            };
+            if n == 0 {
+                return Ok(None);
+            }
+            self.offset += n;
+            self.lines += record.iter().filter(|b| **b == b'\n').count();
+            self.after_separator = self.seq && record.last() == Some(&RECORD_SEPARATOR);
+            // 区切り文字はレコードに含めない
+            if record.last() == Some(&delimiter) {
+                record.pop();
+            }
+
+            if !record.iter().all(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r')) {
+                return Ok(Some((record, lines, offset)));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonLines<R> {
+    type Item = Result<JsonObject, JsonPretError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_record() {
+            Ok(Some((record, lines, offset))) => Some(
+                parse_reader_with_options(record.as_slice(), &self.options)
+                    .map_err(|e| e.shift_position(lines, offset)),
+            ),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufRead, Read};
+
+    use crate::{lines::JsonLines, position::Position, JsonNumber, JsonObject, JsonPretError};
+
+    fn number(n: i64) -> JsonObject {
+        JsonObject::Number(JsonNumber::from(n))
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let input = "1\r\n  \n{\"a\":\n[true, \"\u{3042}\"]\n\"x\"";
+        let records: Vec<Result<JsonObject, JsonPretError>> = JsonLines::new(input.as_bytes()).collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0], Ok(number(1)));
+        let err = records[1].as_ref().unwrap_err();
+        assert_eq!(err.position(), Some(Position::new(3, 6, 11)));
+        assert_eq!(
+            err.to_string(),
+            "EofError: unexpected end of input at line 3, column 6\n3 | {\"a\":\n  |      ^"
+        );
+        assert_eq!(records[2].as_ref().unwrap()[1], JsonObject::String("\u{3042}".to_string()));
+        assert_eq!(records[3], Ok(JsonObject::String("x".to_string())));
+    }
+
+    #[test]
+    fn test_json_lines_seq() {
+        let input = "\u{1E}{\"a\": 1}\n\u{1E}\n\u{1E}[1,\n 2 3]\n\u{1E}null\n";
+        let records: Vec<Result<JsonObject, JsonPretError>> =
+            JsonLines::new(input.as_bytes()).seq(true).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].as_ref().unwrap()["a"], number(1));
+        let err = records[1].as_ref().unwrap_err();
+        assert_eq!(err.position(), Some(Position::new(4, 4, 20)));
+        assert_eq!(err.source_line(), Some(" 2 3]"));
+        assert_eq!(records[2], Ok(JsonObject::Null));
+
+        // 区切り文字の前の空白は読み飛ばす
+        let records: Vec<_> = JsonLines::new(" \u{1E}1\n".as_bytes()).seq(true).collect();
+        assert_eq!(records, vec![Ok(number(1))]);
+    }
+
+    /// 2回目以降の読み込みで失敗する reader
+    struct FailingReader(bool);
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            unreachable!()
+        }
+    }
+
+    impl BufRead for FailingReader {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            if self.0 {
+                Err(io::Error::other("broken"))
+            } else {
+                Ok(b"1\n")
+            }
+        }
+
+        fn consume(&mut self, _: usize) {
+            self.0 = true;
+        }
+    }
+
+    #[test]
+    fn test_json_lines_io_error() {
+        let mut lines = JsonLines::new(FailingReader(false));
+        assert_eq!(lines.next(), Some(Ok(number(1))));
+        assert!(matches!(lines.next(), Some(Err(JsonPretError::IoError(_)))));
+        assert_eq!(lines.next(), None);
+    }
+}
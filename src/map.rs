@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    ops::Index,
+    slice,
+    vec,
+};
+
+use crate::JsonObject;
+
+/// キーの挿入順を保持する JSON Object のキーと値の組
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    /// 挿入順に並んだキーと値
+    entries: Vec<(String, JsonObject)>,
+    /// キーから `entries` の位置への索引
+    indices: HashMap<String, usize>,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Map {
+        Map {
+            entries: Vec::with_capacity(capacity),
+            indices: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonObject> {
+        self.indices.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JsonObject> {
+        match self.indices.get(key) {
+            Some(&i) => Some(&mut self.entries[i].1),
+            None => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// キーと値を追加する。
+    /// 既にキーが存在する場合は、位置はそのままで値を置き換え、古い値を返す。
+    pub fn insert(&mut self, key: String, value: JsonObject) -> Option<JsonObject> {
+        match self.indices.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// キーを削除し、その値を返す。後ろのキーの順序は保たれる。
+    pub fn remove(&mut self, key: &str) -> Option<JsonObject> {
+        let i: usize = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (k, _) in &self.entries[i..] {
+            if let Some(index) = self.indices.get_mut(k) {
+                *index -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    /// キーに対応する `Entry` を返す。値の有無に応じて追加や変更ができる。
+    ///
+    /// ```
+    /// use json_prettier::{JsonNumber, JsonObject, Map};
+    ///
+    /// let mut map = Map::new();
+    /// for key in ["a", "b", "a"] {
+    ///     let count = map.entry(key).or_insert(JsonObject::Number(JsonNumber::from(0)));
+    ///     let n = count.as_i64().unwrap();
+    ///     *count = JsonObject::Number(JsonNumber::from(n + 1));
+    /// }
+    /// assert_eq!(map["a"].as_i64(), Some(2));
+    /// ```
+    pub fn entry(&mut self, key: impl Into<String>) -> Entry<'_> {
+        let key: String = key.into();
+        match self.indices.get(&key) {
+            Some(&index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry { map: self, key }),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+    }
+
+    /// キーを辞書順に並べ替える
+    pub fn sort_keys(&mut self) {
+        self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.reindex();
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { inner: self.entries.iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut { inner: self.entries.iter_mut() }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &String> + ExactSizeIterator {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &JsonObject> + ExactSizeIterator {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut JsonObject> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+
+    /// `entries` から索引を作り直す
+    fn reindex(&mut self) {
+        self.indices.clear();
+        for (i, (k, _)) in self.entries.iter().enumerate() {
+            self.indices.insert(k.clone(), i);
+        }
+    }
+}
+
+/// キーの順序は比較しない
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl Index<&str> for Map {
+    type Output = JsonObject;
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key)
+            .unwrap_or_else(|| panic!("A key is not found: {}", key))
+    }
+}
+
+impl FromIterator<(String, JsonObject)> for Map {
+    fn from_iter<I: IntoIterator<Item = (String, JsonObject)>>(iter: I) -> Map {
+        let mut map: Map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl Extend<(String, JsonObject)> for Map {
+    fn extend<I: IntoIterator<Item = (String, JsonObject)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (String, JsonObject);
+    type IntoIter = vec::IntoIter<(String, JsonObject)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Map {
+    type Item = (&'a String, &'a JsonObject);
+    type IntoIter = Iter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Map {
+    type Item = (&'a String, &'a mut JsonObject);
+    type IntoIter = IterMut<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// `Map::entry` が返す、キーに対応する場所
+pub enum Entry<'a> {
+    /// キーが無い
+    Vacant(VacantEntry<'a>),
+    /// キーがある
+    Occupied(OccupiedEntry<'a>),
+}
+
+impl<'a> Entry<'a> {
+    pub fn key(&self) -> &str {
+        match self {
+            Entry::Vacant(e) => e.key(),
+            Entry::Occupied(e) => e.key(),
+        }
+    }
+
+    /// キーが無い場合は `default` を追加し、値への参照を返す
+    pub fn or_insert(self, default: JsonObject) -> &'a mut JsonObject {
+        match self {
+            Entry::Vacant(e) => e.insert(default),
+            Entry::Occupied(e) => e.into_mut(),
+        }
+    }
+
+    /// キーが無い場合は `f` の結果を追加し、値への参照を返す
+    pub fn or_insert_with<F: FnOnce() -> JsonObject>(self, f: F) -> &'a mut JsonObject {
+        match self {
+            Entry::Vacant(e) => e.insert(f()),
+            Entry::Occupied(e) => e.into_mut(),
+        }
+    }
+
+    /// キーがある場合は値を `f` で変更する
+    pub fn and_modify<F: FnOnce(&mut JsonObject)>(mut self, f: F) -> Entry<'a> {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+}
+
+/// `Map` に無いキーの `Entry`
+pub struct VacantEntry<'a> {
+    map: &'a mut Map,
+    key: String,
+}
+
+impl<'a> VacantEntry<'a> {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// 末尾に値を追加し、その値への参照を返す
+    pub fn insert(self, value: JsonObject) -> &'a mut JsonObject {
+        let index: usize = self.map.entries.len();
+        self.map.indices.insert(self.key.clone(), index);
+        self.map.entries.push((self.key, value));
+        &mut self.map.entries[index].1
+    }
+}
+
+/// `Map` にあるキーの `Entry`
+pub struct OccupiedEntry<'a> {
+    map: &'a mut Map,
+    index: usize,
+}
+
+impl<'a> OccupiedEntry<'a> {
+    pub fn key(&self) -> &str {
+        &self.map.entries[self.index].0
+    }
+
+    pub fn get(&self) -> &JsonObject {
+        &self.map.entries[self.index].1
+    }
+
+    pub fn get_mut(&mut self) -> &mut JsonObject {
+        &mut self.map.entries[self.index].1
+    }
+
+    /// `Entry` より長く使える値への参照を返す
+    pub fn into_mut(self) -> &'a mut JsonObject {
+        &mut self.map.entries[self.index].1
+    }
+
+    /// 値を置き換え、古い値を返す
+    pub fn insert(&mut self, value: JsonObject) -> JsonObject {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// キーを削除し、その値を返す
+    pub fn remove(self) -> JsonObject {
+        let key: String = self.key().to_string();
+        // remove は必ず値を返す
+        self.map.remove(&key).unwrap_or_default()
+    }
+}
+
+/// `Map` のキーと値を挿入順に返すイテレータ
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (String, JsonObject)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a JsonObject);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, v)| (k, v))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+/// `Map` のキーと変更可能な値を挿入順に返すイテレータ
+pub struct IterMut<'a> {
+    inner: slice::IterMut<'a, (String, JsonObject)>,
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a String, &'a mut JsonObject);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, v)| (&*k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IterMut<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, v)| (&*k, v))
+    }
+}
+
+impl ExactSizeIterator for IterMut<'_> {}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{map::{Entry, Map}, JsonNumber, JsonObject};
+
+    fn sample() -> Map {
+        let mut map = Map::new();
+        map.insert("b".to_string(), JsonObject::Number(JsonNumber::from(1)));
+        map.insert("a".to_string(), JsonObject::Null);
+        map.insert("c".to_string(), JsonObject::Bool(true));
+        map
+    }
+
+    #[test]
+    fn test_insert_keeps_order() {
+        let mut map = sample();
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["b", "a", "c"]);
+
+        // 既存のキーは位置を変えずに値を置き換える
+        let old = map.insert("b".to_string(), JsonObject::Number(JsonNumber::from(2)));
+        assert_eq!(old, Some(JsonObject::Number(JsonNumber::from(1))));
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["b", "a", "c"]);
+        assert_eq!(map["b"], JsonObject::Number(JsonNumber::from(2)));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut map = sample();
+        assert_eq!(map.remove("b"), Some(JsonObject::Number(JsonNumber::from(1))));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "c"]);
+        assert_eq!(map.get("c"), Some(&JsonObject::Bool(true)));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let mut map = sample();
+        map.sort_keys();
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(map.get("b"), Some(&JsonObject::Number(JsonNumber::from(1))));
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map = sample();
+
+        // 無いキーは末尾に追加される
+        map.entry("d").or_insert(JsonObject::Bool(false));
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["b", "a", "c", "d"]);
+
+        // あるキーは変更されない
+        map.entry("b").or_insert_with(|| unreachable!());
+        assert_eq!(map["b"], JsonObject::Number(JsonNumber::from(1)));
+
+        map.entry("a")
+            .and_modify(|v| *v = JsonObject::Bool(true))
+            .or_insert(JsonObject::Null);
+        assert_eq!(map["a"], JsonObject::Bool(true));
+
+        match map.entry("c") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), "c");
+                assert_eq!(e.insert(JsonObject::Null), JsonObject::Bool(true));
+                assert_eq!(e.remove(), JsonObject::Null);
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["b", "a", "d"]);
+        assert_eq!(map.get("d"), Some(&JsonObject::Bool(false)));
+    }
+
+    #[test]
+    fn test_eq_ignores_order() {
+        let mut sorted = sample();
+        sorted.sort_keys();
+        assert_eq!(sorted, sample());
+
+        let mut other = sample();
+        other.insert("d".to_string(), JsonObject::Null);
+        assert_ne!(other, sample());
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt::{self, Display};
+
+use crate::lexer::is_valid_number;
+
+/// JSON の数値
+///
+/// 入力された文字列をそのまま保持するので、f64 で表せない桁数の整数や
+/// `1.0` のような表記も失われない。比較は文字列として行う。
+///
+/// JSON5 として読み込んだ `Infinity`, `-Infinity`, `NaN` も、そのままの表記で保持する。
+/// これらは JSON では表せないので、`FormatOptions::non_finite` を指定しない限り `null` として書き出す。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonNumber {
+    /// RFC 8259 の number の文法に従った文字列、または JSON5 の `Infinity` と `NaN`
+    lexeme: String,
+}
+
+impl JsonNumber {
+    /// `s` が JSON の number として正しければ `JsonNumber` を返す
+    pub fn from_lexeme(s: &str) -> Option<JsonNumber> {
+        if is_valid_number(s) {
+            Some(JsonNumber { lexeme: s.to_string() })
+        } else {
+            None
+        }
+    }
+
+    /// 文法を確認済みの文字列から `JsonNumber` を作る
+    pub(crate) fn from_valid_lexeme(lexeme: String) -> JsonNumber {
+        JsonNumber { lexeme }
+    }
+
+    /// 有限の f64 から `JsonNumber` を作る。NaN と無限大の場合は `None` を返す。
+    pub fn from_f64(f: f64) -> Option<JsonNumber> {
+        if f.is_finite() {
+            // Debug 表記は 1.0 や 1e21 のように JSON として正しい形式になる
+            Some(JsonNumber { lexeme: format!("{:?}", f) })
+        } else {
+            None
+        }
+    }
+
+    /// JSON5 の符号付きを含む `Infinity` または `NaN` から `JsonNumber` を作る。先頭の `+` は取り除く。
+    pub(crate) fn from_non_finite(name: &str) -> JsonNumber {
+        JsonNumber { lexeme: name.strip_prefix('+').unwrap_or(name).to_string() }
+    }
+
+    /// 入力された表記のまま返す
+    pub fn as_str(&self) -> &str {
+        &self.lexeme
+    }
+
+    /// `Infinity` と `NaN` ではないかどうかを返す
+    pub fn is_finite(&self) -> bool {
+        // RFC 8259 の number は必ず数字で終わる
+        self.lexeme.ends_with(|c: char| c.is_ascii_digit())
+    }
+
+    /// 小数点と指数を含まない整数の表記かどうかを返す
+    pub fn is_integer(&self) -> bool {
+        self.is_finite() && !self.lexeme.contains(['.', 'e', 'E'])
+    }
+
+    /// i64 で表せる整数の場合は i64 を返す
+    pub fn as_i64(&self) -> Option<i64> {
+        if self.is_integer() {
+            self.lexeme.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    /// u64 で表せる整数の場合は u64 を返す
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.is_integer() {
+            self.lexeme.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    /// f64 に変換する。f64 の範囲を超える場合は `None` を返す。
+    /// 桁数の多い整数などは丸められることがある。
+    pub fn as_f64(&self) -> Option<f64> {
+        self.lexeme.parse::<f64>().ok().filter(|f| f.is_finite())
+    }
+}
+
+impl Display for JsonNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.lexeme)
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for JsonNumber {
+                fn from(n: $t) -> JsonNumber {
+                    JsonNumber { lexeme: n.to_string() }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::number::JsonNumber;
+
+    #[test]
+    fn test_from_lexeme() {
+        assert_eq!(JsonNumber::from_lexeme("1.0").unwrap().as_str(), "1.0");
+        assert_eq!(JsonNumber::from_lexeme("-0").unwrap().as_str(), "-0");
+        assert!(JsonNumber::from_lexeme("01").is_none());
+        assert!(JsonNumber::from_lexeme("NaN").is_none());
+    }
+
+    #[test]
+    fn test_from_f64() {
+        assert_eq!(JsonNumber::from_f64(1.0).unwrap().as_str(), "1.0");
+        assert_eq!(JsonNumber::from_f64(-2.5e-8).unwrap().as_str(), "-2.5e-8");
+        assert_eq!(JsonNumber::from_f64(1e21).unwrap().as_str(), "1e21");
+        assert!(JsonNumber::from_f64(f64::NAN).is_none());
+        assert!(JsonNumber::from_f64(f64::INFINITY).is_none());
+
+        let n = JsonNumber::from_f64(0.1).unwrap();
+        assert_eq!(n.as_f64(), Some(0.1));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let n = JsonNumber::from_lexeme("9007199254740993").unwrap();
+        assert_eq!(n.as_i64(), Some(9007199254740993));
+        assert_eq!(n.as_u64(), Some(9007199254740993));
+        assert_eq!(n.to_string(), "9007199254740993");
+
+        let n = JsonNumber::from_lexeme("18446744073709551615").unwrap();
+        assert_eq!(n.as_i64(), None);
+        assert_eq!(n.as_u64(), Some(u64::MAX));
+
+        let n = JsonNumber::from_lexeme("-12").unwrap();
+        assert_eq!(n.as_i64(), Some(-12));
+        assert_eq!(n.as_u64(), None);
+        assert_eq!(n.as_f64(), Some(-12.0));
+
+        let n = JsonNumber::from_lexeme("1.5e3").unwrap();
+        assert!(!n.is_integer());
+        assert_eq!(n.as_i64(), None);
+        assert_eq!(n.as_f64(), Some(1500.0));
+
+        let n = JsonNumber::from_lexeme("1e400").unwrap();
+        assert_eq!(n.as_f64(), None);
+        assert_eq!(n.to_string(), "1e400");
+        assert!(n.is_finite());
+
+        for (name, lexeme) in [("+Infinity", "Infinity"), ("-Infinity", "-Infinity"), ("NaN", "NaN")] {
+            let n = JsonNumber::from_non_finite(name);
+            assert_eq!(n.to_string(), lexeme);
+            assert!(!n.is_finite());
+            assert!(!n.is_integer());
+            assert_eq!(n.as_f64(), None);
+        }
+    }
+
+    #[test]
+    fn test_from_integer() {
+        assert_eq!(JsonNumber::from(42u8).as_str(), "42");
+        assert_eq!(JsonNumber::from(-7i64).as_str(), "-7");
+        assert_eq!(JsonNumber::from(u64::MAX).as_u64(), Some(u64::MAX));
+    }
+}
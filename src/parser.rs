@@ -1,95 +1,139 @@
-use std::collections::BTreeMap;
+use std::io::BufRead;
 
 use crate::{
-    error::{JsonPretError, ParserError},
-    lexer::Token,
-    JsonObject
+    error::{EofError, JsonPretError, ParserError},
+    lexer::{is_non_finite, Lexer, SpannedToken, Token},
+    map::Map,
+    position::Position,
+    JsonNumber,
+    JsonObject,
+    ParseOptions,
 };
 
-pub struct Parser {
-    /// `Lexer`で`tokenize`した`Token`一覧
-    tokens: Vec<Token>,
-    /// `tokens`の先頭
-    index: usize,
+/// Array と Object の入れ子の最大の深さ
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/// `Lexer` から必要な分だけ `Token` を読み込み、`JsonObject` を組み立てる
+pub struct Parser<R> {
+    /// `Token` の読み込み元
+    lexer: Lexer<R>,
+    /// 先読みした `Token`
+    peeked: Option<SpannedToken>,
+    /// 直前に読んだ `Token` の開始位置
+    previous: Position,
+    /// 現在の Array と Object の入れ子の深さ
+    depth: usize,
+    /// パース時の設定
+    options: ParseOptions,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, index: 0 }
+impl<R: BufRead> Parser<R> {
+    /// `options.json5` の場合は `lexer` も JSON5 の文法を受け付けるようにする
+    pub fn new(lexer: Lexer<R>, options: ParseOptions) -> Parser<R> {
+        let json5: bool = lexer.is_json5() || options.json5;
+        Parser {
+            lexer: lexer.json5(json5),
+            peeked: None,
+            previous: Position::default(),
+            depth: 0,
+            options,
+        }
+    }
+
+    /// 1つの JSON 値をパースし、その後に `Token` が残っていないことを確認する
+    pub fn parse_root(&mut self) -> Result<JsonObject, JsonPretError> {
+        let value: JsonObject = self.parse()?;
+        match self.peek_token()? {
+            Some(t) => {
+                let msg: String = format!("an unexpected token '{}' after the root value", t.token);
+                Err(self.error(&msg))
+            }
+            None => Ok(value),
+        }
+    }
+
+    /// 連結された複数の JSON 値を先頭から順にパースする。
+    /// 値の間の空白は無くてもよく、入力の終わりでは `None` を返す。
+    pub fn parse_next(&mut self) -> Result<Option<JsonObject>, JsonPretError> {
+        match self.peek_token()? {
+            Some(_) => self.parse().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// 直前にパースした値の直後の位置を返す
+    pub fn position(&self) -> Position {
+        match &self.peeked {
+            Some(t) => t.span.start,
+            None => self.lexer.position(),
+        }
     }
 
     pub fn parse(&mut self) -> Result<JsonObject, JsonPretError>{
-        let peeked_token = match self.peek() {
-            Ok(t) => t.clone(),
-            Err(e) => return Err(e),
-        };
+        let peeked_token: Token = self.peek()?.clone();
 
         match peeked_token {
-            Token::LeftBrace => self.parse_object(),
-            Token::LeftBracket => self.parse_array(),
+            Token::LeftBrace => self.nested(Parser::parse_object),
+            Token::LeftBracket => self.nested(Parser::parse_array),
             Token::Bool(b) => {
-                match self.next() {
-                    Ok(_) => Ok(JsonObject::Bool(b)),
-                    Err(e) => return Err(e)
-                }
+                self.next()?;
+                Ok(JsonObject::Bool(b))
             }
             Token::Null => {
-                match self.next() {
-                    Ok(_) => Ok(JsonObject::Null),
-                    Err(e) => return Err(e)
-                }                
+                self.next()?;
+                Ok(JsonObject::Null)
             }
             Token::Number(n) => {
-                match self.next() {
-                    Ok(_) => Ok(JsonObject::Number(n)),
-                    Err(e) => return Err(e)
-                }
+                self.next()?;
+                Ok(JsonObject::Number(n))
             }
             Token::String(s) => {
-                match self.next(){
-                    Ok(_) => Ok(JsonObject::String(s)),
-                    Err(e) => return Err(e)
-                }
+                self.next()?;
+                Ok(JsonObject::String(s))
             },
-            _ => return Err(JsonPretError::ParserError(
-                ParserError::new(&format!(
-                    "token must start {{ or [ or String or Number or Bool or Null, but start '{:?}'",
-                    peeked_token
-                ))
-            ))
+            Token::Identifier(name) if is_non_finite(&name) => {
+                self.next()?;
+                Ok(JsonObject::Number(JsonNumber::from_non_finite(&name)))
+            }
+            _ => Err(self.error(&format!(
+                "token must start {{ or [ or String or Number or Bool or Null, but start '{}'",
+                peeked_token
+            )))
         }
     }
 
     fn parse_array(&mut self) -> Result<JsonObject, JsonPretError>{
-        let token = match self.next() {
-            Ok(t) => t.clone(),
-            Err(e) => return Err(e)
-        };
+        let token: Token = self.next()?;
 
         if token != Token::LeftBracket {
-            return Err(JsonPretError::ParserError(
-                ParserError::new(&format!("JSON Array must start [ but start {:?}", token))
+            return Err(self.error_at_previous(
+                &format!("JSON Array must start [ but start {}", token)
             ))
         }
 
         let mut array: Vec<JsonObject> = vec![];
 
+        // 空の Array
+        if *self.peek()? == Token::RightBracket {
+            self.next()?;
+            return Ok(JsonObject::Array(array));
+        }
+
         loop {
-            match self.parse() {
-                Ok(v) => array.push(v),
-                Err(e) => return Err(e)
-            };
+            array.push(self.parse()?);
 
-            let token = match self.next() {
-                Ok(t) => t,
-                Err(e) => return Err(e),
-            };
+            let token: Token = self.next()?;
 
             match token {
                 Token::RightBracket => break,
+                // JSON5 では最後の要素の後の , を許す
+                Token::Comma if self.lexer.is_json5() && *self.peek()? == Token::RightBracket => {
+                    self.next()?;
+                    break;
+                }
                 Token::Comma => continue,
-                _ => return Err(JsonPretError::ParserError(
-                    ParserError::new(&format!("a ']' or ',' is expected, but '{:?}' is inputed", token))
+                _ => return Err(self.error_at_previous(
+                    &format!("a ']' or ',' is expected, but '{}' is inputed", token)
                 ))
             }
         }
@@ -98,121 +142,212 @@ impl Parser {
     }
 
     fn parse_object(&mut self) -> Result<JsonObject, JsonPretError>{
-        let token = match self.next() {
-            Ok(t) => t.clone(),
-            Err(e) => return Err(e),
-        };
+        let token: Token = self.next()?;
 
         if token != Token::LeftBrace {
-            return Err(JsonPretError::ParserError(
-                ParserError::new(&format!("JSON object must start {{ but start {:?}", token))
+            return Err(self.error_at_previous(
+                &format!("JSON object must start {{ but start {}", token)
             ))
         }
 
-        let mut obj: BTreeMap<String, JsonObject> = BTreeMap::new();
+        let mut obj: Map = Map::new();
 
+        let mut after_comma: bool = false;
         loop {
-            let t1: Token  = match self.next() {
-                Ok(t) => {
-                    if *t == Token::RightBrace {
-                        break;
-                    } else {
-                        t.clone()
-                    }
-                },
-                Err(e) => return Err(e)
-            };
+            let t1: Token = self.next()?;
+            if t1 == Token::RightBrace && (!after_comma || self.lexer.is_json5()) {
+                break;
+            }
 
-            let t2: Token  = match self.next() {
-                Ok(t) => t.clone(),
-                Err(e) => return Err(e)
-            };
+            let t2: Token = self.next()?;
 
-            match (t1, t2) {
-                (Token::String(key), Token::Colon) => obj.insert(key, self.parse().unwrap()),
-                _ => return Err(JsonPretError::ParserError(
-                    ParserError::new("a pair 'String(key)' and ':' is expected.")
+            match (t1.into_key(self.lexer.is_json5()), t2) {
+                (Some(key), Token::Colon) => obj.insert(key, self.parse()?),
+                _ => return Err(self.error_at_previous(
+                    "a pair 'String(key)' and ':' is expected."
                 ))
             };
 
-            match self.next() {
-                Ok(t) => {
-                    match *t {
-                        Token::RightBrace => break,
-                        Token::Comma => continue,
-                        _ => return Err(JsonPretError::ParserError(
-                            ParserError::new(&format!(
-                                "{{ or , is expected, but {:?} is inputed",
-                                t
-                            ))
-                        ))
-                    }
-                }
-                Err(e) => return Err(e)
+            let token: Token = self.next()?;
+            match token {
+                Token::RightBrace => break,
+                Token::Comma => after_comma = true,
+                _ => return Err(self.error_at_previous(&format!(
+                    "{{ or , is expected, but {} is inputed",
+                    token
+                )))
             }
         }
 
+        if self.options.sort_keys {
+            obj.sort_keys();
+        }
         Ok(JsonObject::Object(obj))
     }
 
+    /// 入れ子の深さを数えながら Array または Object をパースする
+    fn nested(
+        &mut self,
+        parse: fn(&mut Parser<R>) -> Result<JsonObject, JsonPretError>
+    ) -> Result<JsonObject, JsonPretError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(&format!(
+                "the nesting depth exceeds the limit of {}",
+                MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// 次の `Token` を読み進めずに返す。入力の終わりでは `None` を返す。
+    fn peek_token(&mut self) -> Result<Option<&SpannedToken>, JsonPretError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.read_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
     fn peek(&mut self) -> Result<&Token, JsonPretError> {
-        match self.tokens.get(self.index) {
-            Some(t) => Ok(t),
-            None => Err(JsonPretError::ParserError(
-                ParserError::new("a token isn't peekable")
-            ))
+        if self.peek_token()?.is_none() {
+            return Err(self.eof_error());
+        }
+        match &self.peeked {
+            Some(t) => Ok(&t.token),
+            None => unreachable!(),
         }
     }
 
-    fn next(&mut self) -> Result<&Token, JsonPretError> {
-        self.index += 1;
-        match self.tokens.get(self.index-1) {
-            Some(t) => Ok(t),
-            None => Err(JsonPretError::ParserError(
-                ParserError::new("a token isn't peekable")
-            ))
+    fn next(&mut self) -> Result<Token, JsonPretError> {
+        self.peek_token()?;
+        match self.peeked.take() {
+            Some(t) => {
+                self.previous = t.span.start;
+                Ok(t.token)
+            }
+            None => Err(self.eof_error()),
         }
     }
 
+    /// 次に読む `Token` の位置で ParserError を作る
+    fn error(&mut self, msg: &str) -> JsonPretError {
+        let position: Position = match &self.peeked {
+            Some(t) => t.span.start,
+            None => self.lexer.position(),
+        };
+        self.lexer.attach_source(JsonPretError::ParserError(ParserError::new(msg, position)))
+    }
+
+    /// 入力の終端で EofError を作る
+    fn eof_error(&mut self) -> JsonPretError {
+        let position: Position = self.lexer.position();
+        self.lexer.attach_source(JsonPretError::EofError(EofError::new(
+            "unexpected end of input",
+            position
+        )))
+    }
+
+    /// 直前に読んだ `Token` の位置で ParserError を作る
+    fn error_at_previous(&mut self, msg: &str) -> JsonPretError {
+        let position: Position = self.previous;
+        self.lexer.attach_source(JsonPretError::ParserError(ParserError::new(msg, position)))
+    }
+
+}
+
+/// 連結された複数の JSON 値(`{"a":1}{"b":2}` や空白区切りの値)を読み込み、
+/// 1つずつ `JsonObject` を返すイテレータ。
+///
+/// 入力全体をメモリに読み込まず、必要な分だけ読み進める。
+/// エラーが発生した場合は、そのエラーを返した後に終了する。
+///
+/// ```
+/// use json_prettier::JsonStream;
+///
+/// let mut stream = JsonStream::new(r#"{"a":1}{"b":2} 3"#.as_bytes());
+/// assert_eq!(stream.next().unwrap().unwrap()["a"].as_i64(), Some(1));
+/// assert_eq!(stream.position().offset, 7);
+/// assert_eq!(stream.next().unwrap().unwrap()["b"].as_i64(), Some(2));
+/// assert_eq!(stream.next().unwrap().unwrap().as_i64(), Some(3));
+/// assert!(stream.next().is_none());
+/// ```
+pub struct JsonStream<R> {
+    parser: Parser<R>,
+    /// エラーまたは入力の終わりに達したかどうか
+    done: bool,
+}
+
+impl<R: BufRead> JsonStream<R> {
+    pub fn new(reader: R) -> JsonStream<R> {
+        JsonStream::with_options(reader, ParseOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: ParseOptions) -> JsonStream<R> {
+        JsonStream {
+            parser: Parser::new(Lexer::from_reader(reader), options),
+            done: false,
+        }
+    }
+
+    /// 直前に返した値の直後の位置を返す
+    pub fn position(&self) -> Position {
+        self.parser.position()
+    }
+}
+
+impl<R: BufRead> Iterator for JsonStream<R> {
+    type Item = Result<JsonObject, JsonPretError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result: Result<Option<JsonObject>, JsonPretError> = self.parser.parse_next();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
-    use crate::{lexer::{Lexer, Token}, JsonObject};
-    use super::Parser;
+    use crate::{
+        lexer::{Lexer, Token},
+        map::Map,
+        position::Position,
+        JsonNumber,
+        JsonObject,
+        ParseOptions,
+    };
+    use super::{JsonStream, Parser};
 
     #[test]
     fn test_parser_new() {
-        let expect: Vec<Token> = vec![
-            Token::LeftBrace,
-            Token::String("is_test".to_string()),
-            Token::Bool(true),
-            Token::RightBrace
-        ];
-
-        let parser: Parser = Parser::new(vec![
-            Token::LeftBrace,
-            Token::String("is_test".to_string()),
-            Token::Bool(true),
-            Token::RightBrace
-        ]);
-        assert_eq!(parser.tokens, expect);
-        assert_eq!(parser.index, 0);
+        let mut parser = Parser::new(Lexer::new(r#"{"is_test": true}"#), ParseOptions::default());
+        assert!(parser.peeked.is_none());
+        assert_eq!(parser.depth, 0);
+
+        // Token は必要になった時に読み込まれる
+        assert_eq!(parser.peek().unwrap(), &Token::LeftBrace);
+        assert_eq!(parser.lexer.position(), Position::new(1, 2, 1));
+        assert_eq!(parser.next().unwrap(), Token::LeftBrace);
+        assert_eq!(parser.next().unwrap(), Token::String("is_test".to_string()));
     }
 
     #[test]
     fn test_parse_object() {
-        let mut obj = BTreeMap::new();
+        let mut obj = Map::new();
         obj.insert(
             "key".to_string(),
             JsonObject::String("JsonObject".to_string())
         );
         let expect = JsonObject::Object(obj);
 
-        let mut lexer = Lexer::new(r#"{"key" : "JsonObject"}"#);
-        let tokens = lexer.lexical_analyze().unwrap();
-        let mut parser = Parser::new(tokens);
+        let lexer = Lexer::new(r#"{"key" : "JsonObject"}"#);
+        let mut parser = Parser::new(lexer, ParseOptions::default());
         let actual = parser.parse_object().unwrap();
 
         assert_eq!(actual, expect);
@@ -222,14 +357,13 @@ mod tests {
     fn test_parse_array() {
         let expect: JsonObject = JsonObject::Array(vec![
             JsonObject::Null,
-            JsonObject::Number(1.0),
+            JsonObject::Number(JsonNumber::from(1)),
             JsonObject::Bool(true),
             JsonObject::String("test".to_string()),
         ]);
 
-        let mut lexer = Lexer::new(r#"[null, 1, true, "test"]"#);
-        let tokens = lexer.lexical_analyze().unwrap();
-        let mut parser = Parser::new(tokens);
+        let lexer = Lexer::new(r#"[null, 1, true, "test"]"#);
+        let mut parser = Parser::new(lexer, ParseOptions::default());
         let actual = parser.parse_array().unwrap();
 
         assert_eq!(actual, expect)
@@ -238,24 +372,141 @@ mod tests {
     #[test]
     fn test_parse() {
         let json = r#"{"key" : [1, "JsonObject"]}"#;
-        let json_obj = Parser::new(Lexer::new(json).lexical_analyze().unwrap())
+        let json_obj = Parser::new(Lexer::new(json), ParseOptions::default())
             .parse()
             .unwrap();
-        let mut object = BTreeMap::new();
+        let mut object = Map::new();
         object.insert(
             "key".to_string(),
-            JsonObject::Array(vec![JsonObject::Number(1.0), JsonObject::String("JsonObject".to_string())]),
+            JsonObject::Array(vec![JsonObject::Number(JsonNumber::from(1)), JsonObject::String("JsonObject".to_string())]),
         );
         assert_eq!(json_obj, JsonObject::Object(object));
 
         let json = r#"[{"key": "JsonObject"}]"#;
-        let json_obj = Parser::new(Lexer::new(json).lexical_analyze().unwrap())
+        let json_obj = Parser::new(Lexer::new(json), ParseOptions::default())
             .parse()
             .unwrap();
-        let mut object = BTreeMap::new();
+        let mut object = Map::new();
         object.insert("key".to_string(), JsonObject::String("JsonObject".to_string()));
 
         let array = JsonObject::Array(vec![JsonObject::Object(object)]);
         assert_eq!(json_obj, array);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_error_position() {
+        let json = "{\n  \"a\": 1\n  \"b\": 2\n}";
+        let err = Parser::new(Lexer::new(json), ParseOptions::default())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.position(), Some(Position::new(3, 3, 13)));
+
+        let err = Parser::new(Lexer::new("[1,"), ParseOptions::default())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.position(), Some(Position::new(1, 4, 3)));
+    }
+
+    #[test]
+    fn test_parse_root() {
+        let json_obj = Parser::new(Lexer::new("[1] "), ParseOptions::default())
+            .parse_root()
+            .unwrap();
+        assert_eq!(json_obj, JsonObject::Array(vec![JsonObject::Number(JsonNumber::from(1))]));
+
+        let err = Parser::new(Lexer::new("[1] 2"), ParseOptions::default())
+            .parse_root()
+            .unwrap_err();
+        assert_eq!(err.position(), Some(Position::new(1, 5, 4)));
+    }
+
+    #[test]
+    fn test_parse_object_trailing_comma() {
+        let mut parser = Parser::new(Lexer::new(r#"{"a": 1,}"#), ParseOptions::default());
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_json5() {
+        let options = ParseOptions { json5: true, ..ParseOptions::default() };
+        let json_obj = Parser::new(Lexer::new("{a: [1, NaN,], true: {},}"), options.clone())
+            .parse_root()
+            .unwrap();
+        let mut object = Map::new();
+        object.insert(
+            "a".to_string(),
+            JsonObject::Array(vec![
+                JsonObject::Number(JsonNumber::from(1)),
+                JsonObject::Number(JsonNumber::from_non_finite("NaN")),
+            ]),
+        );
+        object.insert("true".to_string(), JsonObject::Object(Map::new()));
+        assert_eq!(json_obj, JsonObject::Object(object));
+
+        for json in ["[1,,]", "[,]", "{,}", "{a: 1,,}", "{-Infinity: 1}", "[foo]"] {
+            let mut parser = Parser::new(Lexer::new(json), options.clone());
+            assert!(parser.parse_root().is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_parse_empty_containers() {
+        let json = r#"{"a": [], "b": {}, "c": [[], {}]}"#;
+        let json_obj = Parser::new(Lexer::new(json), ParseOptions::default())
+            .parse()
+            .unwrap();
+        let mut object = Map::new();
+        object.insert("a".to_string(), JsonObject::Array(vec![]));
+        object.insert("b".to_string(), JsonObject::Object(Map::new()));
+        object.insert(
+            "c".to_string(),
+            JsonObject::Array(vec![JsonObject::Array(vec![]), JsonObject::Object(Map::new())]),
+        );
+        assert_eq!(json_obj, JsonObject::Object(object));
+
+        let mut parser = Parser::new(Lexer::new("[]"), ParseOptions::default());
+        assert_eq!(parser.parse_array().unwrap(), JsonObject::Array(vec![]));
+    }
+
+    #[test]
+    fn test_parse_stops_at_root_value() {
+        // parse は値の終わりまでしか読み込まない
+        let mut parser = Parser::new(Lexer::new("[1, 2] garbage"), ParseOptions::default());
+        assert!(parser.parse().is_ok());
+        assert_eq!(parser.lexer.position(), Position::new(1, 7, 6));
+    }
+
+    #[test]
+    fn test_parse_error_source_line() {
+        let json = "{\n  \"a\": 1\n  \"b\": 2\n}";
+        let err = Parser::new(Lexer::new(json), ParseOptions::default())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.source_line(), Some("  \"b\": 2"));
+    }
+
+    #[test]
+    fn test_parse_next() {
+        let mut parser = Parser::new(Lexer::new(" [1]{\"a\":null}\n\"x\"2 "), ParseOptions::default());
+        assert_eq!(
+            parser.parse_next().unwrap(),
+            Some(JsonObject::Array(vec![JsonObject::Number(JsonNumber::from(1))]))
+        );
+        assert_eq!(parser.position(), Position::new(1, 5, 4));
+        assert!(parser.parse_next().unwrap().unwrap().is_object());
+        assert_eq!(parser.parse_next().unwrap(), Some(JsonObject::String("x".to_string())));
+        assert_eq!(parser.parse_next().unwrap(), Some(JsonObject::Number(JsonNumber::from(2))));
+        assert_eq!(parser.parse_next().unwrap(), None);
+        assert_eq!(parser.parse_next().unwrap(), None);
+    }
+
+    #[test]
+    fn test_json_stream() {
+        let values: Vec<_> = JsonStream::new("1 [2".as_bytes()).collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0], Ok(JsonObject::Number(JsonNumber::from(1))));
+        assert_eq!(values[1].as_ref().unwrap_err().position(), Some(Position::new(1, 5, 4)));
+
+        assert_eq!(JsonStream::new(" \n ".as_bytes()).count(), 0);
+    }
+}
@@ -0,0 +1,325 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    error::{JsonPretError, PointerError},
+    JsonObject,
+};
+
+/// RFC 6901 の JSON Pointer
+///
+/// `/a/0/b` のように `/` で区切った参照トークンの並び。
+/// トークン中の `~` は `~0`、`/` は `~1` とエスケープする。
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct JsonPointer {
+    /// エスケープを戻した参照トークン
+    tokens: Vec<String>,
+}
+
+impl JsonPointer {
+    /// 文字列の JSON Pointer をパースする。空文字列は値全体を指す。
+    pub fn parse(pointer: &str) -> Result<JsonPointer, JsonPretError> {
+        if pointer.is_empty() {
+            return Ok(JsonPointer::default());
+        }
+        let rest: &str = match pointer.strip_prefix('/') {
+            Some(rest) => rest,
+            None => return Err(pointer_error("a JSON pointer must start with '/'", pointer)),
+        };
+
+        let mut tokens: Vec<String> = vec![];
+        for token in rest.split('/') {
+            match unescape(token) {
+                Some(t) => tokens.push(t),
+                None => return Err(pointer_error(
+                    "'~' must be followed by '0' or '1' in a JSON pointer",
+                    pointer,
+                )),
+            }
+        }
+        Ok(JsonPointer { tokens })
+    }
+
+    /// エスケープしていない参照トークンから JSON Pointer を作る
+    pub fn from_tokens<I, S>(tokens: I) -> JsonPointer
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        JsonPointer { tokens: tokens.into_iter().map(Into::into).collect() }
+    }
+
+    /// エスケープを戻した参照トークンを返す
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// 値全体を指す場合は `true` を返す
+    pub fn is_root(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// 末尾にトークンを追加する
+    pub fn push(&mut self, token: impl Into<String>) {
+        self.tokens.push(token.into());
+    }
+
+    /// 親を指す JSON Pointer と、最後のトークンに分ける。値全体を指す場合は `None` を返す。
+    pub fn split_last(&self) -> Option<(JsonPointer, &str)> {
+        let (last, parent) = self.tokens.split_last()?;
+        Some((JsonPointer { tokens: parent.to_vec() }, last))
+    }
+
+    /// `value` の中でこの JSON Pointer が指す値を返す
+    pub fn resolve<'a>(&self, value: &'a JsonObject) -> Option<&'a JsonObject> {
+        self.tokens.iter().try_fold(value, |v, token| match v {
+            JsonObject::Object(map) => map.get(token),
+            JsonObject::Array(array) => array.get(parse_index(token)?),
+            _ => None,
+        })
+    }
+
+    /// `value` の中でこの JSON Pointer が指す値を変更可能な参照で返す
+    pub fn resolve_mut<'a>(&self, value: &'a mut JsonObject) -> Option<&'a mut JsonObject> {
+        self.tokens.iter().try_fold(value, |v, token| match v {
+            JsonObject::Object(map) => map.get_mut(token),
+            JsonObject::Array(array) => array.get_mut(parse_index(token)?),
+            _ => None,
+        })
+    }
+}
+
+impl Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            write!(f, "/{}", escape(token))?;
+        }
+        Ok(())
+    }
+}
+
+/// 参照トークンの `~` を `~0` に、`/` を `~1` にエスケープする
+pub fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// 参照トークンの `~1` を `/` に、`~0` を `~` に戻す。
+/// `~` の後に `0` か `1` が無い場合は `None` を返す。
+fn unescape(token: &str) -> Option<String> {
+    let mut unescaped: String = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => unescaped.push('~'),
+            Some('1') => unescaped.push('/'),
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+/// Array の添字として参照トークンをパースする。
+/// 先頭の 0 や符号は認めない。
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || !token.bytes().all(|b| b.is_ascii_digit())
+        || (token.len() > 1 && token.starts_with('0'))
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn pointer_error(msg: &str, pointer: &str) -> JsonPretError {
+    JsonPretError::PointerError(PointerError::new(msg, pointer))
+}
+
+impl JsonObject {
+    /// JSON Pointer が指す値を返す。
+    /// 値が無い場合や JSON Pointer の文法が正しくない場合は `None` を返す。
+    ///
+    /// ```
+    /// let value = json_prettier::parse(r#"{"a": [{"b/c": 1}]}"#).unwrap();
+    /// assert_eq!(value.pointer("/a/0/b~1c").unwrap().to_string(), "1");
+    /// assert!(value.pointer("/a/1").is_none());
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&JsonObject> {
+        JsonPointer::parse(pointer).ok()?.resolve(self)
+    }
+
+    /// JSON Pointer が指す値を変更可能な参照で返す
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JsonObject> {
+        JsonPointer::parse(pointer).ok()?.resolve_mut(self)
+    }
+
+    /// JSON Pointer が指す場所に値を追加する(RFC 6902 の add と同じ)。
+    ///
+    /// - Object の場合はキーを追加し、既にキーがある場合は値を置き換えて古い値を返す
+    /// - Array の場合は添字の位置に挿入する。`-` は末尾に追加する
+    /// - 値全体を指す場合は値を置き換えて古い値を返す
+    pub fn pointer_insert(
+        &mut self,
+        pointer: &str,
+        value: JsonObject,
+    ) -> Result<Option<JsonObject>, JsonPretError> {
+        let parsed: JsonPointer = JsonPointer::parse(pointer)?;
+        let (parent, last) = match parsed.split_last() {
+            Some(split) => split,
+            None => return Ok(Some(std::mem::replace(self, value))),
+        };
+
+        match parent.resolve_mut(self) {
+            Some(JsonObject::Object(map)) => Ok(map.insert(last.to_string(), value)),
+            Some(JsonObject::Array(array)) => {
+                let index: usize = match last {
+                    "-" => array.len(),
+                    _ => match parse_index(last) {
+                        Some(i) if i <= array.len() => i,
+                        _ => return Err(pointer_error(
+                            &format!("'{}' is not a valid index for an array of length {}", last, array.len()),
+                            pointer,
+                        )),
+                    },
+                };
+                array.insert(index, value);
+                Ok(None)
+            }
+            Some(_) => Err(pointer_error(
+                &format!("'{}' is neither an object nor an array", parent),
+                pointer,
+            )),
+            None => Err(pointer_error(&format!("no value at '{}'", parent), pointer)),
+        }
+    }
+
+    /// JSON Pointer が指す値を取り除いて返す。値全体は取り除けない。
+    pub fn pointer_remove(&mut self, pointer: &str) -> Result<JsonObject, JsonPretError> {
+        let parsed: JsonPointer = JsonPointer::parse(pointer)?;
+        let (parent, last) = match parsed.split_last() {
+            Some(split) => split,
+            None => return Err(pointer_error("the root value can't be removed", pointer)),
+        };
+
+        let removed: Option<JsonObject> = match parent.resolve_mut(self) {
+            Some(JsonObject::Object(map)) => map.remove(last),
+            Some(JsonObject::Array(array)) => match parse_index(last) {
+                Some(i) if i < array.len() => Some(array.remove(i)),
+                _ => None,
+            },
+            _ => None,
+        };
+        removed.ok_or_else(|| pointer_error(&format!("no value at '{}'", pointer), pointer))
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, pointer::JsonPointer, JsonNumber, JsonObject, JsonPretError};
+
+    /// RFC 6901 5節の例
+    const RFC_EXAMPLE: &str = r#"{
+        "foo": ["bar", "baz"],
+        "": 0,
+        "a/b": 1,
+        "c%d": 2,
+        "e^f": 3,
+        "g|h": 4,
+        "i\\j": 5,
+        "k\"l": 6,
+        " ": 7,
+        "m~n": 8
+    }"#;
+
+    fn number(n: i64) -> JsonObject {
+        JsonObject::Number(JsonNumber::from(n))
+    }
+
+    #[test]
+    fn test_pointer_rfc_example() {
+        let value = parse(RFC_EXAMPLE).unwrap();
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/foo"), Some(&parse(r#"["bar", "baz"]"#).unwrap()));
+        assert_eq!(value.pointer("/foo/0"), Some(&JsonObject::String("bar".to_string())));
+        assert_eq!(value.pointer("/"), Some(&number(0)));
+        assert_eq!(value.pointer("/a~1b"), Some(&number(1)));
+        assert_eq!(value.pointer("/c%d"), Some(&number(2)));
+        assert_eq!(value.pointer("/e^f"), Some(&number(3)));
+        assert_eq!(value.pointer("/g|h"), Some(&number(4)));
+        assert_eq!(value.pointer("/i\\j"), Some(&number(5)));
+        assert_eq!(value.pointer("/k\"l"), Some(&number(6)));
+        assert_eq!(value.pointer("/ "), Some(&number(7)));
+        assert_eq!(value.pointer("/m~0n"), Some(&number(8)));
+    }
+
+    #[test]
+    fn test_pointer_missing() {
+        let value = parse(RFC_EXAMPLE).unwrap();
+        for pointer in ["/bar", "/foo/2", "/foo/01", "/foo/-", "/foo/+1", "/foo/0/x", "foo", "/m~2n", "/m~"] {
+            assert_eq!(value.pointer(pointer), None, "{}", pointer);
+        }
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let pointer = JsonPointer::parse("/a~1b/~0/0").unwrap();
+        assert_eq!(pointer.tokens(), ["a/b", "~", "0"]);
+        assert_eq!(pointer.to_string(), "/a~1b/~0/0");
+        assert_eq!(JsonPointer::from_tokens(["~1", "/"]).to_string(), "/~01/~1");
+        assert!(JsonPointer::parse("").unwrap().is_root());
+
+        // ~01 は ~1 に戻し、/ にはしない
+        assert_eq!(JsonPointer::parse("/~01").unwrap().tokens(), ["~1"]);
+
+        for pointer in ["a", "/~", "/~2"] {
+            assert!(matches!(JsonPointer::parse(pointer), Err(JsonPretError::PointerError(_))));
+        }
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut value = parse(r#"{"a": [1, {"b": null}]}"#).unwrap();
+        *value.pointer_mut("/a/1/b").unwrap() = number(2);
+        assert_eq!(value, parse(r#"{"a": [1, {"b": 2}]}"#).unwrap());
+        assert!(value.pointer_mut("/a/2").is_none());
+    }
+
+    #[test]
+    fn test_pointer_insert() {
+        let mut value = parse(r#"{"a": [1, 2], "b": {}}"#).unwrap();
+        assert_eq!(value.pointer_insert("/a/0", number(0)), Ok(None));
+        assert_eq!(value.pointer_insert("/a/-", number(3)), Ok(None));
+        assert_eq!(value.pointer_insert("/a/4", number(4)), Ok(None));
+        assert_eq!(value.pointer_insert("/b/c~1d", JsonObject::Null), Ok(None));
+        assert_eq!(value.pointer_insert("/b/c~1d", number(5)), Ok(Some(JsonObject::Null)));
+        assert_eq!(value, parse(r#"{"a": [0, 1, 2, 3, 4], "b": {"c/d": 5}}"#).unwrap());
+
+        for pointer in ["/a/6", "/a/01", "/x/y", "/a/0/z", "z"] {
+            assert!(value.pointer_insert(pointer, JsonObject::Null).is_err(), "{}", pointer);
+        }
+
+        let old = value.pointer_insert("", JsonObject::Bool(true)).unwrap();
+        assert_eq!(value, JsonObject::Bool(true));
+        assert!(old.is_some());
+    }
+
+    #[test]
+    fn test_pointer_remove() {
+        let mut value = parse(r#"{"a": [1, 2, 3], "b": {"c": 4}}"#).unwrap();
+        assert_eq!(value.pointer_remove("/a/1"), Ok(number(2)));
+        assert_eq!(value.pointer_remove("/b/c"), Ok(number(4)));
+        assert_eq!(value, parse(r#"{"a": [1, 3], "b": {}}"#).unwrap());
+
+        for pointer in ["", "/a/2", "/a/-", "/b/c", "/x"] {
+            assert!(value.pointer_remove(pointer).is_err(), "{}", pointer);
+        }
+        assert_eq!(
+            value.pointer_remove("/x").unwrap_err().to_string(),
+            "PointerError: no value at '/x'"
+        );
+    }
+}
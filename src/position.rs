@@ -0,0 +1,70 @@
+/// 入力中の位置
+///
+/// `line` と `column` は 1 始まり、`offset` は先頭からのバイト数。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// 行番号
+    pub line: usize,
+    /// 列番号(文字単位)
+    pub column: usize,
+    /// 先頭からのバイトオフセット
+    pub offset: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize, offset: usize) -> Position {
+        Position { line, column, offset }
+    }
+
+    /// 1文字読み進めた後の位置に更新する
+    pub fn advance(&mut self, c: char) {
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position::new(1, 1, 0)
+    }
+}
+
+/// 入力中の範囲。`end` は範囲の直後の位置を指す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Span {
+    /// 範囲の開始位置
+    pub start: Position,
+    /// 範囲の終了位置
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::position::Position;
+
+    #[test]
+    fn test_position_advance() {
+        let mut position = Position::default();
+        position.advance('a');
+        assert_eq!(position, Position::new(1, 2, 1));
+
+        position.advance('あ');
+        assert_eq!(position, Position::new(1, 3, 4));
+
+        position.advance('\n');
+        assert_eq!(position, Position::new(2, 1, 5));
+    }
+}
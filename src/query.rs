@@ -0,0 +1,1237 @@
+//! jq に似たフィルタ式で `JsonObject` から値を取り出す
+//!
+//! 対応している構文
+//!
+//! - `.`, `..`, `.foo`, `."foo bar"`, `.[2]`, `.[-1]`, `.["foo"]`, `.[1:3]`, `.[]`, 末尾の `?`
+//! - `|`, `,`, `//`, `and`, `or`, `==`, `!=`, `<`, `<=`, `>`, `>=`, `+`, `-`, `*`, `/`, `%`
+//! - リテラル(文字列、数値、`true`, `false`, `null`)、`[...]`, `{a: .b, "c": 1, (.k): .v, d}`
+//! - 関数 `length`, `keys`, `keys_unsorted`, `map(f)`, `select(f)`, `not`, `type`,
+//!   `has(k)`, `add`, `empty`, `tostring`
+//!
+//! ```
+//! use json_prettier::{parse, query::Query};
+//!
+//! let value = parse(r#"{"items": [{"id": 1, "ok": true}, {"id": 2, "ok": false}]}"#).unwrap();
+//! let query = Query::parse("[.items[] | select(.ok) | .id]").unwrap();
+//! assert_eq!(query.run(&value).unwrap(), vec![parse("[1]").unwrap()]);
+//! ```
+
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::{
+    error::{JsonPretError, QueryError},
+    lexer::{Lexer, Token},
+    map::Map,
+    to_string, JsonNumber, JsonObject,
+};
+
+/// フィルタの括弧や演算子の入れ子の深さの上限。
+/// 1段ごとに再帰下降の関数を何段も呼ぶため、JSON の入れ子の上限より小さくしている。
+const MAX_DEPTH: usize = 64;
+
+/// パース済みのフィルタ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    filter: Filter,
+}
+
+impl Query {
+    /// フィルタ式をパースする
+    pub fn parse(source: &str) -> Result<Query, JsonPretError> {
+        let tokens: Vec<(QueryToken, usize)> = tokenize(source)?;
+        let mut parser: QueryParser = QueryParser { tokens, index: 0, depth: 0 };
+        let filter: Filter = parser.parse_pipe(true)?;
+        if let Some((token, column)) = parser.tokens.get(parser.index) {
+            return Err(query_error(&format!(
+                "an unexpected {:?} at column {}",
+                token, column
+            )));
+        }
+        Ok(Query { filter })
+    }
+
+    /// `input` にフィルタを適用し、出力された値を順に返す
+    pub fn run(&self, input: &JsonObject) -> Result<Vec<JsonObject>, JsonPretError> {
+        eval(&self.filter, input)
+    }
+}
+
+impl FromStr for Query {
+    type Err = JsonPretError;
+
+    fn from_str(s: &str) -> Result<Query, JsonPretError> {
+        Query::parse(s)
+    }
+}
+
+/// `filter` をパースして `input` に適用する
+pub fn query(filter: &str, input: &JsonObject) -> Result<Vec<JsonObject>, JsonPretError> {
+    Query::parse(filter)?.run(input)
+}
+
+/// フィルタの構文木
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    /// `.`
+    Identity,
+    /// `..`
+    Recurse,
+    /// `target.name`
+    Field(Box<Filter>, String),
+    /// `target[index]`
+    Index(Box<Filter>, Box<Filter>),
+    /// `target[from:to]`
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    /// `target[]`
+    Iterate(Box<Filter>),
+    /// `target?`
+    Try(Box<Filter>),
+    Literal(JsonObject),
+    /// `[...]`
+    Array(Option<Box<Filter>>),
+    /// `{key: value, ...}`
+    Object(Vec<(Filter, Filter)>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    /// `-x`
+    Negate(Box<Filter>),
+    Binary(BinaryOp, Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    /// `a // b`
+    Alternative(Box<Filter>, Box<Filter>),
+    /// 関数呼び出し
+    Call(String, Vec<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// --- 字句解析 ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryToken {
+    Dot,
+    DotDot,
+    /// `.foo`
+    Field(String),
+    Ident(String),
+    Str(String),
+    Number(JsonNumber),
+    /// 記号
+    Punct(&'static str),
+}
+
+/// 長いものから順に並べた記号
+const PUNCTS: [&str; 21] = [
+    "//", "==", "!=", "<=", ">=", "|", ",", "(", ")", "[", "]", "{", "}", ":", "?", "<", ">",
+    "+", "-", "*", "/",
+];
+
+/// フィルタ式を `QueryToken` と、その開始位置の列(1始まり)に分割する
+fn tokenize(source: &str) -> Result<Vec<(QueryToken, usize)>, JsonPretError> {
+    let chars: Vec<(usize, char)> = source.char_indices().collect();
+    let mut tokens: Vec<(QueryToken, usize)> = vec![];
+    let mut i: usize = 0;
+
+    while i < chars.len() {
+        let (offset, c) = chars[i];
+        let column: usize = i + 1;
+        let rest: &str = &source[offset..];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let token: QueryToken = if c == '.' {
+            match chars.get(i + 1).map(|&(_, c)| c) {
+                Some('.') => {
+                    i += 2;
+                    QueryToken::DotDot
+                }
+                Some(c) if is_ident_start(c) => {
+                    let name: String = chars[i + 1..]
+                        .iter()
+                        .map(|&(_, c)| c)
+                        .take_while(|&c| is_ident_char(c))
+                        .collect();
+                    i += 1 + name.chars().count();
+                    QueryToken::Field(name)
+                }
+                _ => {
+                    i += 1;
+                    QueryToken::Dot
+                }
+            }
+        } else if c == '"' {
+            // 文字列リテラルは JSON の文字列と同じ文法
+            let (s, length) = read_string(rest, column)?;
+            i += length;
+            QueryToken::Str(s)
+        } else if c.is_ascii_digit() {
+            let lexeme: String = read_number(rest);
+            i += lexeme.len();
+            match JsonNumber::from_lexeme(&lexeme) {
+                Some(n) => QueryToken::Number(n),
+                None => return Err(query_error(&format!(
+                    "'{}' is not a valid number at column {}",
+                    lexeme, column
+                ))),
+            }
+        } else if is_ident_start(c) {
+            let name: String = rest.chars().take_while(|&c| is_ident_char(c)).collect();
+            i += name.chars().count();
+            QueryToken::Ident(name)
+        } else if c == '%' {
+            i += 1;
+            QueryToken::Punct("%")
+        } else {
+            match PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+                Some(p) => {
+                    i += p.len();
+                    QueryToken::Punct(p)
+                }
+                None => return Err(query_error(&format!(
+                    "an unexpected char {} at column {}",
+                    c, column
+                ))),
+            }
+        };
+        tokens.push((token, column));
+    }
+    Ok(tokens)
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// `rest` の先頭の文字列リテラルを読み、内容と文字数を返す
+fn read_string(rest: &str, column: usize) -> Result<(String, usize), JsonPretError> {
+    let mut lexer = Lexer::new(rest);
+    match lexer.read_token() {
+        Ok(Some(t)) => match t.token {
+            Token::String(s) => Ok((s, t.span.end.column - 1)),
+            _ => unreachable!(),
+        },
+        _ => Err(query_error(&format!("an invalid string literal at column {}", column))),
+    }
+}
+
+/// `rest` の先頭の数値リテラルを返す
+fn read_number(rest: &str) -> String {
+    let bytes: &[u8] = rest.as_bytes();
+    let digits = |from: usize| from + bytes[from..].iter().take_while(|b| b.is_ascii_digit()).count();
+
+    let mut end: usize = digits(0);
+    if bytes.get(end) == Some(&b'.') && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+        end = digits(end + 1);
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exp: usize = end + 1;
+        if matches!(bytes.get(exp), Some(b'+' | b'-')) {
+            exp += 1;
+        }
+        if bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+            end = digits(exp);
+        }
+    }
+    rest[..end].to_string()
+}
+
+// --- 構文解析 ---
+
+struct QueryParser {
+    tokens: Vec<(QueryToken, usize)>,
+    index: usize,
+    /// 括弧や演算子の入れ子の深さ
+    depth: usize,
+}
+
+impl QueryParser {
+    /// `a | b`。`allow_comma` が `false` の場合は , を区切りとして扱わない。
+    fn parse_pipe(&mut self, allow_comma: bool) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        self.enter()?;
+        let lhs: Filter = if allow_comma {
+            self.parse_comma()?
+        } else {
+            self.parse_alternative()?
+        };
+        let filter: Filter = if self.eat("|") {
+            let rhs: Filter = self.parse_pipe(allow_comma)?;
+            Filter::Pipe(Box::new(lhs), Box::new(rhs))
+        } else {
+            lhs
+        };
+        self.depth = depth;
+        Ok(filter)
+    }
+
+    /// `a, b`
+    fn parse_comma(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut lhs: Filter = self.parse_alternative()?;
+        while self.eat(",") {
+            self.enter()?;
+            let rhs: Filter = self.parse_alternative()?;
+            lhs = Filter::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    /// `a // b`(右結合)
+    fn parse_alternative(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let lhs: Filter = self.parse_or()?;
+        let filter: Filter = if self.eat("//") {
+            self.enter()?;
+            let rhs: Filter = self.parse_alternative()?;
+            Filter::Alternative(Box::new(lhs), Box::new(rhs))
+        } else {
+            lhs
+        };
+        self.depth = depth;
+        Ok(filter)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut lhs: Filter = self.parse_and()?;
+        while self.eat_keyword("or") {
+            self.enter()?;
+            let rhs: Filter = self.parse_and()?;
+            lhs = Filter::Or(Box::new(lhs), Box::new(rhs));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut lhs: Filter = self.parse_comparison()?;
+        while self.eat_keyword("and") {
+            self.enter()?;
+            let rhs: Filter = self.parse_comparison()?;
+            lhs = Filter::And(Box::new(lhs), Box::new(rhs));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    /// 比較演算子は結合しない
+    fn parse_comparison(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let lhs: Filter = self.parse_additive()?;
+        let op: BinaryOp = match self.peek() {
+            Some(QueryToken::Punct("==")) => BinaryOp::Eq,
+            Some(QueryToken::Punct("!=")) => BinaryOp::Ne,
+            Some(QueryToken::Punct("<")) => BinaryOp::Lt,
+            Some(QueryToken::Punct("<=")) => BinaryOp::Le,
+            Some(QueryToken::Punct(">")) => BinaryOp::Gt,
+            Some(QueryToken::Punct(">=")) => BinaryOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.index += 1;
+        self.enter()?;
+        let rhs: Filter = self.parse_additive()?;
+        self.depth = depth;
+        Ok(Filter::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut lhs: Filter = self.parse_multiplicative()?;
+        loop {
+            let op: BinaryOp = match self.peek() {
+                Some(QueryToken::Punct("+")) => BinaryOp::Add,
+                Some(QueryToken::Punct("-")) => BinaryOp::Sub,
+                _ => {
+                    self.depth = depth;
+                    return Ok(lhs);
+                }
+            };
+            self.index += 1;
+            self.enter()?;
+            let rhs: Filter = self.parse_multiplicative()?;
+            lhs = Filter::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut lhs: Filter = self.parse_unary()?;
+        loop {
+            let op: BinaryOp = match self.peek() {
+                Some(QueryToken::Punct("*")) => BinaryOp::Mul,
+                Some(QueryToken::Punct("/")) => BinaryOp::Div,
+                Some(QueryToken::Punct("%")) => BinaryOp::Rem,
+                _ => {
+                    self.depth = depth;
+                    return Ok(lhs);
+                }
+            };
+            self.index += 1;
+            self.enter()?;
+            let rhs: Filter = self.parse_unary()?;
+            lhs = Filter::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, JsonPretError> {
+        if self.eat("-") {
+            let depth: usize = self.depth;
+            self.enter()?;
+            let filter: Filter = self.parse_unary()?;
+            self.depth = depth;
+            return Ok(Filter::Negate(Box::new(filter)));
+        }
+        self.parse_postfix()
+    }
+
+    /// 項と、その後に続く `.foo`, `[...]`, `?`
+    fn parse_postfix(&mut self) -> Result<Filter, JsonPretError> {
+        let depth: usize = self.depth;
+        let mut term: Filter = self.parse_term()?;
+        loop {
+            term = match self.peek() {
+                Some(QueryToken::Field(name)) => {
+                    let name: String = name.clone();
+                    self.index += 1;
+                    Filter::Field(Box::new(term), name)
+                }
+                Some(QueryToken::Dot) if matches!(self.peek_at(1), Some(QueryToken::Str(_) | QueryToken::Punct("["))) => {
+                    self.index += 1;
+                    continue;
+                }
+                Some(QueryToken::Str(_)) if self.previous_is_dot() => {
+                    let name: String = match self.next() {
+                        Some(QueryToken::Str(s)) => s,
+                        _ => unreachable!(),
+                    };
+                    Filter::Field(Box::new(term), name)
+                }
+                Some(QueryToken::Punct("[")) => {
+                    self.index += 1;
+                    self.parse_brackets(term)?
+                }
+                Some(QueryToken::Punct("?")) => {
+                    self.index += 1;
+                    Filter::Try(Box::new(term))
+                }
+                _ => {
+                    self.depth = depth;
+                    return Ok(term);
+                }
+            };
+            self.enter()?;
+        }
+    }
+
+    /// `[` の後の `]`, `index]`, `from:to]` を読む
+    fn parse_brackets(&mut self, target: Filter) -> Result<Filter, JsonPretError> {
+        if self.eat("]") {
+            return Ok(Filter::Iterate(Box::new(target)));
+        }
+
+        let from: Option<Filter> = if self.peek() == Some(&QueryToken::Punct(":")) {
+            None
+        } else {
+            Some(self.parse_pipe(true)?)
+        };
+        if self.eat(":") {
+            let to: Option<Filter> = if self.peek() == Some(&QueryToken::Punct("]")) {
+                None
+            } else {
+                Some(self.parse_pipe(true)?)
+            };
+            self.expect("]")?;
+            return Ok(Filter::Slice(Box::new(target), from.map(Box::new), to.map(Box::new)));
+        }
+        self.expect("]")?;
+        match from {
+            Some(index) => Ok(Filter::Index(Box::new(target), Box::new(index))),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Filter, JsonPretError> {
+        let token: QueryToken = match self.next() {
+            Some(t) => t,
+            None => return Err(query_error("unexpected end of the query")),
+        };
+        match token {
+            // `."foo"` と `.[...]` の続きは parse_postfix で読む
+            QueryToken::Dot => Ok(Filter::Identity),
+            QueryToken::DotDot => Ok(Filter::Recurse),
+            QueryToken::Field(name) => Ok(Filter::Field(Box::new(Filter::Identity), name)),
+            QueryToken::Str(s) => Ok(Filter::Literal(JsonObject::String(s))),
+            QueryToken::Number(n) => Ok(Filter::Literal(JsonObject::Number(n))),
+            QueryToken::Punct("(") => {
+                let filter: Filter = self.parse_pipe(true)?;
+                self.expect(")")?;
+                Ok(filter)
+            }
+            QueryToken::Punct("[") => {
+                if self.eat("]") {
+                    return Ok(Filter::Array(None));
+                }
+                let filter: Filter = self.parse_pipe(true)?;
+                self.expect("]")?;
+                Ok(Filter::Array(Some(Box::new(filter))))
+            }
+            QueryToken::Punct("{") => self.parse_object(),
+            QueryToken::Ident(name) => match name.as_str() {
+                "true" => Ok(Filter::Literal(JsonObject::Bool(true))),
+                "false" => Ok(Filter::Literal(JsonObject::Bool(false))),
+                "null" => Ok(Filter::Literal(JsonObject::Null)),
+                _ => {
+                    let mut args: Vec<Filter> = vec![];
+                    if self.eat("(") {
+                        args.push(self.parse_pipe(true)?);
+                        self.expect(")")?;
+                    }
+                    Ok(Filter::Call(name, args))
+                }
+            },
+            _ => {
+                self.index -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// `{` の後の `key: value, ...}` を読む
+    fn parse_object(&mut self) -> Result<Filter, JsonPretError> {
+        let mut entries: Vec<(Filter, Filter)> = vec![];
+        if self.eat("}") {
+            return Ok(Filter::Object(entries));
+        }
+        loop {
+            let (key, shorthand): (Filter, Option<String>) = match self.next() {
+                Some(QueryToken::Ident(name)) => {
+                    (Filter::Literal(JsonObject::String(name.clone())), Some(name))
+                }
+                Some(QueryToken::Str(s)) => (Filter::Literal(JsonObject::String(s.clone())), Some(s)),
+                Some(QueryToken::Punct("(")) => {
+                    let key: Filter = self.parse_pipe(true)?;
+                    self.expect(")")?;
+                    (key, None)
+                }
+                _ => {
+                    self.index -= 1;
+                    return Err(self.unexpected());
+                }
+            };
+
+            let value: Filter = if self.eat(":") {
+                self.parse_pipe(false)?
+            } else {
+                // `{a}` は `{a: .a}` と同じ
+                match shorthand {
+                    Some(name) => Filter::Field(Box::new(Filter::Identity), name),
+                    None => return Err(self.unexpected()),
+                }
+            };
+            entries.push((key, value));
+
+            if self.eat("}") {
+                return Ok(Filter::Object(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// 入れ子を1段深くする。深さが `MAX_DEPTH` に達した場合はエラーを返す。
+    fn enter(&mut self) -> Result<(), JsonPretError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(query_error(&format!(
+                "the nesting depth exceeds the limit of {}",
+                MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<&QueryToken> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<&QueryToken> {
+        self.tokens.get(self.index + n).map(|(t, _)| t)
+    }
+
+    fn previous_is_dot(&self) -> bool {
+        self.index > 0 && self.tokens[self.index - 1].0 == QueryToken::Dot
+    }
+
+    fn next(&mut self) -> Option<QueryToken> {
+        let token: Option<QueryToken> = self.tokens.get(self.index).map(|(t, _)| t.clone());
+        self.index += 1;
+        token
+    }
+
+    /// 次が記号 `punct` の場合は読み進めて `true` を返す
+    fn eat(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(QueryToken::Punct(p)) if *p == punct => {
+                self.index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(QueryToken::Ident(name)) if name == keyword => {
+                self.index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), JsonPretError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// 次の `QueryToken` が予期しないものである時のエラーを作る
+    fn unexpected(&self) -> JsonPretError {
+        match self.tokens.get(self.index) {
+            Some((token, column)) => query_error(&format!(
+                "an unexpected {:?} at column {}",
+                token, column
+            )),
+            None => query_error("unexpected end of the query"),
+        }
+    }
+}
+
+// --- 評価 ---
+
+fn eval(filter: &Filter, input: &JsonObject) -> Result<Vec<JsonObject>, JsonPretError> {
+    match filter {
+        Filter::Identity => Ok(vec![input.clone()]),
+        Filter::Recurse => {
+            let mut outputs: Vec<JsonObject> = vec![];
+            recurse(input, &mut outputs);
+            Ok(outputs)
+        }
+        Filter::Field(target, name) => eval(target, input)?
+            .iter()
+            .map(|v| index(v, &JsonObject::String(name.clone())))
+            .collect(),
+        Filter::Index(target, idx) => {
+            let targets: Vec<JsonObject> = eval(target, input)?;
+            let indices: Vec<JsonObject> = eval(idx, input)?;
+            let mut outputs: Vec<JsonObject> = vec![];
+            for t in &targets {
+                for i in &indices {
+                    outputs.push(index(t, i)?);
+                }
+            }
+            Ok(outputs)
+        }
+        Filter::Slice(target, from, to) => {
+            let froms: Vec<JsonObject> = eval_optional(from.as_deref(), input)?;
+            let tos: Vec<JsonObject> = eval_optional(to.as_deref(), input)?;
+            let mut outputs: Vec<JsonObject> = vec![];
+            for t in &eval(target, input)? {
+                for from in &froms {
+                    for to in &tos {
+                        outputs.push(slice(t, from, to)?);
+                    }
+                }
+            }
+            Ok(outputs)
+        }
+        Filter::Iterate(target) => {
+            let mut outputs: Vec<JsonObject> = vec![];
+            for t in eval(target, input)? {
+                match t {
+                    JsonObject::Array(array) => outputs.extend(array),
+                    JsonObject::Object(map) => outputs.extend(map.into_iter().map(|(_, v)| v)),
+                    other => return Err(query_error(&format!(
+                        "cannot iterate over {}",
+                        type_name(&other)
+                    ))),
+                }
+            }
+            Ok(outputs)
+        }
+        Filter::Try(target) => Ok(eval(target, input).unwrap_or_default()),
+        Filter::Literal(value) => Ok(vec![value.clone()]),
+        Filter::Array(None) => Ok(vec![JsonObject::Array(vec![])]),
+        Filter::Array(Some(inner)) => Ok(vec![JsonObject::Array(eval(inner, input)?)]),
+        Filter::Object(entries) => {
+            // 各エントリが複数の値を出力する場合は、全ての組み合わせの Object を出力する
+            let mut objects: Vec<Map> = vec![Map::new()];
+            for (key, value) in entries {
+                let keys: Vec<JsonObject> = eval(key, input)?;
+                let values: Vec<JsonObject> = eval(value, input)?;
+                let mut next: Vec<Map> = vec![];
+                for object in &objects {
+                    for k in &keys {
+                        let k: &str = match k {
+                            JsonObject::String(s) => s,
+                            other => return Err(query_error(&format!(
+                                "object keys must be strings, but {} is given",
+                                type_name(other)
+                            ))),
+                        };
+                        for v in &values {
+                            let mut object: Map = object.clone();
+                            object.insert(k.to_string(), v.clone());
+                            next.push(object);
+                        }
+                    }
+                }
+                objects = next;
+            }
+            Ok(objects.into_iter().map(JsonObject::Object).collect())
+        }
+        Filter::Pipe(lhs, rhs) => {
+            let mut outputs: Vec<JsonObject> = vec![];
+            for v in eval(lhs, input)? {
+                outputs.extend(eval(rhs, &v)?);
+            }
+            Ok(outputs)
+        }
+        Filter::Comma(lhs, rhs) => {
+            let mut outputs: Vec<JsonObject> = eval(lhs, input)?;
+            outputs.extend(eval(rhs, input)?);
+            Ok(outputs)
+        }
+        Filter::Negate(inner) => eval(inner, input)?
+            .iter()
+            .map(|v| match v {
+                // i64 で表せる整数は f64 を経由せずに正確に計算する
+                JsonObject::Number(n) => match n.as_i64().and_then(i64::checked_neg) {
+                    Some(n) => Ok(JsonObject::Number(JsonNumber::from(n))),
+                    None => number(-to_f64(n)),
+                },
+                other => Err(query_error(&format!("{} cannot be negated", type_name(other)))),
+            })
+            .collect(),
+        Filter::Binary(op, lhs, rhs) => {
+            let rhs_values: Vec<JsonObject> = eval(rhs, input)?;
+            let lhs_values: Vec<JsonObject> = eval(lhs, input)?;
+            let mut outputs: Vec<JsonObject> = vec![];
+            for r in &rhs_values {
+                for l in &lhs_values {
+                    outputs.push(binary(*op, l, r)?);
+                }
+            }
+            Ok(outputs)
+        }
+        Filter::And(lhs, rhs) | Filter::Or(lhs, rhs) => {
+            let is_and: bool = matches!(filter, Filter::And(..));
+            let mut outputs: Vec<JsonObject> = vec![];
+            for l in eval(lhs, input)? {
+                // and の左辺が偽、or の左辺が真の場合は右辺を評価しない
+                if is_truthy(&l) != is_and {
+                    outputs.push(JsonObject::Bool(!is_and));
+                    continue;
+                }
+                for r in eval(rhs, input)? {
+                    outputs.push(JsonObject::Bool(is_truthy(&r)));
+                }
+            }
+            Ok(outputs)
+        }
+        Filter::Alternative(lhs, rhs) => {
+            let outputs: Vec<JsonObject> = eval(lhs, input)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(is_truthy)
+                .collect();
+            if outputs.is_empty() {
+                eval(rhs, input)
+            } else {
+                Ok(outputs)
+            }
+        }
+        Filter::Call(name, args) => call(name, args, input),
+    }
+}
+
+fn eval_optional(filter: Option<&Filter>, input: &JsonObject) -> Result<Vec<JsonObject>, JsonPretError> {
+    match filter {
+        Some(f) => eval(f, input),
+        None => Ok(vec![JsonObject::Null]),
+    }
+}
+
+/// `value` とその子孫を全て出力する
+fn recurse(value: &JsonObject, outputs: &mut Vec<JsonObject>) {
+    outputs.push(value.clone());
+    match value {
+        JsonObject::Array(array) => array.iter().for_each(|v| recurse(v, outputs)),
+        JsonObject::Object(map) => map.values().for_each(|v| recurse(v, outputs)),
+        _ => {}
+    }
+}
+
+/// `value[idx]`。Null とキーや添字が無い場合は Null を返す。
+fn index(value: &JsonObject, idx: &JsonObject) -> Result<JsonObject, JsonPretError> {
+    match (value, idx) {
+        (JsonObject::Null, JsonObject::String(_) | JsonObject::Number(_)) => Ok(JsonObject::Null),
+        (JsonObject::Object(map), JsonObject::String(key)) => {
+            Ok(map.get(key).cloned().unwrap_or(JsonObject::Null))
+        }
+        (JsonObject::Array(array), JsonObject::Number(n)) => {
+            let i: f64 = to_f64(n).floor();
+            let i: f64 = if i < 0.0 { array.len() as f64 + i } else { i };
+            if i < 0.0 {
+                return Ok(JsonObject::Null);
+            }
+            Ok(array.get(i as usize).cloned().unwrap_or(JsonObject::Null))
+        }
+        _ => Err(query_error(&format!(
+            "cannot index {} with {}",
+            type_name(value),
+            match idx {
+                JsonObject::String(s) => format!("\"{}\"", s),
+                other => type_name(other).to_string(),
+            }
+        ))),
+    }
+}
+
+/// `value[from:to]`。Array と String の一部を返す。
+fn slice(value: &JsonObject, from: &JsonObject, to: &JsonObject) -> Result<JsonObject, JsonPretError> {
+    let len: usize = match value {
+        JsonObject::Null => return Ok(JsonObject::Null),
+        JsonObject::Array(array) => array.len(),
+        JsonObject::String(s) => s.chars().count(),
+        other => return Err(query_error(&format!("cannot slice {}", type_name(other)))),
+    };
+    let bound = |b: &JsonObject, default: usize| -> Result<usize, JsonPretError> {
+        match b {
+            JsonObject::Null => Ok(default),
+            JsonObject::Number(n) => {
+                let i: f64 = to_f64(n).floor();
+                let i: f64 = if i < 0.0 { len as f64 + i } else { i };
+                Ok(i.clamp(0.0, len as f64) as usize)
+            }
+            other => Err(query_error(&format!(
+                "slice indices must be numbers, but {} is given",
+                type_name(other)
+            ))),
+        }
+    };
+    let start: usize = bound(from, 0)?;
+    let end: usize = bound(to, len)?.max(start);
+    match value {
+        JsonObject::Array(array) => Ok(JsonObject::Array(array[start..end].to_vec())),
+        JsonObject::String(s) => Ok(JsonObject::String(s.chars().skip(start).take(end - start).collect())),
+        _ => unreachable!(),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: &JsonObject, rhs: &JsonObject) -> Result<JsonObject, JsonPretError> {
+    use JsonObject::*;
+
+    let ordering: Ordering = compare(lhs, rhs);
+    match op {
+        BinaryOp::Eq => return Ok(Bool(ordering == Ordering::Equal)),
+        BinaryOp::Ne => return Ok(Bool(ordering != Ordering::Equal)),
+        BinaryOp::Lt => return Ok(Bool(ordering == Ordering::Less)),
+        BinaryOp::Le => return Ok(Bool(ordering != Ordering::Greater)),
+        BinaryOp::Gt => return Ok(Bool(ordering == Ordering::Greater)),
+        BinaryOp::Ge => return Ok(Bool(ordering != Ordering::Less)),
+        _ => {}
+    }
+
+    match (op, lhs, rhs) {
+        (BinaryOp::Add, Null, v) | (BinaryOp::Add, v, Null) => Ok(v.clone()),
+        (_, Number(a), Number(b)) => arithmetic(op, a, b),
+        (BinaryOp::Add, String(a), String(b)) => Ok(String(format!("{}{}", a, b))),
+        (BinaryOp::Add, Array(a), Array(b)) => Ok(Array(a.iter().chain(b).cloned().collect())),
+        (BinaryOp::Add, Object(a), Object(b)) => {
+            let mut merged: Map = a.clone();
+            merged.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Ok(Object(merged))
+        }
+        (BinaryOp::Sub, Array(a), Array(b)) => Ok(Array(
+            a.iter().filter(|v| !b.contains(v)).cloned().collect(),
+        )),
+        _ => Err(query_error(&format!(
+            "{} and {} cannot be {}",
+            type_name(lhs),
+            type_name(rhs),
+            match op {
+                BinaryOp::Add => "added",
+                BinaryOp::Sub => "subtracted",
+                BinaryOp::Mul => "multiplied",
+                BinaryOp::Div => "divided",
+                _ => "divided (remainder)",
+            }
+        ))),
+    }
+}
+
+/// Number どうしの四則演算。整数で表せる場合は整数で計算する。
+fn arithmetic(op: BinaryOp, a: &JsonNumber, b: &JsonNumber) -> Result<JsonObject, JsonPretError> {
+    if let (Some(x), Some(y)) = (a.as_i64(), b.as_i64()) {
+        let exact: Option<i64> = match op {
+            BinaryOp::Add => x.checked_add(y),
+            BinaryOp::Sub => x.checked_sub(y),
+            BinaryOp::Mul => x.checked_mul(y),
+            BinaryOp::Rem if y == 0 => return Err(query_error("division by zero")),
+            BinaryOp::Rem => x.checked_rem(y),
+            _ => None,
+        };
+        if let Some(n) = exact {
+            return Ok(JsonObject::Number(JsonNumber::from(n)));
+        }
+    }
+
+    let (x, y): (f64, f64) = (to_f64(a), to_f64(b));
+    match op {
+        BinaryOp::Add => number(x + y),
+        BinaryOp::Sub => number(x - y),
+        BinaryOp::Mul => number(x * y),
+        BinaryOp::Div | BinaryOp::Rem if y == 0.0 => Err(query_error("division by zero")),
+        BinaryOp::Div => number(x / y),
+        _ => number((x.trunc() % y.trunc()).trunc()),
+    }
+}
+
+/// 計算結果の f64 を Number にする。整数の場合は小数点を付けない。
+fn number(f: f64) -> Result<JsonObject, JsonPretError> {
+    // 2^53 未満の整数は f64 で正確に表せる
+    if f.fract() == 0.0 && f.abs() < 9007199254740992.0 {
+        return Ok(JsonObject::Number(JsonNumber::from(f as i64)));
+    }
+    match JsonNumber::from_f64(f) {
+        Some(n) => Ok(JsonObject::Number(n)),
+        None => Err(query_error("the result is not a finite number")),
+    }
+}
+
+fn to_f64(n: &JsonNumber) -> f64 {
+    n.as_str().parse().unwrap_or(f64::NAN)
+}
+
+/// jq と同じ順序で比較する。
+/// null < false < true < Number < String < Array < Object の順で、同じ型どうしは値で比べる。
+fn compare(a: &JsonObject, b: &JsonObject) -> Ordering {
+    fn rank(v: &JsonObject) -> u8 {
+        match v {
+            JsonObject::Null => 0,
+            JsonObject::Bool(false) => 1,
+            JsonObject::Bool(true) => 2,
+            JsonObject::Number(_) => 3,
+            JsonObject::String(_) => 4,
+            JsonObject::Array(_) => 5,
+            JsonObject::Object(_) => 6,
+        }
+    }
+
+    match (a, b) {
+        (JsonObject::Number(x), JsonObject::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            // i64 で表せる整数は f64 に丸めずに比べる
+            (Some(x), Some(y)) => x.cmp(&y),
+            _ => to_f64(x).partial_cmp(&to_f64(y)).unwrap_or(Ordering::Equal),
+        },
+        (JsonObject::String(x), JsonObject::String(y)) => x.cmp(y),
+        (JsonObject::Array(x), JsonObject::Array(y)) => x
+            .iter()
+            .zip(y)
+            .map(|(x, y)| compare(x, y))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        (JsonObject::Object(x), JsonObject::Object(y)) => {
+            // キーの集合を比べてから、キーの順に値を比べる
+            let keys_x: Vec<&String> = sorted_keys(x);
+            let keys_y: Vec<&String> = sorted_keys(y);
+            keys_x.cmp(&keys_y).then_with(|| {
+                keys_x
+                    .iter()
+                    .map(|k| compare(&x[k.as_str()], &y[k.as_str()]))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn sorted_keys(map: &Map) -> Vec<&String> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+/// false と null 以外は真
+fn is_truthy(value: &JsonObject) -> bool {
+    !matches!(value, JsonObject::Null | JsonObject::Bool(false))
+}
+
+fn type_name(value: &JsonObject) -> &'static str {
+    match value {
+        JsonObject::Null => "null",
+        JsonObject::Bool(_) => "boolean",
+        JsonObject::Number(_) => "number",
+        JsonObject::String(_) => "string",
+        JsonObject::Array(_) => "array",
+        JsonObject::Object(_) => "object",
+    }
+}
+
+/// 組み込み関数を呼び出す
+fn call(name: &str, args: &[Filter], input: &JsonObject) -> Result<Vec<JsonObject>, JsonPretError> {
+    match (name, args) {
+        ("empty", []) => Ok(vec![]),
+        ("not", []) => Ok(vec![JsonObject::Bool(!is_truthy(input))]),
+        ("type", []) => Ok(vec![JsonObject::String(type_name(input).to_string())]),
+        ("length", []) => {
+            let length: JsonObject = match input {
+                JsonObject::Null => JsonObject::Number(JsonNumber::from(0)),
+                JsonObject::Bool(_) => return Err(query_error("boolean has no length")),
+                JsonObject::Number(n) => number(to_f64(n).abs())?,
+                JsonObject::String(s) => JsonObject::Number(JsonNumber::from(s.chars().count())),
+                JsonObject::Array(array) => JsonObject::Number(JsonNumber::from(array.len())),
+                JsonObject::Object(map) => JsonObject::Number(JsonNumber::from(map.len())),
+            };
+            Ok(vec![length])
+        }
+        ("keys", []) | ("keys_unsorted", []) => {
+            let keys: Vec<JsonObject> = match input {
+                JsonObject::Object(map) => {
+                    let keys: Vec<&String> = if name == "keys" {
+                        sorted_keys(map)
+                    } else {
+                        map.keys().collect()
+                    };
+                    keys.into_iter().map(|k| JsonObject::String(k.clone())).collect()
+                }
+                JsonObject::Array(array) => (0..array.len())
+                    .map(|i| JsonObject::Number(JsonNumber::from(i)))
+                    .collect(),
+                other => return Err(query_error(&format!("{} has no keys", type_name(other)))),
+            };
+            Ok(vec![JsonObject::Array(keys)])
+        }
+        ("has", [key]) => eval(key, input)?
+            .iter()
+            .map(|k| match (input, k) {
+                (JsonObject::Object(map), JsonObject::String(k)) => Ok(JsonObject::Bool(map.contains_key(k))),
+                (JsonObject::Array(array), JsonObject::Number(n)) => {
+                    let i: f64 = to_f64(n);
+                    Ok(JsonObject::Bool(i >= 0.0 && i < array.len() as f64))
+                }
+                _ => Err(query_error(&format!(
+                    "cannot check whether {} has a {} key",
+                    type_name(input),
+                    type_name(k)
+                ))),
+            })
+            .collect(),
+        ("map", [f]) => {
+            let iterate: Filter = Filter::Iterate(Box::new(Filter::Identity));
+            let mut outputs: Vec<JsonObject> = vec![];
+            for v in eval(&iterate, input)? {
+                outputs.extend(eval(f, &v)?);
+            }
+            Ok(vec![JsonObject::Array(outputs)])
+        }
+        ("select", [f]) => Ok(eval(f, input)?
+            .iter()
+            .filter(|v| is_truthy(v))
+            .map(|_| input.clone())
+            .collect()),
+        ("add", []) => {
+            let values: Vec<JsonObject> = match input {
+                JsonObject::Array(array) => array.clone(),
+                JsonObject::Object(map) => map.values().cloned().collect(),
+                JsonObject::Null => vec![],
+                other => return Err(query_error(&format!("cannot add the elements of {}", type_name(other)))),
+            };
+            let sum: JsonObject = values
+                .iter()
+                .try_fold(JsonObject::Null, |sum, v| binary(BinaryOp::Add, &sum, v))?;
+            Ok(vec![sum])
+        }
+        ("tostring", []) => Ok(vec![match input {
+            JsonObject::String(_) => input.clone(),
+            other => JsonObject::String(to_string(other)),
+        }]),
+        _ => Err(query_error(&format!("{}/{} is not defined", name, args.len()))),
+    }
+}
+
+fn query_error(msg: &str) -> JsonPretError {
+    JsonPretError::QueryError(QueryError::new(msg))
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, query::query, to_string, JsonPretError};
+
+    const INPUT: &str = r#"{
+        "name": "jp",
+        "version": [1, 2, 3],
+        "users": [
+            {"id": 1, "name": "alice", "admin": true, "tags": ["a", "b"]},
+            {"id": 2, "name": "bob", "admin": false, "tags": []},
+            {"id": 3, "name": "carol", "tags": ["c"]}
+        ],
+        "a b": {"c": null}
+    }"#;
+
+    /// フィルタの出力を compact な JSON にして空白区切りで返す
+    fn run(filter: &str) -> String {
+        let input = parse(INPUT).unwrap();
+        match query(filter, &input) {
+            Ok(outputs) => outputs.iter().map(to_string).collect::<Vec<_>>().join(" "),
+            Err(e) => panic!("{}: {}", filter, e),
+        }
+    }
+
+    #[test]
+    fn test_path() {
+        assert_eq!(run(".name"), r#""jp""#);
+        assert_eq!(run(".version[1]"), "2");
+        assert_eq!(run(".version[-1]"), "3");
+        assert_eq!(run(".version[5]"), "null");
+        assert_eq!(run(".users[0].name"), r#""alice""#);
+        assert_eq!(run(r#"."a b".c"#), "null");
+        assert_eq!(run(r#".["a b"]"#), r#"{"c":null}"#);
+        assert_eq!(run(".missing.deep"), "null");
+        assert_eq!(run(".version[1:]"), "[2,3]");
+        assert_eq!(run(".version[:-1]"), "[1,2]");
+        assert_eq!(run(".name[0:1]"), r#""j""#);
+        assert_eq!(run(".version[]"), "1 2 3");
+        assert_eq!(run(".users[].tags[]"), r#""a" "b" "c""#);
+        assert_eq!(run(".name[]?"), "");
+        assert_eq!(run(r#"."a b" | .."#), r#"{"c":null} null"#);
+    }
+
+    #[test]
+    fn test_pipe_and_comma() {
+        assert_eq!(run(".users[] | .id"), "1 2 3");
+        assert_eq!(run(".name, .version[0]"), r#""jp" 1"#);
+        assert_eq!(run("[.users[] | .name]"), r#"["alice","bob","carol"]"#);
+        assert_eq!(run(".users | map(.id * 10)"), "[10,20,30]");
+        assert_eq!(run("[.version[] | . + 1] | add"), "9");
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(run(".users[] | select(.admin) | .name"), r#""alice""#);
+        assert_eq!(run(".users[] | select(.admin | not) | .id"), "2 3");
+        assert_eq!(run(".users[] | select(.id >= 2 and (.tags | length) > 0) | .name"), r#""carol""#);
+        assert_eq!(run(r#"[.users[] | select(.name == "bob" or .id == 1) | .id]"#), "[1,2]");
+        assert_eq!(run(".users[] | .admin // \"unknown\""), r#"true "unknown" "unknown""#);
+    }
+
+    #[test]
+    fn test_object_construction() {
+        assert_eq!(run(".users[0] | {id, n: .name}"), r#"{"id":1,"n":"alice"}"#);
+        assert_eq!(run(r#"{"v": .version[0], (.name): 1}"#), r#"{"v":1,"jp":1}"#);
+        assert_eq!(run("{id: .users[].id}"), r#"{"id":1} {"id":2} {"id":3}"#);
+        assert_eq!(run("{} + {a: 1} + {a: 2, b: .version | length}"), r#"{"a":2,"b":3}"#);
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(run("keys"), r#"["a b","name","users","version"]"#);
+        assert_eq!(run("keys_unsorted"), r#"["name","version","users","a b"]"#);
+        assert_eq!(run(".version | keys"), "[0,1,2]");
+        assert_eq!(run("length, (.users | length), (.name | length), (null | length)"), "4 3 2 0");
+        assert_eq!(run("[.[] | type]"), r#"["string","array","array","object"]"#);
+        assert_eq!(run(r#"has("name"), (.version | has(3))"#), "true false");
+        assert_eq!(run("[.version[] | tostring]"), r#"["1","2","3"]"#);
+        assert_eq!(run("[empty, 1]"), "[1]");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(run("1 + 2 * 3, (1 + 2) * 3, 7 % 3, 7 / 2, -1 - 1"), "7 9 1 3.5 -2");
+        assert_eq!(run("9007199254740993 + 0"), "9007199254740993");
+        assert_eq!(run("-9007199254740993, -(-9007199254740993), -1.5"), "-9007199254740993 9007199254740993 -1.5");
+        assert_eq!(run("-9007199254740993 < -9007199254740992, -9007199254740993 == -9007199254740992"), "true false");
+        assert_eq!(run(r#""a" + "b", [1, 2] - [2], null + 1"#), r#""ab" [1] 1"#);
+        assert_eq!(run("[1, null, true, \"a\", [], {}] | map(. < \"b\")"), "[true,true,true,true,false,false]");
+        assert_eq!(run("1 == 1.0, [1, 2] < [1, 3]"), "true true");
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = parse(INPUT).unwrap();
+        for filter in [".[", "map(", ".a |", "{(1)}", "1 +", ".a b", "@"] {
+            assert!(
+                matches!(query(filter, &input), Err(JsonPretError::QueryError(_))),
+                "{}",
+                filter
+            );
+        }
+        for filter in [".name.x", ".version.a", ".name[]", "1 / 0", "{(1): 2}", "foo", "true | length"] {
+            assert!(
+                matches!(query(filter, &input), Err(JsonPretError::QueryError(_))),
+                "{}",
+                filter
+            );
+        }
+        assert_eq!(
+            query(".name.x", &input).unwrap_err().to_string(),
+            r#"QueryError: cannot index string with "x""#
+        );
+        assert_eq!(
+            query(".a ]", &input).unwrap_err().to_string(),
+            r#"QueryError: an unexpected Punct("]") at column 4"#
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let input = parse(INPUT).unwrap();
+        let deep: [String; 6] = [
+            format!("{}1{}", "(".repeat(50000), ")".repeat(50000)),
+            format!("{}1{}", "[".repeat(50000), "]".repeat(50000)),
+            "-".repeat(50000) + "1",
+            ".a".repeat(50000),
+            vec!["1"; 50000].join(" + "),
+            vec!["."; 50000].join(" | "),
+        ];
+        for filter in &deep {
+            assert_eq!(
+                query(filter, &input).unwrap_err().to_string(),
+                "QueryError: the nesting depth exceeds the limit of 64",
+                "{}",
+                &filter[..10]
+            );
+        }
+        assert_eq!(run(&format!("{}1{}", "(".repeat(60), ")".repeat(60))), "1");
+        assert_eq!(run(&vec!["1"; 60].join(" + ")), "60");
+        assert_eq!(run(&("-".repeat(60) + "1")), "1");
+    }
+}
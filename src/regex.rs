@@ -0,0 +1,469 @@
+//! JSONPath の `match()` と `search()` で使う I-Regexp (RFC 9485) の正規表現
+//!
+//! パースした正規表現を NFA の命令列にし、入力の各位置で到達できる状態の集合を1文字ずつ進めて照合する。
+//! バックトラックしないため、照合にかかる時間は入力の長さと命令列の長さの積に比例する。
+//!
+//! Unicode の文字カテゴリ(`\p{..}`, `\P{..}`)には対応していない。リテラルのパターンに含まれる場合は
+//! `JsonPath::parse` がエラーを返す。
+
+use crate::parser::MAX_DEPTH;
+
+/// 命令列の長さの上限。`{n,m}` は展開するため、入れ子にすると命令列が大きくなる。
+const MAX_PROGRAM: usize = 100_000;
+
+/// 正規表現を使えない理由
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RegexError {
+    /// I-Regexp として正しくない
+    Invalid,
+    /// I-Regexp として正しいが対応していない(文字カテゴリ、深すぎる入れ子、大きすぎる繰り返し)
+    Unsupported,
+}
+
+/// パース済みの正規表現
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Regex {
+    program: Vec<Inst>,
+}
+
+/// NFA の命令
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    /// 1文字を読む
+    Char(char),
+    Any,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+    /// 2つの命令の両方へ進む
+    Split(usize, usize),
+    Jump(usize),
+    /// 一致した
+    Match,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Char(char),
+    /// `.`。改行と復帰以外の任意の文字。
+    Any,
+    /// `[...]`
+    Class { negated: bool, ranges: Vec<(char, char)> },
+    /// `(...)`
+    Group(Vec<Vec<Node>>),
+    /// `*`, `+`, `?`, `{n,m}`。`max` が `None` の場合は上限なし。
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+impl Regex {
+    /// I-Regexp として正しくない場合と、対応していない構文を含む場合はエラーを返す
+    pub(crate) fn parse(pattern: &str) -> Result<Regex, RegexError> {
+        let mut parser: RegexParser = RegexParser {
+            chars: pattern.chars().collect(),
+            index: 0,
+            depth: 0,
+            unsupported: false,
+        };
+        let branches: Option<Vec<Vec<Node>>> = parser.parse_branches();
+        let branches: Vec<Vec<Node>> = match branches {
+            Some(branches) if parser.index == parser.chars.len() => branches,
+            _ if parser.unsupported => return Err(RegexError::Unsupported),
+            _ => return Err(RegexError::Invalid),
+        };
+        let mut program: Vec<Inst> = vec![];
+        compile_branches(&branches, &mut program).ok_or(RegexError::Unsupported)?;
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    /// `input` 全体が一致するかどうか
+    pub(crate) fn is_match(&self, input: &str) -> bool {
+        self.run(input, true)
+    }
+
+    /// `input` の一部が一致するかどうか
+    pub(crate) fn is_found(&self, input: &str) -> bool {
+        self.run(input, false)
+    }
+
+    /// 命令列を実行する。`anchored` の場合は入力全体が一致する必要がある。
+    fn run(&self, input: &str, anchored: bool) -> bool {
+        let mut current: Threads = Threads::new(self.program.len());
+        let mut next: Threads = Threads::new(self.program.len());
+        current.add(&self.program, 0);
+        let mut chars = input.chars();
+        loop {
+            let c: Option<char> = chars.next();
+            if current.pcs.iter().any(|&pc| matches!(self.program[pc], Inst::Match)) && (!anchored || c.is_none()) {
+                return true;
+            }
+            let Some(c) = c else {
+                return false;
+            };
+            for &pc in &current.pcs {
+                let step: bool = match &self.program[pc] {
+                    Inst::Char(expect) => *expect == c,
+                    Inst::Any => c != '\n' && c != '\r',
+                    Inst::Class { negated, ranges } => {
+                        ranges.iter().any(|(from, to)| *from <= c && c <= *to) != *negated
+                    }
+                    _ => false,
+                };
+                if step {
+                    next.add(&self.program, pc + 1);
+                }
+            }
+            // 部分一致では、どの位置からでも照合を始められる
+            if !anchored {
+                next.add(&self.program, 0);
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+    }
+}
+
+/// 同じ位置で到達できる命令の集合
+struct Threads {
+    pcs: Vec<usize>,
+    /// 命令ごとに、集合に含まれているかどうか
+    added: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads { pcs: vec![], added: vec![false; len] }
+    }
+
+    /// `pc` と、そこから文字を読まずに到達できる命令を加える
+    fn add(&mut self, program: &[Inst], pc: usize) {
+        let mut stack: Vec<usize> = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if self.added[pc] {
+                continue;
+            }
+            self.added[pc] = true;
+            match program[pc] {
+                Inst::Split(a, b) => stack.extend([b, a]),
+                Inst::Jump(to) => stack.push(to),
+                _ => self.pcs.push(pc),
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pcs.clear();
+        self.added.fill(false);
+    }
+}
+
+struct RegexParser {
+    chars: Vec<char>,
+    index: usize,
+    /// `(...)` の入れ子の深さ
+    depth: usize,
+    /// 対応していない構文があったかどうか
+    unsupported: bool,
+}
+
+impl RegexParser {
+    fn parse_branches(&mut self) -> Option<Vec<Vec<Node>>> {
+        let mut branches: Vec<Vec<Node>> = vec![self.parse_branch()?];
+        while self.eat('|') {
+            branches.push(self.parse_branch()?);
+        }
+        Some(branches)
+    }
+
+    fn parse_branch(&mut self) -> Option<Vec<Node>> {
+        let mut nodes: Vec<Node> = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom: Node = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Some(nodes)
+    }
+
+    fn parse_atom(&mut self) -> Option<Node> {
+        let c: char = self.next()?;
+        match c {
+            '.' => Some(Node::Any),
+            '(' => {
+                if self.depth >= MAX_DEPTH {
+                    self.unsupported = true;
+                    return None;
+                }
+                self.depth += 1;
+                let branches: Vec<Vec<Node>> = self.parse_branches()?;
+                self.depth -= 1;
+                self.eat(')').then_some(Node::Group(branches))
+            }
+            '[' => self.parse_class(),
+            '\\' => Some(Node::Char(self.parse_escape()?)),
+            ')' | '*' | '+' | '?' | '{' | '}' | ']' => None,
+            c => Some(Node::Char(c)),
+        }
+    }
+
+    /// 量指定子があれば `atom` を `Node::Repeat` にする
+    fn parse_quantifier(&mut self, atom: Node) -> Option<Node> {
+        let (min, max): (usize, Option<usize>) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.index += 1;
+                let min: usize = self.parse_count()?;
+                let max: Option<usize> = if self.eat(',') {
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.parse_count()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if !self.eat('}') || max.is_some_and(|max| max < min) {
+                    return None;
+                }
+                return Some(Node::Repeat { node: Box::new(atom), min, max });
+            }
+            _ => return Some(atom),
+        };
+        self.index += 1;
+        Some(Node::Repeat { node: Box::new(atom), min, max })
+    }
+
+    fn parse_count(&mut self) -> Option<usize> {
+        let start: usize = self.index;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.index += 1;
+        }
+        self.chars[start..self.index].iter().collect::<String>().parse().ok()
+    }
+
+    /// `[` の後の文字クラスを読む
+    fn parse_class(&mut self) -> Option<Node> {
+        let negated: bool = self.eat('^');
+        let mut ranges: Vec<(char, char)> = vec![];
+        // 先頭と末尾の - は文字として扱う
+        if self.eat('-') {
+            ranges.push(('-', '-'));
+        }
+        loop {
+            match self.next()? {
+                ']' => return Some(Node::Class { negated, ranges }),
+                '-' if self.peek() == Some(']') => ranges.push(('-', '-')),
+                '[' | '-' => return None,
+                c => {
+                    let from: char = if c == '\\' { self.parse_escape()? } else { c };
+                    let to: char = if self.peek() == Some('-') && self.chars.get(self.index + 1) != Some(&']') {
+                        self.index += 1;
+                        match self.next()? {
+                            '\\' => self.parse_escape()?,
+                            '[' | ']' | '-' => return None,
+                            c => c,
+                        }
+                    } else {
+                        from
+                    };
+                    if to < from {
+                        return None;
+                    }
+                    ranges.push((from, to));
+                }
+            }
+        }
+    }
+
+    /// `\` の後のエスケープされた文字を読む
+    fn parse_escape(&mut self) -> Option<char> {
+        match self.next()? {
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            c @ ('(' | ')' | '*' | '+' | '-' | '.' | '?' | '[' | '\\' | ']' | '^' | '{' | '|' | '}') => {
+                Some(c)
+            }
+            'p' | 'P' => {
+                // 正しい文字カテゴリであれば、対応していない構文として扱う
+                let start: usize = self.index;
+                let close: usize = self.chars[start..].iter().position(|c| *c == '}')? + start;
+                let name: String = self.chars.get(start + 1..close)?.iter().collect();
+                if self.chars[start] == '{' && CATEGORIES.contains(&name.as_str()) {
+                    self.unsupported = true;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c: Option<char> = self.peek();
+        self.index += 1;
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// RFC 9485 の `\p{..}` で使える文字カテゴリ
+const CATEGORIES: [&str; 36] = [
+    "L", "Lu", "Ll", "Lt", "Lm", "Lo", "M", "Mn", "Mc", "Me", "N", "Nd", "Nl", "No", "P", "Pc", "Pd", "Ps", "Pe",
+    "Pi", "Pf", "Po", "Z", "Zs", "Zl", "Zp", "S", "Sm", "Sc", "Sk", "So", "C", "Cc", "Cf", "Co", "Cn",
+];
+
+// --- 命令列への変換 ---
+// 命令列が `MAX_PROGRAM` より長くなる場合は `None` を返す
+
+fn compile_branches(branches: &[Vec<Node>], program: &mut Vec<Inst>) -> Option<()> {
+    // 最後以外の候補の前に Split を置き、候補の後は全て末尾へ飛ぶ
+    let mut jumps: Vec<usize> = vec![];
+    for (i, branch) in branches.iter().enumerate() {
+        let split: usize = program.len();
+        if i + 1 < branches.len() {
+            emit(program, Inst::Split(split + 1, 0))?;
+        }
+        for node in branch {
+            compile_node(node, program)?;
+        }
+        if i + 1 < branches.len() {
+            jumps.push(program.len());
+            emit(program, Inst::Jump(0))?;
+            program[split] = Inst::Split(split + 1, program.len());
+        }
+    }
+    let end: usize = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+    Some(())
+}
+
+fn compile_node(node: &Node, program: &mut Vec<Inst>) -> Option<()> {
+    match node {
+        Node::Char(c) => emit(program, Inst::Char(*c)),
+        Node::Any => emit(program, Inst::Any),
+        Node::Class { negated, ranges } => emit(program, Inst::Class { negated: *negated, ranges: ranges.clone() }),
+        Node::Group(branches) => compile_branches(branches, program),
+        Node::Repeat { node, min, max } => {
+            // 空文字列にしか一致しない場合は、繰り返しても同じ
+            let start: usize = program.len();
+            compile_node(node, program)?;
+            if program.len() == start {
+                return Some(());
+            }
+            program.truncate(start);
+            for _ in 0..*min {
+                compile_node(node, program)?;
+            }
+            match max {
+                // L: Split(L+1, end) node Jump(L)
+                None => {
+                    let split: usize = program.len();
+                    emit(program, Inst::Split(split + 1, 0))?;
+                    compile_node(node, program)?;
+                    emit(program, Inst::Jump(split))?;
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                // Split(L1, end) node Split(L2, end) node ... end:
+                Some(max) => {
+                    let mut splits: Vec<usize> = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        emit(program, Inst::Split(0, 0))?;
+                        compile_node(node, program)?;
+                    }
+                    let end: usize = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+            Some(())
+        }
+    }
+}
+
+fn emit(program: &mut Vec<Inst>, inst: Inst) -> Option<()> {
+    if program.len() >= MAX_PROGRAM {
+        return None;
+    }
+    program.push(inst);
+    Some(())
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::regex::{Regex, RegexError};
+
+    #[test]
+    fn test_is_match() {
+        let cases = [
+            ("abc", "abc", true),
+            ("abc", "abcd", false),
+            ("a.c", "a\u{3042}c", true),
+            ("a.c", "a\nc", false),
+            ("a*b+c?", "aaabb", true),
+            ("a*b+c?", "ac", false),
+            ("(ab|cd){2}", "abcd", true),
+            ("(ab|cd){2,}", "ab", false),
+            ("x{1,3}", "xxxx", false),
+            ("[a-c]+[^0-9]", "cabz", true),
+            ("[a-c]+[^0-9]", "cab1", false),
+            ("[-a]*", "-a-", true),
+            ("\\.\\[", ".[", true),
+            ("(a?){3}", "", true),
+            ("(a*)*b", "aaaaaaaaaa", false),
+            ("^a$", "^a$", true),
+        ];
+        for (pattern, input, expect) in cases {
+            let regex = Regex::parse(pattern).unwrap();
+            assert_eq!(regex.is_match(input), expect, "{} {}", pattern, input);
+        }
+    }
+
+    #[test]
+    fn test_linear_time() {
+        // バックトラックすると指数時間かかるパターン
+        let input: String = "a".repeat(1000);
+        assert!(!Regex::parse("(a|aa)*c").unwrap().is_match(&input));
+        assert!(!Regex::parse("(a*)*b").unwrap().is_found(&input));
+        assert!(Regex::parse("(a?){500}a{500}").unwrap().is_match(&input));
+        assert!(Regex::parse("(){1000000000}").unwrap().is_match(""));
+    }
+
+    #[test]
+    fn test_is_found() {
+        let regex = Regex::parse("b[0-9]").unwrap();
+        assert!(regex.is_found("ab1c"));
+        assert!(!regex.is_found("ab"));
+        assert!(Regex::parse("").unwrap().is_found("abc"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for pattern in ["(a", "a)", "*", "a{2,1}", "[b-a]", "[a", "\\d", "\\p{X}", "\\pL", "a{", "[[]"] {
+            assert_eq!(Regex::parse(pattern), Err(RegexError::Invalid), "{}", pattern);
+        }
+        let deep: String = "(".repeat(300) + &")".repeat(300);
+        for pattern in ["\\p{L}", "[\\P{Nd}]", "(a{1000}){1000}", &deep] {
+            assert_eq!(Regex::parse(pattern), Err(RegexError::Unsupported), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use std::{
+    fmt::{self, Write},
+    io,
+};
+
+use crate::JsonObject;
+
+/// インデントの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// 指定した数のスペース
+    Spaces(usize),
+    /// タブ1つ
+    Tab,
+}
+
+/// 改行コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Newline {
+    /// \n
+    Lf,
+    /// \r\n
+    CrLf,
+}
+
+impl Newline {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// 出力時の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// 1段あたりのインデント
+    pub indent: Indent,
+    /// 改行や空白を入れずに出力する
+    pub compact: bool,
+    /// 改行コード
+    pub newline: Newline,
+    /// 出力の最後に改行を付ける
+    pub trailing_newline: bool,
+    /// ANSI エスケープシーケンスで色を付ける
+    pub color: bool,
+    /// ASCII 以外の文字を \uXXXX にエスケープする
+    pub ascii_only: bool,
+    /// 1行に収まる Array と Object を改行せずに出力する時の、1行の最大の幅。
+    /// `None` の場合は常に改行する。
+    pub max_width: Option<usize>,
+    /// JSON で表せない `Infinity` と `NaN` をそのまま出力する。JSON5 として出力する場合に使う。
+    /// `false` の場合は `null` を出力する。
+    pub non_finite: bool,
+}
+
+impl FormatOptions {
+    /// 改行や空白を入れない設定を返す
+    pub fn compact() -> FormatOptions {
+        FormatOptions {
+            compact: true,
+            ..FormatOptions::default()
+        }
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent: Indent::Spaces(3),
+            compact: false,
+            newline: Newline::Lf,
+            trailing_newline: false,
+            color: false,
+            ascii_only: false,
+            max_width: None,
+            non_finite: false,
+        }
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[m";
+
+/// 幅を計算する時のタブ1つ分の幅
+const TAB_WIDTH: usize = 4;
+
+/// `JsonObject` を JSON 文字列として `fmt::Write` に書き込む
+pub struct Serializer<W: Write> {
+    writer: W,
+    options: FormatOptions,
+    /// 1行に収まる Array または Object を書き込んでいる途中かどうか
+    flat: bool,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Serializer<W> {
+        Serializer::with_options(writer, FormatOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: FormatOptions) -> Serializer<W> {
+        Serializer { writer, options, flat: false }
+    }
+
+    /// 書き込み先を返す
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// `value` を書き込む
+    pub fn serialize(&mut self, value: &JsonObject) -> fmt::Result {
+        self.write_value(value, 0)?;
+        self.write_trailing_newline()
+    }
+
+    /// 書き込み先を返す
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// `depth` 段目の値として `value` を書き込む
+    pub(crate) fn write_value(&mut self, value: &JsonObject, depth: usize) -> fmt::Result {
+        let column: usize = self.indent_width(depth);
+        self.write_value_at(value, depth, column, 0)
+    }
+
+    /// `column` 桁目から始まり、後ろに `trailing` 文字が続く値として `value` を書き込む
+    pub(crate) fn write_value_at(&mut self, value: &JsonObject, depth: usize, column: usize, trailing: usize) -> fmt::Result {
+        match value {
+            JsonObject::Null => self.write_colored("null", RED),
+            JsonObject::Bool(b) => write!(self.writer, "{}", b),
+            JsonObject::Number(n) if !n.is_finite() && !self.options.non_finite => self.write_colored("null", RED),
+            JsonObject::Number(n) => self.writer.write_str(n.as_str()),
+            JsonObject::String(s) => self.write_string(s, GREEN),
+            JsonObject::Array(vs) => {
+                if vs.is_empty() {
+                    return self.writer.write_str("[]");
+                }
+                let flat: bool = self.fits(value, column, trailing);
+                self.writer.write_char('[')?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i != 0 {
+                        self.write_comma()?;
+                    }
+                    self.write_line_break(depth + 1)?;
+                    let trailing: usize = if i + 1 < vs.len() { 1 } else { 0 };
+                    self.write_value_at(v, depth + 1, self.indent_width(depth + 1), trailing)?;
+                }
+                self.write_line_break(depth)?;
+                self.writer.write_char(']')?;
+                if flat {
+                    self.set_flat(false);
+                }
+                Ok(())
+            }
+            JsonObject::Object(map) => {
+                if map.is_empty() {
+                    return self.writer.write_str("{}");
+                }
+                let flat: bool = self.fits(value, column, trailing);
+                self.writer.write_char('{')?;
+                for (i, (k, v)) in map.iter().enumerate() {
+                    if i != 0 {
+                        self.write_comma()?;
+                    }
+                    self.write_line_break(depth + 1)?;
+                    self.write_key(k)?;
+                    let trailing: usize = if i + 1 < map.len() { 1 } else { 0 };
+                    let column: usize = match self.flat || self.options.max_width.is_none() {
+                        true => 0,
+                        false => self.indent_width(depth + 1) + self.measure(usize::MAX, |s| s.write_key(k)).unwrap_or(0),
+                    };
+                    self.write_value_at(v, depth + 1, column, trailing)?;
+                }
+                self.write_line_break(depth)?;
+                self.writer.write_char('}')?;
+                if flat {
+                    self.set_flat(false);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// `column` 桁目から書き込む `value` が、後ろに続く `trailing` 文字と合わせて
+    /// `max_width` に収まる場合は、1行に書き込む状態にして `true` を返す
+    fn fits(&mut self, value: &JsonObject, column: usize, trailing: usize) -> bool {
+        let fits: bool = self
+            .flat_limit(column + trailing)
+            .and_then(|limit| self.measure(limit, |s| s.write_value(value, 0)))
+            .is_some();
+        if fits {
+            self.set_flat(true);
+        }
+        fits
+    }
+
+    /// `column` 桁目から1行に書き込める幅を返す。
+    /// `max_width` が無い場合、`compact` の場合、既に1行に書き込んでいる場合は `None` を返す。
+    pub(crate) fn flat_limit(&self, column: usize) -> Option<usize> {
+        if self.flat || self.options.compact {
+            return None;
+        }
+        self.options.max_width.and_then(|w| w.checked_sub(column))
+    }
+
+    /// 1行に書き込む状態を切り替える。1行に書き込む間は改行せず、, の後に空白を入れる。
+    pub(crate) fn set_flat(&mut self, flat: bool) {
+        self.flat = flat;
+    }
+
+    /// `write` が1行に書き込む文字列の幅を返す。`limit` を超える場合は `None` を返す。
+    pub(crate) fn measure(
+        &self,
+        limit: usize,
+        write: impl FnOnce(&mut Serializer<WidthCounter>) -> fmt::Result,
+    ) -> Option<usize> {
+        let options: FormatOptions = FormatOptions { color: false, ..self.options.clone() };
+        let mut counter: Serializer<WidthCounter> = Serializer {
+            writer: WidthCounter { width: 0, limit },
+            options,
+            flat: true,
+        };
+        write(&mut counter).ok()?;
+        Some(counter.writer.width)
+    }
+
+    /// `depth` 段分のインデントの幅を返す
+    pub(crate) fn indent_width(&self, depth: usize) -> usize {
+        match self.options.indent {
+            Indent::Spaces(n) => n * depth,
+            Indent::Tab => TAB_WIDTH * depth,
+        }
+    }
+
+    /// Array の要素または Object のメンバーの間の , を書き込む。1行に書き込む場合は後ろに空白を入れる。
+    pub(crate) fn write_comma(&mut self) -> fmt::Result {
+        self.writer.write_char(',')?;
+        if self.flat {
+            self.writer.write_char(' ')?;
+        }
+        Ok(())
+    }
+
+    /// Object のキーと : を書き込む
+    pub(crate) fn write_key(&mut self, key: &str) -> fmt::Result {
+        self.write_string(key, YELLOW)?;
+        self.writer.write_str(if self.options.compact { ":" } else { ": " })
+    }
+
+    /// 括弧や , をそのまま書き込む
+    pub(crate) fn write_punct(&mut self, c: char) -> fmt::Result {
+        self.writer.write_char(c)
+    }
+
+    /// コメントをそのまま書き込む。
+    /// `compact` の場合、後ろに続く出力がコメントにならないよう行コメントの後に改行を書き込む。
+    pub(crate) fn write_comment(&mut self, comment: &str) -> fmt::Result {
+        self.writer.write_str(comment)?;
+        if self.options.compact && comment.starts_with("//") {
+            self.writer.write_str(self.options.newline.as_str())?;
+        }
+        Ok(())
+    }
+
+    /// `trailing_newline` の場合は出力の最後の改行を書き込む
+    pub(crate) fn write_trailing_newline(&mut self) -> fmt::Result {
+        if self.options.trailing_newline {
+            self.writer.write_str(self.options.newline.as_str())?;
+        }
+        Ok(())
+    }
+
+    fn write_string(&mut self, s: &str, color: &str) -> fmt::Result {
+        self.writer.write_char('"')?;
+        if self.options.color {
+            self.writer.write_str(color)?;
+        }
+        write_escaped(&mut self.writer, s, self.options.ascii_only)?;
+        if self.options.color {
+            self.writer.write_str(RESET)?;
+        }
+        self.writer.write_char('"')
+    }
+
+    /// `color` が有効な場合は色を付けて書き込む
+    fn write_colored(&mut self, s: &str, color: &str) -> fmt::Result {
+        if self.options.color {
+            write!(self.writer, "{}{}{}", color, s, RESET)
+        } else {
+            self.writer.write_str(s)
+        }
+    }
+
+    /// 改行し、`depth` 段分インデントする。`compact` の場合と1行に書き込む場合は何もしない。
+    pub(crate) fn write_line_break(&mut self, depth: usize) -> fmt::Result {
+        if self.options.compact || self.flat {
+            return Ok(());
+        }
+        self.writer.write_str(self.options.newline.as_str())?;
+        for _ in 0..depth {
+            match self.options.indent {
+                Indent::Spaces(n) => write!(self.writer, "{:n$}", "", n = n)?,
+                Indent::Tab => self.writer.write_char('\t')?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JSON の文字列として `s` をエスケープして書き込む(前後の " は書き込まない)。
+/// `ascii_only` の場合は ASCII 以外の文字を \uXXXX (必要ならサロゲートペア)にする。
+pub(crate) fn write_escaped<W: Write>(writer: &mut W, s: &str, ascii_only: bool) -> fmt::Result {
+    let mut start: usize = 0;
+    for (i, c) in s.char_indices() {
+        let escaped: Option<&str> = match c {
+            '"' => Some("\\\""),
+            '\\' => Some("\\\\"),
+            '\n' => Some("\\n"),
+            '\r' => Some("\\r"),
+            '\t' => Some("\\t"),
+            '\u{08}' => Some("\\b"),
+            '\u{0C}' => Some("\\f"),
+            _ => None,
+        };
+        let needs_code_point: bool = c < '\u{20}' || (ascii_only && !c.is_ascii());
+        if escaped.is_none() && !needs_code_point {
+            continue;
+        }
+
+        writer.write_str(&s[start..i])?;
+        match escaped {
+            Some(e) => writer.write_str(e)?,
+            None => {
+                let mut buffer: [u16; 2] = [0; 2];
+                for unit in c.encode_utf16(&mut buffer) {
+                    write!(writer, "\\u{:04x}", unit)?;
+                }
+            }
+        }
+        start = i + c.len_utf8();
+    }
+    writer.write_str(&s[start..])
+}
+
+/// 書き込まれた文字列の幅を数え、`limit` を超えたらエラーを返す `fmt::Write`
+pub(crate) struct WidthCounter {
+    width: usize,
+    limit: usize,
+}
+
+impl Write for WidthCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.width += s.chars().count();
+        if self.width > self.limit {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+/// `io::Write` を `fmt::Write` として扱うためのアダプタ
+pub(crate) struct IoWriter<W: io::Write> {
+    inner: W,
+    /// 書き込み中に発生した I/O エラー
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    pub(crate) fn new(inner: W) -> IoWriter<W> {
+        IoWriter { inner, error: None }
+    }
+
+    /// `fmt::Error` の原因になった I/O エラーを取り出す
+    pub(crate) fn take_error(&mut self) -> io::Error {
+        self.error
+            .take()
+            .unwrap_or_else(|| io::Error::other("failed to write JSON"))
+    }
+}
+
+impl<W: io::Write> Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// `value` を改行や空白を入れずに文字列にする
+pub fn to_string(value: &JsonObject) -> String {
+    to_string_with_options(value, &FormatOptions::compact())
+}
+
+/// `value` をインデントを付けて文字列にする
+pub fn to_string_pretty(value: &JsonObject) -> String {
+    to_string_with_options(value, &FormatOptions::default())
+}
+
+/// `value` を `options` に従って文字列にする
+pub fn to_string_with_options(value: &JsonObject, options: &FormatOptions) -> String {
+    let mut serializer: Serializer<String> = Serializer::with_options(String::new(), options.clone());
+    // String への書き込みは失敗しない
+    let _ = serializer.serialize(value);
+    serializer.into_inner()
+}
+
+/// `value` を `options` に従って `io::Write` に書き込む
+pub fn to_writer<W: io::Write>(writer: W, value: &JsonObject, options: &FormatOptions) -> io::Result<()> {
+    let mut serializer: Serializer<IoWriter<W>> = Serializer::with_options(IoWriter::new(writer), options.clone());
+    match serializer.serialize(value) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(serializer.get_mut().take_error()),
+    }
+}
+
+/// `{}` では改行や空白を入れずに、`{:#}` ではインデントを付けて出力する。
+impl fmt::Display for JsonObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options: FormatOptions = if f.alternate() {
+            FormatOptions::default()
+        } else {
+            FormatOptions::compact()
+        };
+        Serializer::with_options(f, options).serialize(self)
+    }
+}
+
+// --- テストコード ---
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse,
+        serializer::{to_string, to_string_pretty, to_string_with_options, to_writer, FormatOptions, Indent, Newline},
+    };
+
+    const JSON: &str = r#"{"b": [1, true, null], "a": {"c": "d", "e": []}, "f": {}}"#;
+
+    #[test]
+    fn test_to_string() {
+        let value = parse(JSON).unwrap();
+        assert_eq!(to_string(&value), r#"{"b":[1,true,null],"a":{"c":"d","e":[]},"f":{}}"#);
+    }
+
+    #[test]
+    fn test_to_string_pretty() {
+        let value = parse(JSON).unwrap();
+        let expect = [
+            "{",
+            r#"   "b": ["#,
+            "      1,",
+            "      true,",
+            "      null",
+            "   ],",
+            r#"   "a": {"#,
+            r#"      "c": "d","#,
+            r#"      "e": []"#,
+            "   },",
+            r#"   "f": {}"#,
+            "}",
+        ].join("\n");
+        assert_eq!(to_string_pretty(&value), expect);
+    }
+
+    #[test]
+    fn test_to_string_with_options() {
+        let value = parse(r#"{"a": [1]}"#).unwrap();
+
+        let options = FormatOptions {
+            indent: Indent::Tab,
+            newline: Newline::CrLf,
+            trailing_newline: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            to_string_with_options(&value, &options),
+            "{\r\n\t\"a\": [\r\n\t\t1\r\n\t]\r\n}\r\n"
+        );
+
+        let options = FormatOptions {
+            indent: Indent::Spaces(2),
+            ..FormatOptions::default()
+        };
+        assert_eq!(to_string_with_options(&value, &options), "{\n  \"a\": [\n    1\n  ]\n}");
+
+        let options = FormatOptions {
+            color: true,
+            ..FormatOptions::compact()
+        };
+        let value = parse(r#"{"a": ["b", null]}"#).unwrap();
+        assert_eq!(
+            to_string_with_options(&value, &options),
+            "{\"\x1b[33ma\x1b[m\":[\"\x1b[32mb\x1b[m\",\x1b[31mnull\x1b[m]}"
+        );
+    }
+
+    #[test]
+    fn test_max_width() {
+        let value = parse(r#"{"points": [[1, 2], [3, 4]], "name": "abc", "nested": {"a": [1], "b": {}}}"#).unwrap();
+        let options = FormatOptions {
+            indent: Indent::Spaces(2),
+            max_width: Some(32),
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            to_string_with_options(&value, &options),
+            "{\n  \"points\": [[1, 2], [3, 4]],\n  \"name\": \"abc\",\n  \"nested\": {\"a\": [1], \"b\": {}}\n}"
+        );
+
+        // , を含めて収まらない場合は改行する
+        let options = FormatOptions { max_width: Some(28), ..options };
+        assert_eq!(
+            to_string_with_options(&value, &options),
+            "{\n  \"points\": [\n    [1, 2],\n    [3, 4]\n  ],\n  \"name\": \"abc\",\n  \"nested\": {\n    \"a\": [1],\n    \"b\": {}\n  }\n}"
+        );
+
+        // 全体が収まる場合はルートも1行になる
+        let options = FormatOptions { max_width: Some(80), ..options };
+        assert_eq!(to_string_with_options(&value, &options), r#"{"points": [[1, 2], [3, 4]], "name": "abc", "nested": {"a": [1], "b": {}}}"#);
+        let options = FormatOptions { max_width: Some(80), ..FormatOptions::compact() };
+        assert_eq!(to_string_with_options(&value, &options), to_string(&value));
+    }
+
+    #[test]
+    fn test_escape() {
+        let value = parse(r#"["\"\\\/\b\f\n\r\t", "\u0001\u001F", "あ😄"]"#).unwrap();
+        assert_eq!(
+            to_string(&value),
+            r#"["\"\\/\b\f\n\r\t","\u0001\u001f","あ😄"]"#
+        );
+
+        let options = FormatOptions {
+            ascii_only: true,
+            ..FormatOptions::compact()
+        };
+        assert_eq!(
+            to_string_with_options(&value, &options),
+            r#"["\"\\/\b\f\n\r\t","\u0001\u001f","\u3042\ud83d\ude04"]"#
+        );
+
+        // キーもエスケープされる
+        let value = parse(r#"{"a\"b": "c"}"#).unwrap();
+        assert_eq!(to_string(&value), r#"{"a\"b":"c"}"#);
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        let json = r#"{"\u0000\"": ["\\u0041", "\ud83d\ude04\u00e9\n", "</script>"]}"#;
+        let value = parse(json).unwrap();
+        assert_eq!(parse(&to_string(&value)).unwrap(), value);
+
+        let options = FormatOptions {
+            ascii_only: true,
+            ..FormatOptions::default()
+        };
+        let ascii = to_string_with_options(&value, &options);
+        assert!(ascii.is_ascii());
+        assert_eq!(parse(&ascii).unwrap(), value);
+    }
+
+    #[test]
+    fn test_numbers_are_preserved() {
+        let json = "[9007199254740993,1.0,-0,1.50e+10,123456789012345678901234567890]";
+        assert_eq!(to_string(&parse(json).unwrap()), json);
+    }
+
+    #[test]
+    fn test_to_writer() {
+        let value = parse(JSON).unwrap();
+        let mut buffer: Vec<u8> = vec![];
+        to_writer(&mut buffer, &value, &FormatOptions::default()).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), to_string_pretty(&value));
+    }
+
+    #[test]
+    fn test_display() {
+        let value = parse(JSON).unwrap();
+        assert_eq!(format!("{}", value), to_string(&value));
+        assert_eq!(format!("{:#}", value), to_string_pretty(&value));
+    }
+}
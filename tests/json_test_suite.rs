@@ -0,0 +1,110 @@
+//! JSONTestSuite (https://github.com/nst/JSONTestSuite) のテストケースを使ったテスト
+//!
+//! - `y_` で始まるファイルは受理されなければならない
+//! - `n_` で始まるファイルは拒否されなければならない
+//! - `i_` で始まるファイルは実装依存だが、パニックしてはならない
+
+use std::{fs, path::PathBuf};
+
+/// テストケースのファイル名と内容の一覧を返す
+fn cases(prefix: &str) -> Vec<(String, Vec<u8>)> {
+    let dir: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "json_test_suite"].iter().collect();
+    let mut cases: Vec<(String, Vec<u8>)> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            if name.starts_with(prefix) && name.ends_with(".json") {
+                Some((name, fs::read(&path).unwrap()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no test cases for {}", prefix);
+    cases
+}
+
+/// UTF-8 として正しくない入力は拒否されたものとして扱う
+fn parse(bytes: &[u8]) -> Result<json_prettier::JsonObject, String> {
+    let input = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+    json_prettier::parse(input).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_accepted() {
+    let failures: Vec<String> = cases("y_")
+        .into_iter()
+        .filter_map(|(name, bytes)| parse(&bytes).err().map(|e| format!("{}: {}", name, e)))
+        .collect();
+    assert!(failures.is_empty(), "rejected:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_round_trip() {
+    for (name, bytes) in cases("y_") {
+        let value = parse(&bytes).unwrap();
+        let output = json_prettier::to_string(&value);
+        assert_eq!(parse(output.as_bytes()), Ok(value), "{}: {}", name, output);
+    }
+}
+
+/// ストリームで整形した結果を返す
+fn format_stream(bytes: &[u8], options: &json_prettier::FormatOptions) -> Result<String, String> {
+    let mut output: Vec<u8> = vec![];
+    json_prettier::format_reader(bytes, &mut output, options).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_stream_format_matches_tree() {
+    let options = [json_prettier::FormatOptions::default(), json_prettier::FormatOptions::compact()];
+    for (name, bytes) in cases("y_") {
+        let value = parse(&bytes).unwrap();
+        // 重複したキーは Tree ではまとめられるため比較しない
+        if name.contains("duplicate") {
+            continue;
+        }
+        for options in &options {
+            let expect = json_prettier::to_string_with_options(&value, options);
+            assert_eq!(format_stream(&bytes, options), Ok(expect), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn test_stream_format_rejected() {
+    let failures: Vec<String> = cases("n_")
+        .into_iter()
+        .filter(|(_, bytes)| format_stream(bytes, &json_prettier::FormatOptions::default()).is_ok())
+        .map(|(name, _)| name)
+        .collect();
+    assert!(failures.is_empty(), "accepted:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_rejected() {
+    let failures: Vec<String> = cases("n_")
+        .into_iter()
+        .filter(|(_, bytes)| parse(bytes).is_ok())
+        .map(|(name, _)| name)
+        .collect();
+    assert!(failures.is_empty(), "accepted:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_implementation_defined() {
+    for (_, bytes) in cases("i_") {
+        let _ = parse(&bytes);
+    }
+}
+
+#[test]
+fn test_lone_surrogates_rejected() {
+    for (name, bytes) in cases("i_") {
+        if name.contains("surrogate") {
+            assert!(parse(&bytes).is_err(), "accepted: {}", name);
+        }
+    }
+}